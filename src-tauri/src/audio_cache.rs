@@ -0,0 +1,107 @@
+// In-memory cache of decoded audio, so re-running VAD on the same file with other
+// settings skips decoding and resampling. Entries are keyed by path, modification time
+// and the settings that shape the decoded samples; an entry whose file has changed
+// since is dropped on the next lookup. Least recently used entries are evicted once
+// the total size passes the limit.
+
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// About 4.5 hours of 16kHz mono audio
+pub const DEFAULT_MAX_BYTES: usize = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedAudioKey {
+    path: PathBuf,
+    modified: SystemTime,
+    settings: String,
+}
+
+impl DecodedAudioKey {
+    pub fn for_file(file_path: &str, settings: String) -> io::Result<Self> {
+        let modified = std::fs::metadata(file_path)?.modified()?;
+        Ok(Self { path: PathBuf::from(file_path), modified, settings })
+    }
+}
+
+#[derive(Debug)]
+pub struct DecodedAudioCache {
+    // Least recently used first
+    entries: VecDeque<(DecodedAudioKey, Vec<i16>)>,
+    max_bytes: usize,
+    used_bytes: usize,
+}
+
+impl Default for DecodedAudioCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BYTES)
+    }
+}
+
+fn size_of(samples: &[i16]) -> usize {
+    std::mem::size_of_val(samples)
+}
+
+impl DecodedAudioCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self { entries: VecDeque::new(), max_bytes, used_bytes: 0 }
+    }
+
+    /// Copy of the cached samples for `key`, marking them as recently used
+    pub fn get(&mut self, key: &DecodedAudioKey) -> Option<Vec<i16>> {
+        // The file was rewritten since these were decoded
+        let stale: Vec<usize> = self.entries.iter().enumerate()
+            .filter(|(_, (cached, _))| cached.path == key.path && cached.modified != key.modified)
+            .map(|(position, _)| position)
+            .collect();
+        for position in stale.into_iter().rev() {
+            self.remove(position);
+        }
+
+        let position = self.entries.iter().position(|(cached, _)| cached == key)?;
+        let entry = self.entries.remove(position)?;
+        let samples = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(samples)
+    }
+
+    // Audio larger than the whole cache is not stored
+    pub fn insert(&mut self, key: DecodedAudioKey, samples: Vec<i16>) {
+        if let Some(position) = self.entries.iter().position(|(cached, _)| *cached == key) {
+            self.remove(position);
+        }
+        if size_of(&samples) > self.max_bytes {
+            return;
+        }
+        self.used_bytes += size_of(&samples);
+        self.entries.push_back((key, samples));
+        self.evict();
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.evict();
+    }
+
+    /// Drop every entry, returning how many bytes were freed
+    pub fn clear(&mut self) -> usize {
+        let freed = self.used_bytes;
+        self.entries.clear();
+        self.used_bytes = 0;
+        freed
+    }
+
+    fn evict(&mut self) {
+        while self.used_bytes > self.max_bytes && !self.entries.is_empty() {
+            self.remove(0);
+        }
+    }
+
+    fn remove(&mut self, position: usize) {
+        if let Some((_, samples)) = self.entries.remove(position) {
+            self.used_bytes -= size_of(&samples);
+        }
+    }
+}
@@ -1,451 +1,3057 @@
-use crate::{utils};
-use symphonia::core::audio::{AudioBufferRef, SampleBuffer};
-use symphonia::core::codecs::{CODEC_TYPE_NULL, DecoderOptions};
+use crate::audio_cache::{DecodedAudioCache, DecodedAudioKey};
+use crate::segment_cache::SegmentCache;
+use crate::{dsp, utils, wav};
+use rubato::{Resampler, SincFixedIn};
+use symphonia::core::audio::{AudioBufferRef, Channels, SampleBuffer};
+use symphonia::core::codecs::{CodecType, CODEC_TYPE_NULL, DecoderOptions};
 use symphonia::core::errors::Error as SymphoniaError;
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo, Track};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::{MetadataOptions, StandardVisualKey, Visual};
+use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::units::Time;
 use std::fs::File;
-use voice_activity_detector::{VoiceActivityDetector, IteratorExt, LabeledAudio};
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use voice_activity_detector::{VoiceActivityDetector, IteratorExt};
 
+// Console diagnostics (decisions, rates, segment counts) are only useful while
+// developing, so release builds stay quiet unless they are switched on
+static DEBUG_LOGGING: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
+/// Print pipeline diagnostics to the console. On by default in debug builds only.
+pub fn set_debug_logging(enabled: bool) {
+    DEBUG_LOGGING.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn debug_logging_enabled() -> bool {
+    DEBUG_LOGGING.load(Ordering::Relaxed)
+}
+
+// Shared by the whole crate so commands and the HTTP client follow the same switch
+macro_rules! debug_log {
+    ($($arg:tt)*) => {
+        if $crate::audio_processing::debug_logging_enabled() {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use debug_log;
+
+/// A detected stretch of speech. By default each segment carries its audio twice, as
+/// samples and as base64 WAV (another third larger), so a long recording holds all of
+/// its speech in memory about 2.3 times over. With lazy segment audio both are left
+/// empty and the frontend fetches a segment's audio on demand via get_segment_base64.
 #[derive(Debug, serde::Serialize)]
 pub struct AudioSegment {
     pub start_sample: i64,
     pub end_sample: i64,
     pub start_time_seconds: f64,
     pub end_time_seconds: f64,
-    pub audio_data: Vec<i16>,
-    pub audio_base64: String, // Base64-encoded WAV data for browser playback
+    pub audio_data: Vec<i16>, // Empty with lazy segment audio
+    pub audio_base64: String, // Base64-encoded WAV data for browser playback, empty with lazy segment audio
+    pub snr_db: Option<f64>, // Estimated signal-to-noise ratio, None without non-speech audio to compare
+    // Seconds at the start repeated from the end of the previous segment, when a long
+    // segment was split into overlapping parts; 0 otherwise
+    pub overlap_seconds: f64,
+    pub channel: Option<u8>, // Source channel (0 = left) in per-channel mode, None when channels were mixed
 }
 
-pub struct AudioProcessor {
-    sample_rate: utils::SampleRate,
+/// Where a segment lies, without its audio; what estimate_segments returns
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SegmentInfo {
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
 }
 
-impl AudioProcessor {
-    pub fn new() -> Self {
-        Self {
-            sample_rate: utils::SampleRate::SixteenkHz, // Default to 16kHz
+/// How a processing run ended, so an empty result can be explained
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+    Speech,
+    NoSpeech, // Decoded fine, VAD found nothing
+    NoAudio,  // Empty file, no audio track or no samples
+}
+
+/// Something about the input worth telling the user, noticed while processing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    Clipping,           // Many samples at full scale
+    ExtraChannels,      // Channels beyond the first two were ignored in per-channel mode
+    LateSpeech,         // No speech in the first LATE_SPEECH_SECONDS
+    ManyShortSegments,  // Speech broken up into lots of very short segments
+    WavDataLength,      // A WAV header's data size doesn't match the file
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProcessingWarning {
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+/// Segments found in a file, with how the run ended
+#[derive(Debug, serde::Serialize)]
+pub struct ProcessingReport {
+    pub status: ProcessingStatus,
+    pub segments: Vec<AudioSegment>,
+    // Speech probability per time step across the file, only when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speech_probabilities: Option<Vec<f32>>,
+    pub warnings: Vec<ProcessingWarning>,
+}
+
+impl ProcessingReport {
+    /// Files without audio become a `NoAudio` report; every other error is passed through
+    pub fn from_result(result: Result<Vec<AudioSegment>, Box<dyn std::error::Error>>) -> Result<Self, Box<dyn std::error::Error>> {
+        match result {
+            Ok(segments) => Ok(Self {
+                status: if segments.is_empty() { ProcessingStatus::NoSpeech } else { ProcessingStatus::Speech },
+                segments,
+                speech_probabilities: None,
+                warnings: Vec::new(),
+            }),
+            Err(e) if e.downcast_ref::<DecodeError>().is_some_and(DecodeError::is_no_audio) => {
+                debug_log!("No audio in file: {}", e);
+                Ok(Self { status: ProcessingStatus::NoAudio, segments: Vec::new(), speech_probabilities: None, warnings: Vec::new() })
+            }
+            Err(e) => Err(e),
         }
     }
+}
 
-    // Decode audio using Symphonia (supports MP3, WAV, FLAC, etc.)
-    pub fn decode_audio_symphonia(&self, file_path: &str) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>> {
-        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
-        self.decode_audio_symphonia_with_progress(file_path, &dummy_callback)
+/// Intermediate results reported while a file is being segmented
+pub enum SegmentUpdate<'a> {
+    /// A speech segment finalized by VAD, before merging (index in detection order)
+    Found(usize, &'a AudioSegment),
+    /// The merged, validated segments that replace everything reported so far
+    Merged(&'a [AudioSegment]),
+}
+
+/// Returned when processing stops early because its cancel flag was set
+#[derive(Debug)]
+pub struct ProcessingCancelled;
+
+impl std::fmt::Display for ProcessingCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Processing cancelled")
     }
+}
 
-    fn decode_audio_symphonia_with_progress<F>(&self, file_path: &str, progress_callback: &F) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>>
-    where
-        F: Fn(&str, f64, Option<&str>),
-    {
-        let file = File::open(file_path)?;
-        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+impl std::error::Error for ProcessingCancelled {}
 
-        let mut hint = Hint::new();
-        if let Some(extension) = std::path::Path::new(file_path).extension() {
-            if let Some(ext_str) = extension.to_str() {
-                hint.with_extension(ext_str);
+/// Why a file could not be decoded, worded for the user rather than as Symphonia's internals
+#[derive(Debug)]
+pub enum DecodeError {
+    EmptyFile,
+    UnrecognizedFormat,
+    NoAudioTrack,
+    UnsupportedCodec(String), // Codec name, or its id when Symphonia doesn't know it
+    Corrupt(String),          // What went wrong, e.g. "unexpected end of file"
+    NoSamples,                // Decoded cleanly, but not a single sample came out
+    Io(std::io::Error),
+}
+
+impl DecodeError {
+    /// The file was readable but has no audio in it, as opposed to audio that failed to decode
+    pub fn is_no_audio(&self) -> bool {
+        matches!(self, DecodeError::EmptyFile | DecodeError::NoAudioTrack | DecodeError::NoSamples)
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::EmptyFile => write!(f, "The file is empty"),
+            DecodeError::UnrecognizedFormat => write!(f, "The file is not in a recognized audio format (supported: {})",
+                SUPPORTED_FORMATS.join(", ").to_uppercase()),
+            DecodeError::NoAudioTrack => write!(f, "The file contains no audio track"),
+            DecodeError::UnsupportedCodec(codec) => write!(f, "The audio is encoded with {}, which this build has no decoder for; convert it to WAV, MP3 or FLAC", codec),
+            DecodeError::Corrupt(detail) => write!(f, "The audio stream is corrupt or truncated ({})", detail),
+            DecodeError::NoSamples => write!(f, "The file contains no audio samples"),
+            DecodeError::Io(e) => write!(f, "The file could not be read: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<SymphoniaError> for DecodeError {
+    fn from(err: SymphoniaError) -> Self {
+        match err {
+            SymphoniaError::IoError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                DecodeError::Corrupt("unexpected end of file".to_string())
             }
+            SymphoniaError::IoError(e) => DecodeError::Io(e),
+            SymphoniaError::DecodeError(detail) => DecodeError::Corrupt(detail.to_string()),
+            SymphoniaError::Unsupported(_) => DecodeError::UnrecognizedFormat,
+            other => DecodeError::Corrupt(other.to_string()),
         }
+    }
+}
 
-        let meta_opts: MetadataOptions = Default::default();
-        let fmt_opts: FormatOptions = Default::default();
+// Readable name for a codec, also for ones this build has no decoder for
+fn codec_name(codec: CodecType) -> String {
+    use symphonia::core::codecs::*;
 
-        let probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?;
-        let mut format = probed.format;
+    if let Some(descriptor) = symphonia::default::get_codecs().get_codec(codec) {
+        return descriptor.long_name.to_string();
+    }
+    let name = match codec {
+        CODEC_TYPE_ALAC => "Apple Lossless (ALAC)",
+        CODEC_TYPE_OPUS => "Opus",
+        CODEC_TYPE_VORBIS => "Vorbis",
+        CODEC_TYPE_WAVPACK => "WavPack",
+        CODEC_TYPE_EAC3 => "Dolby Digital Plus (E-AC-3)",
+        CODEC_TYPE_DCA => "DTS",
+        CODEC_TYPE_ATRAC3 | CODEC_TYPE_ATRAC3PLUS => "ATRAC",
+        CODEC_TYPE_WMA => "Windows Media Audio",
+        CODEC_TYPE_MONKEYS_AUDIO => "Monkey's Audio",
+        CODEC_TYPE_ADPCM_IMA_WAV | CODEC_TYPE_ADPCM_IMA_QT | CODEC_TYPE_ADPCM_MS => "ADPCM",
+        CODEC_TYPE_SPEEX => "Speex",
+        _ => return format!("an unknown codec ({})", codec),
+    };
+    name.to_string()
+}
 
-        let track = format
-            .tracks()
-            .iter()
-            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .ok_or("No supported audio tracks found")?;
+// Share of samples at the i16 limits above which decoding warns about clipping
+const CLIPPING_WARN_RATIO: f64 = 0.01;
 
-        let dec_opts: DecoderOptions = Default::default();
-        let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &dec_opts)?;
+// Per-channel weights for folding surround audio down to mono, in interleaved order.
+// Follows the ITU-R BS.775 stereo downmix (centre and surrounds at -3 dB into each side,
+// LFE left out) and then averages the two sides, so dialog in the centre channel comes
+// through loudest. None for mono, stereo and unknown layouts, which are mixed evenly.
+fn surround_weights(layout: Option<Channels>) -> Option<Vec<f64>> {
+    use std::f64::consts::FRAC_1_SQRT_2;
 
-        let track_id = track.id;
-        let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
-        let channels = track.codec_params.channels.unwrap_or_default().count();
+    let layout = layout.filter(|layout| layout.count() > 2)?;
+    Some(layout.iter()
+        .map(|channel| match channel {
+            Channels::FRONT_LEFT | Channels::FRONT_RIGHT | Channels::FRONT_LEFT_CENTRE | Channels::FRONT_RIGHT_CENTRE => 0.5,
+            Channels::FRONT_CENTRE => FRAC_1_SQRT_2,
+            Channels::LFE1 | Channels::LFE2 => 0.0,
+            Channels::REAR_CENTRE => 0.5,
+            // Rear, side and height surrounds
+            _ => 0.5 * FRAC_1_SQRT_2,
+        })
+        .collect())
+}
 
-        let mut samples = Vec::new();
-        let mut sample_buf = None;
-        let mut packet_count = 0;
-        let estimated_packets = 1000; // Rough estimate for progress tracking
+// Maximum per-sample difference between channels still treated as dual-mono, in i16 steps
+const DUAL_MONO_TOLERANCE: i32 = 2;
 
-        loop {
-            let packet = match format.next_packet() {
-                Ok(packet) => packet,
-                Err(SymphoniaError::ResetRequired) => {
-                    break;
-                }
-                Err(SymphoniaError::IoError(err))
-                    if err.kind() == std::io::ErrorKind::UnexpectedEof
-                        && err.to_string() == "end of stream" =>
-                {
-                    break;
-                }
-                Err(err) => return Err(err.into()),
-            };
+// Frames with signal in them (not near-silent) a file must open with, all with identical
+// channels, before it is treated as dual-mono. About a second at common rates.
+const DUAL_MONO_PROBE_FRAMES: usize = 48000;
 
-            if packet.track_id() != track_id {
-                continue;
-            }
+// Decides once per file whether its channels are (near-)identical, from the first
+// buffers decoded, so later buffers skip both the check and the averaging. Silent
+// frames say nothing about the channels and don't count towards the decision.
+struct DualMonoDetector {
+    tolerance: f64,
+    probed_frames: usize,
+    decision: Option<bool>,
+}
 
-            packet_count += 1;
-            
-            // Update progress every 50 packets
-            if packet_count % 50 == 0 {
-                let decode_progress = 10.0 + (packet_count as f64 / estimated_packets as f64) * 15.0;
-                progress_callback("Decoding audio packets", decode_progress.min(24.0), Some(&format!("Processed {} packets", packet_count)));
-            }
+impl DualMonoDetector {
+    // `full_scale` is the sample value of a full-scale signal: i16::MAX for i16, 1.0 for f32
+    fn new(enabled: bool, full_scale: f64) -> Self {
+        Self {
+            tolerance: DUAL_MONO_TOLERANCE as f64 * full_scale / i16::MAX as f64,
+            probed_frames: 0,
+            decision: if enabled { None } else { Some(false) },
+        }
+    }
 
-            match decoder.decode(&packet) {
-                Ok(audio_buf) => {
-                    if sample_buf.is_none() {
-                        let spec = *audio_buf.spec();
-                        let duration = audio_buf.capacity() as u64;
-                        sample_buf = Some(SampleBuffer::<i16>::new(duration, spec));
-                    }
+    // How many leading frames of this buffer carry identical channels: all of them once the
+    // file is known to be dual-mono, none once it is known not to be. While undecided,
+    // frames are probed up to the first one whose channels differ.
+    fn identical_frames<T: Copy + Into<f64>>(&mut self, interleaved: &[T], channels: usize) -> usize {
+        let frames = interleaved.len() / channels;
+        if let Some(decision) = self.decision {
+            return if decision { frames } else { 0 };
+        }
 
-                    if let Some(buf) = &mut sample_buf {
-                        buf.copy_interleaved_ref(audio_buf);
-                        
-                        // Convert to mono if stereo
-                        let buf_samples = buf.samples();
-                        if channels == 1 {
-                            samples.extend_from_slice(buf_samples);
-                        } else {
-                            // Convert stereo to mono by averaging channels
-                            for chunk in buf_samples.chunks(channels) {
-                                if !chunk.is_empty() {
-                                    let mono_sample = chunk.iter().map(|&s| s as i32).sum::<i32>() / chunk.len() as i32;
-                                    samples.push(mono_sample as i16);
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(SymphoniaError::IoError(_)) => break,
-                Err(SymphoniaError::DecodeError(_)) => continue,
-                Err(err) => return Err(err.into()),
+        for (index, frame) in interleaved.chunks_exact(channels).enumerate() {
+            let first: f64 = frame[0].into();
+            if frame[1..].iter().any(|&s| (s.into() - first).abs() > self.tolerance) {
+                self.decision = Some(false);
+                return index;
+            }
+            if first.abs() > self.tolerance {
+                self.probed_frames += 1;
             }
         }
 
-        if samples.is_empty() {
-            return Err("No audio samples decoded".into());
+        if self.probed_frames >= DUAL_MONO_PROBE_FRAMES {
+            debug_log!("Detected dual-mono audio ({} identical channels), skipping channel averaging", channels);
+            self.decision = Some(true);
         }
+        frames
+    }
+}
 
-        Ok((samples, sample_rate))
+/// How multichannel audio is collapsed to mono while decoding
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelMixMode {
+    /// Mean of all channels; out-of-phase content cancels. Surround audio with a known
+    /// layout is folded down with BS.775 weights instead (see `surround_weights`).
+    #[default]
+    Average,
+    /// First channel only
+    Left,
+    /// Second channel only (the first channel for mono audio)
+    Right,
+    /// Whichever channel has the largest magnitude in each frame
+    Max,
+    /// Sum of all channels divided by the square root of their count, so a typical
+    /// stereo mix keeps its loudness instead of dropping ~3 dB; clamped at full scale
+    Power,
+}
+
+/// Sample format of exported WAV files. 16-bit PCM comes from the usual i16 decode;
+/// the others keep the float samples of a separate decode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WavBitDepth {
+    #[default]
+    Pcm16,
+    Pcm24,
+    Float32,
+}
+
+impl WavBitDepth {
+    fn bits(self) -> u16 {
+        match self {
+            WavBitDepth::Pcm16 => 16,
+            WavBitDepth::Pcm24 => 24,
+            WavBitDepth::Float32 => 32,
+        }
     }
+}
 
-    pub fn process_audio_file(&mut self, file_path: &str, _model_path: &str) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>> {
-        // Default progress callback that does nothing
-        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
-        self.process_audio_file_with_progress(file_path, _model_path, dummy_callback)
+/// File extensions the pipeline accepts
+pub const SUPPORTED_FORMATS: &[&str] = &["wav", "mp3", "m4a", "aac", "flac", "ogg", "opus", "webm"];
+
+/// Whether `ext` (without the dot, any case) is one of SUPPORTED_FORMATS
+pub fn is_supported_format(ext: &str) -> bool {
+    SUPPORTED_FORMATS.iter().any(|format| format.eq_ignore_ascii_case(ext))
+}
+
+/// Guess the container from a file's first bytes, as one of SUPPORTED_FORMATS
+pub fn sniff_format(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.len() >= 12 && (bytes.starts_with(b"RIFF") || bytes.starts_with(b"RIFX")) && &bytes[8..12] == b"WAVE" {
+        Some("wav")
+    } else if bytes.len() >= 8 && &bytes[4..8] == b"ftyp" {
+        Some("m4a")
+    } else if bytes.starts_with(b"fLaC") {
+        Some("flac")
+    } else if bytes.starts_with(b"OggS") {
+        Some("ogg")
+    } else if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        // EBML header of Matroska and WebM
+        Some("webm")
+    } else if bytes.starts_with(b"ID3") {
+        Some("mp3")
+    } else if bytes.len() >= 2 && bytes[0] == 0xFF && (bytes[1] & 0xF6) == 0xF0 {
+        // ADTS frame sync with layer 0
+        Some("aac")
+    } else if bytes.len() >= 2 && bytes[0] == 0xFF && (bytes[1] & 0xE0) == 0xE0 {
+        // MPEG audio frame sync
+        Some("mp3")
+    } else {
+        None
     }
+}
 
-    pub fn process_audio_file_with_progress<F>(&mut self, file_path: &str, _model_path: &str, progress_callback: F) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>>
-    where
-        F: Fn(&str, f64, Option<&str>),
-    {
-        // Check file extension to provide better error messages
-        let path = std::path::Path::new(file_path);
-        let extension = path.extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-        
-        println!("Processing audio file: {} (format: {})", file_path, extension);
-        progress_callback("Validating file format", 5.0, Some(&format!("Detected format: {}", extension)));
-        
-        // Support multiple audio formats now
-        match extension.as_str() {
-            "wav" | "mp3" | "m4a" | "aac" | "flac" | "ogg" => {
-                // Supported formats - continue processing
-            },
-            _ => {
-                return Err(format!("Unsupported audio format: '{}'. Supported formats: WAV, MP3, M4A, AAC, FLAC, OGG", extension).into());
-            }
-        }
-        
-        // Decode audio using Symphonia
-        progress_callback("Decoding audio file", 10.0, Some("Reading and decoding audio data"));
-        let (mut content, original_sample_rate) = self.decode_audio_symphonia_with_progress(file_path, &progress_callback)?;
-        
-        // Always target 16kHz for VAD processing
-        let target_sample_rate = utils::SampleRate::SixteenkHz;
-        let target_rate_hz = 16000u32;
-        
-        println!("Processing audio file: {} Hz -> {} Hz", original_sample_rate, target_rate_hz);
-        progress_callback("Audio decoded", 25.0, Some(&format!("{} samples at {} Hz", content.len(), original_sample_rate)));
-        
-        self.sample_rate = target_sample_rate;
+// Default speech probability threshold and chunks of padding around detected speech
+const VAD_THRESHOLD: f32 = 0.5;
+const VAD_PADDING_CHUNKS: usize = 2;
 
-        if content.is_empty() {
-            return Err("Audio file is empty or contains no valid samples.".into());
-        }
+// Upper bounds for VadConfig: about 2s of padding either side, and a minute of speech
+const MAX_VAD_PADDING_CHUNKS: usize = 64;
+const MAX_MIN_SPEECH_MS: u32 = 60_000;
 
-        println!("Original audio: {} samples at {} Hz", content.len(), original_sample_rate);
+// Segments closer together than this are merged by default
+const MERGE_GAP_SECONDS: f64 = 1.5;
 
-        // Resample to 16kHz if needed
-        if original_sample_rate != target_rate_hz {
-            progress_callback("Resampling audio", 35.0, Some(&format!("Converting from {} Hz to {} Hz", original_sample_rate, target_rate_hz)));
-            content = self.simple_resample(&content, original_sample_rate, target_rate_hz);
-            println!("Resampled to: {} samples at {} Hz", content.len(), target_rate_hz);
-            progress_callback("Audio resampled", 45.0, Some(&format!("{} samples at {} Hz", content.len(), target_rate_hz)));
-        }
+// Audio decoded and thrown away ahead of a seeking extraction, so the decoder state at
+// the range start matches a full decode. Covers MP3's bit reservoir many times over.
+const SEEK_PREROLL_SECONDS: f64 = 0.25;
 
-        // Use real Silero VAD through voice_activity_detector crate
-        println!("Running voice activity detection...");
-        progress_callback("Running voice activity detection", 50.0, Some("Initializing AI voice detection"));
-        
-        // According to the docs, 16kHz sample rate requires 512-sample chunks
-        let chunk_size = 512usize;
-        let mut vad = VoiceActivityDetector::builder()
-            .sample_rate(16000) // We always resample to 16kHz
-            .chunk_size(chunk_size)
-            .build()
-            .map_err(|e| format!("Failed to create VAD: {}", e))?;
-        
-        // Use the label iterator with threshold 0.5 and 2 chunks padding
-        let threshold = 0.5;
-        let padding_chunks = 2;
-        
-        progress_callback("Analyzing speech patterns", 60.0, Some("Processing audio chunks for speech detection"));
-        let labels: Vec<_> = content.iter().cloned().label(&mut vad, threshold, padding_chunks).collect();
-        progress_callback("Speech detection complete", 75.0, Some(&format!("Processed {} audio chunks", labels.len())));
-        
-        // Convert labeled chunks back to continuous segments
-        let mut segments = Vec::new();
-        let mut current_speech_start = None;
-        let sample_rate_f64 = 16000.0; // We know it's 16kHz after resampling
-        
-        progress_callback("Extracting speech segments", 80.0, Some("Converting detection results to segments"));
-        
-        for (chunk_index, label) in labels.iter().enumerate() {
-            let chunk_start_sample = chunk_index * chunk_size;
-            let chunk_start_time = chunk_start_sample as f64 / sample_rate_f64;
-            
-            match label {
-                LabeledAudio::Speech(chunk_data) => {
-                    if current_speech_start.is_none() {
-                        // Start of a new speech segment
-                        current_speech_start = Some(chunk_start_sample);
-                    }
-                }
-                LabeledAudio::NonSpeech(_) => {
-                    if let Some(speech_start) = current_speech_start.take() {
-                        // End of speech segment
-                        let speech_end = chunk_start_sample;
-                        let start_time = speech_start as f64 / sample_rate_f64;
-                        let end_time = speech_end as f64 / sample_rate_f64;
-                        
-                        // Extract audio data for this segment
-                        let start_idx = speech_start.min(content.len());
-                        let end_idx = speech_end.min(content.len());
-                        let segment_audio = content[start_idx..end_idx].to_vec();
-                        
-                        if !segment_audio.is_empty() {
-                            let audio_base64 = self.samples_to_wav_base64(&segment_audio)
-                                .unwrap_or_else(|_| String::new());
-                            
-                            segments.push(AudioSegment {
-                                start_sample: speech_start as i64,
-                                end_sample: speech_end as i64,
-                                start_time_seconds: start_time,
-                                end_time_seconds: end_time,
-                                audio_data: segment_audio,
-                                audio_base64,
-                            });
-                        }
-                    }
-                }
-            }
-        }
-        
-        // Handle any remaining speech segment at the end
-        if let Some(speech_start) = current_speech_start {
-            let speech_end = content.len();
-            let start_time = speech_start as f64 / sample_rate_f64;
-            let end_time = speech_end as f64 / sample_rate_f64;
-            
-            let start_idx = speech_start.min(content.len());
-            let segment_audio = content[start_idx..].to_vec();
-            
-            if !segment_audio.is_empty() {
-                let audio_base64 = self.samples_to_wav_base64(&segment_audio)
-                    .unwrap_or_else(|_| String::new());
-                
-                segments.push(AudioSegment {
-                    start_sample: speech_start as i64,
-                    end_sample: speech_end as i64,
-                    start_time_seconds: start_time,
-                    end_time_seconds: end_time,
-                    audio_data: segment_audio,
-                    audio_base64,
-                });
-            }
-        }
+// Length of the crossfade at the joins of merged segment audio, when enabled
+const MERGE_CROSSFADE_MS: u32 = 5;
 
-        println!("Generated {} initial speech segments using Silero VAD", segments.len());
-        progress_callback("Optimizing segments", 90.0, Some(&format!("Found {} initial segments", segments.len())));
+// Samples at or below this amplitude (about -50 dBFS) count as silence when skipping
+// leading/trailing dead air; speech is kept with SILENCE_MARGIN_SECONDS of context
+const SILENCE_LEVEL: u16 = 100;
+const SILENCE_MARGIN_SECONDS: f64 = 0.5;
 
-        // Merge segments that are close together (within 3 seconds)
-        let merged_segments = self.merge_close_segments_with_progress(segments, &content, 1.5, &progress_callback);
-        
-        println!("After merging close segments: {} final segments", merged_segments.len());
-        progress_callback("Segmentation complete", 95.0, Some(&format!("Optimized to {} final segments", merged_segments.len())));
+// Near-silence trimmed from segment edges when enabled: 10ms frames below this RMS
+// (about -50 dBFS, like SILENCE_LEVEL), at most TRIM_MAX_MS from each end
+const TRIM_THRESHOLD_RMS: f64 = 100.0;
+const TRIM_MAX_MS: u32 = 250;
+const TRIM_FRAME_MS: usize = 10;
 
-        Ok(merged_segments)
+// Loudness gate used by VadBackend::Rms: frames at or above this level are speech, and
+// speech is held for RMS_HANG_MS after the level drops so word gaps don't split it
+const RMS_FRAME_MS: u32 = 20;
+const RMS_THRESHOLD_DBFS: f64 = -40.0;
+const RMS_HANG_MS: u32 = 300;
+
+// Files at least this long are segmented as they decode instead of being decoded whole
+// first (see AudioProcessor::stream_segments)
+const STREAMING_MIN_SECONDS: f64 = 3600.0;
+
+// Input frames per resampler call; about 20-60ms at common rates
+const RESAMPLE_CHUNK: usize = 1024;
+
+// Split points for long segments are searched for in the last quarter of the allowed
+// length, in frames this long
+const SPLIT_SEARCH_FRACTION: f64 = 0.25;
+const SPLIT_FRAME_MS: usize = 20;
+
+// Non-speech audio either side of a segment used as its noise reference, and the
+// least of it worth trusting before falling back to the whole file's noise
+const SNR_CONTEXT_SECONDS: f64 = 2.0;
+const SNR_MIN_NOISE_SECONDS: f64 = 0.1;
+
+// Upper bound for the adaptive VAD threshold so loud noise can't mask all speech
+const MAX_ADAPTIVE_THRESHOLD: f32 = 0.95;
+
+// Noise-floor threshold: the floor is the level of the quietest tenth of 30ms frames. Up to
+// CLEAN_NOISE_FLOOR_DBFS the configured threshold is kept; from there it rises linearly
+// to MAX_ADAPTIVE_THRESHOLD at NOISY_NOISE_FLOOR_DBFS. The loudness gate instead stays
+// RMS_NOISE_MARGIN_DB above the floor.
+const NOISE_FLOOR_FRAME_MS: usize = 30;
+const NOISE_FLOOR_FRACTION: f64 = 0.1;
+const CLEAN_NOISE_FLOOR_DBFS: f64 = -60.0;
+const NOISY_NOISE_FLOOR_DBFS: f64 = -30.0;
+const RMS_NOISE_MARGIN_DB: f64 = 10.0;
+
+// Speech first found this late suggests a long intro or a too strict VAD threshold
+const LATE_SPEECH_SECONDS: f64 = 30.0;
+
+// At least this many segments, most of them shorter than SHORT_SEGMENT_SECONDS, means
+// speech is being chopped up, usually by noise around the VAD threshold
+const MANY_SHORT_SEGMENTS_MIN_COUNT: usize = 20;
+const SHORT_SEGMENT_SECONDS: f64 = 1.0;
+
+/// Turns per-chunk speech probabilities into speech runs one chunk at a time, so the
+/// same logic serves whole files and live capture. Every speech chunk is extended by
+/// the configured padding on both sides (matches the padding behaviour of
+/// voice_activity_detector's label iterator), and runs shorter than the minimum
+/// speech duration are dropped. Runs are chunk ranges with padding included.
+pub struct SpeechRunTracker {
+    vad_config: VadConfig,
+    adaptive_threshold: Option<AdaptiveThreshold>,
+    min_speech_chunks: usize,
+    noise_floor: f32,
+    highest_threshold: f32,
+    // First and last chunk above the threshold in the current run
+    current_run: Option<(usize, usize)>,
+    chunk_count: usize,
+}
+
+impl SpeechRunTracker {
+    // Effective speech threshold for the next chunk: fixed, or tracking the noise level when adaptive
+    fn chunk_threshold(&mut self, probability: f32) -> f32 {
+        let threshold = self.vad_config.threshold;
+        let Some(adaptive) = self.adaptive_threshold else {
+            return threshold;
+        };
+
+        // Never drop below the configured threshold, even if that is above the adaptive cap
+        let effective = |noise_floor: f32| (noise_floor + adaptive.margin).min(MAX_ADAPTIVE_THRESHOLD).max(threshold);
+        let current = effective(self.noise_floor);
+
+        // Only chunks heard as noise move the floor, or a long utterance would drag the
+        // threshold up past its own probabilities and cut itself short. Falling back to a
+        // quieter floor happens faster than rising to a louder one.
+        if probability >= current {
+            return current;
+        }
+        let rate = if probability < self.noise_floor { (adaptive.adaptation_rate * 4.0).min(1.0) } else { adaptive.adaptation_rate };
+        self.noise_floor += rate * (probability - self.noise_floor);
+        effective(self.noise_floor)
     }
 
-    // Merge segments that are close together (within max_gap_seconds)
-    fn merge_close_segments(&self, mut segments: Vec<AudioSegment>, content: &[i16], max_gap_seconds: f64) -> Vec<AudioSegment> {
-        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
-        self.merge_close_segments_with_progress(segments, content, max_gap_seconds, &dummy_callback)
+    fn is_long_enough(&self, first: usize, last: usize) -> bool {
+        last - first + 1 >= self.min_speech_chunks
     }
 
-    fn merge_close_segments_with_progress<F>(&self, mut segments: Vec<AudioSegment>, content: &[i16], max_gap_seconds: f64, progress_callback: &F) -> Vec<AudioSegment>
-    where
-        F: Fn(&str, f64, Option<&str>),
-    {
-        if segments.is_empty() {
-            return segments;
-        }
+    fn padded(&self, first: usize, last: usize) -> std::ops::Range<usize> {
+        let padding = self.vad_config.padding_chunks;
+        first.saturating_sub(padding)..last + padding + 1
+    }
 
-        // Sort segments by start time to ensure proper order
-        segments.sort_by(|a, b| a.start_time_seconds.partial_cmp(&b.start_time_seconds).unwrap());
+    /// Feed the next chunk's probability. Returns a run once padding can no longer
+    /// bridge the gap to a later speech chunk.
+    pub fn push(&mut self, probability: f32) -> Option<std::ops::Range<usize>> {
+        let chunk_index = self.chunk_count;
+        self.chunk_count += 1;
+        let threshold = self.chunk_threshold(probability);
+        self.highest_threshold = self.highest_threshold.max(threshold);
 
-        let mut merged = Vec::new();
-        let mut segments_iter = segments.into_iter();
-        let mut current = segments_iter.next().unwrap();
-        let mut processed = 0;
-        let total_segments = segments_iter.len() + 1;
+        if probability >= threshold {
+            let first = self.current_run.map_or(chunk_index, |(first, _)| first);
+            self.current_run = Some((first, chunk_index));
+            return None;
+        }
 
-        for next in segments_iter {
-            processed += 1;
-            
-            // Update progress during merging
-            if processed % 10 == 0 || processed == total_segments - 1 {
-                let merge_progress = 90.0 + (processed as f64 / total_segments as f64) * 5.0;
-                progress_callback("Merging segments", merge_progress, Some(&format!("Processed {}/{} segments", processed, total_segments)));
-            }
-            
-            let gap = next.start_time_seconds - current.end_time_seconds;
-            
-            if gap <= max_gap_seconds {
-                // Merge current and next segments
-                println!("Merging segments: {:.2}s-{:.2}s with {:.2}s-{:.2}s (gap: {:.2}s)", 
-                    current.start_time_seconds, current.end_time_seconds,
-                    next.start_time_seconds, next.end_time_seconds, gap);
-                
-                let merged_start = current.start_sample;
-                let merged_end = next.end_sample;
-                let merged_start_time = current.start_time_seconds;
-                let merged_end_time = next.end_time_seconds;
-                
-                // Extract audio data for the merged segment (including the gap)
-                let start_idx = merged_start.min(content.len() as i64) as usize;
-                let end_idx = (merged_end as usize).min(content.len());
-                let merged_audio = content[start_idx..end_idx].to_vec();
-                
-                println!("Merged segment: {:.2}s-{:.2}s, samples: {}-{}, audio length: {} samples", 
-                    merged_start_time, merged_end_time, merged_start, merged_end, merged_audio.len());
-                
-                let audio_base64 = self.samples_to_wav_base64(&merged_audio)
-                    .unwrap_or_else(|_| String::new());
-                
-                current = AudioSegment {
-                    start_sample: merged_start,
-                    end_sample: merged_end,
-                    start_time_seconds: merged_start_time,
-                    end_time_seconds: merged_end_time,
-                    audio_data: merged_audio,
-                    audio_base64,
-                };
-            } else {
-                // Gap is too large, keep current segment and move to next
-                println!("Gap too large ({:.2}s > {:.2}s), not merging segments: {:.2}s-{:.2}s and {:.2}s-{:.2}s", 
-                    gap, max_gap_seconds,
-                    current.start_time_seconds, current.end_time_seconds,
-                    next.start_time_seconds, next.end_time_seconds);
-                merged.push(current);
-                current = next;
-            }
+        let (first, last) = self.current_run?;
+        if chunk_index - last <= 2 * self.vad_config.padding_chunks {
+            return None;
         }
-        
-        // Don't forget to add the last segment
-        merged.push(current);
-        
-        merged
+        self.current_run = None;
+        Some(self.padded(first, last)).filter(|_| self.is_long_enough(first, last))
     }
 
-    // Convert audio samples to base64-encoded WAV for browser playback
-    fn samples_to_wav_base64(&self, samples: &[i16]) -> Result<String, Box<dyn std::error::Error>> {
-        let sample_rate = 16000u32; // Always 16kHz for our processed audio
-        let channels = 1u16; // Mono
-        let bits_per_sample = 16u16;
-        
-        let mut wav_data = Vec::new();
-        
-        // WAV header
-        wav_data.extend_from_slice(b"RIFF");
-        let file_size = 36 + (samples.len() * 2) as u32;
-        wav_data.extend_from_slice(&file_size.to_le_bytes());
-        wav_data.extend_from_slice(b"WAVE");
-        
-        // Format chunk
-        wav_data.extend_from_slice(b"fmt ");
-        wav_data.extend_from_slice(&16u32.to_le_bytes()); // Chunk size
-        wav_data.extend_from_slice(&1u16.to_le_bytes()); // Audio format (PCM)
-        wav_data.extend_from_slice(&channels.to_le_bytes());
-        wav_data.extend_from_slice(&sample_rate.to_le_bytes());
-        let byte_rate = sample_rate * channels as u32 * bits_per_sample as u32 / 8;
-        wav_data.extend_from_slice(&byte_rate.to_le_bytes());
-        let block_align = channels * bits_per_sample / 8;
-        wav_data.extend_from_slice(&block_align.to_le_bytes());
-        wav_data.extend_from_slice(&bits_per_sample.to_le_bytes());
-        
-        // Data chunk
-        wav_data.extend_from_slice(b"data");
-        let data_size = (samples.len() * 2) as u32;
-        wav_data.extend_from_slice(&data_size.to_le_bytes());
-        
-        // Audio data
-        for &sample in samples {
-            wav_data.extend_from_slice(&sample.to_le_bytes());
-        }
-        
-        // Encode to base64
-        Ok(base64::encode(&wav_data))
+    /// The run still open at the end of the audio, if long enough. Its padding may
+    /// reach past the last chunk seen.
+    pub fn finish(&self) -> Option<std::ops::Range<usize>> {
+        self.current_run
+            .filter(|&(first, last)| self.is_long_enough(first, last))
+            .map(|(first, last)| self.padded(first, last))
     }
 
-    pub fn extract_audio_chunk(&self, content: &[i16], start_sample: i64, end_sample: i64) -> Vec<i16> {
-        let start_idx = start_sample.max(0) as usize;
-        let end_idx = (end_sample as usize).min(content.len());
-        content[start_idx..end_idx].to_vec()
+    /// Earliest chunk a run reported later could still start at
+    pub fn earliest_pending_chunk(&self) -> usize {
+        let next = self.current_run.map_or(self.chunk_count, |(first, _)| first);
+        next.saturating_sub(self.vad_config.padding_chunks)
     }
 
-    /// Simple resampling by linear interpolation
-    /// This is a basic approach - for production, you'd want proper anti-aliasing
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_count
+    }
+}
+
+// The loudness gate behind rms_vad, fed a block at a time: ranges where `frame`-sample
+// frames reach `threshold` RMS, each held open for `hang` samples after the last loud frame
+struct RmsGate {
+    frame: usize,
+    hang: usize,
+    threshold: f64,
+    // Samples of the frame still being filled
+    partial: Vec<i16>,
+    // End of the last gated frame
+    position: usize,
+    // Start of the open range and end of its last loud frame
+    open: Option<(usize, usize)>,
+    ranges: Vec<std::ops::Range<usize>>,
+}
+
+impl RmsGate {
+    fn push(&mut self, mut samples: &[i16]) {
+        if !self.partial.is_empty() {
+            let fill = (self.frame - self.partial.len()).min(samples.len());
+            self.partial.extend_from_slice(&samples[..fill]);
+            samples = &samples[fill..];
+            if self.partial.len() < self.frame {
+                return;
+            }
+            let frame = std::mem::take(&mut self.partial);
+            self.gate_frame(&frame);
+        }
+
+        let mut frames = samples.chunks_exact(self.frame);
+        for frame in &mut frames {
+            self.gate_frame(frame);
+        }
+        self.partial.extend_from_slice(frames.remainder());
+    }
+
+    fn gate_frame(&mut self, frame: &[i16]) {
+        let (frame_start, frame_end) = (self.position, self.position + frame.len());
+        self.position = frame_end;
+        let loud = dsp::mean_square(&[frame]).is_some_and(|square| square.sqrt() >= self.threshold);
+        if loud {
+            self.open = Some((self.open.map_or(frame_start, |(start, _)| start), frame_end));
+        } else if let Some((start, last_loud_end)) = self.open {
+            if frame_end - last_loud_end > self.hang {
+                self.ranges.push(start..last_loud_end + self.hang);
+                self.open = None;
+            }
+        }
+    }
+
+    // Gate the last, partial frame and close the range still open at the end of the audio
+    fn finish(&mut self) {
+        let frame = std::mem::take(&mut self.partial);
+        if !frame.is_empty() {
+            self.gate_frame(&frame);
+        }
+        if let Some((start, last_loud_end)) = self.open.take() {
+            self.ranges.push(start..(last_loud_end + self.hang).min(self.position));
+        }
+    }
+
+    // Whether the sample range `chunk` overlaps speech, or None while frames starting
+    // inside it are still to come. The open range will end at least `hang` past the last
+    // loud frame, so it overlaps any chunk it started before the end of.
+    fn is_speech(&self, chunk: std::ops::Range<usize>) -> Option<bool> {
+        if self.position < chunk.end {
+            return None;
+        }
+        Some(self.open.is_some_and(|(start, _)| start < chunk.end)
+            || self.ranges.iter().any(|range| range.start < chunk.end && chunk.start < range.end))
+    }
+
+    // Drop closed ranges ending at or before `sample`
+    fn forget_before(&mut self, sample: usize) {
+        self.ranges.retain(|range| range.end > sample);
+    }
+}
+
+/// What decides which chunks are speech
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VadBackend {
+    /// The Silero neural model
+    #[default]
+    Silero,
+    /// A loudness gate; less accurate, but works where the model can't be loaded
+    Rms,
+}
+
+/// Tuning for speech detection. Quiet recordings want a lower threshold, noisy ones a higher one.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct VadConfig {
+    pub threshold: f32,        // Speech probability a chunk needs to count as speech (0.0-1.0)
+    pub padding_chunks: usize, // 32ms chunks of context kept either side of speech
+    pub min_speech_ms: u32,    // Speech runs shorter than this (before padding) are dropped
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            threshold: VAD_THRESHOLD,
+            padding_chunks: VAD_PADDING_CHUNKS,
+            min_speech_ms: 0,
+        }
+    }
+}
+
+impl VadConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.threshold) {
+            return Err(format!("VAD threshold must be between 0.0 and 1.0, got {}", self.threshold));
+        }
+        if self.padding_chunks > MAX_VAD_PADDING_CHUNKS {
+            return Err(format!("VAD padding must be at most {} chunks, got {}", MAX_VAD_PADDING_CHUNKS, self.padding_chunks));
+        }
+        if self.min_speech_ms > MAX_MIN_SPEECH_MS {
+            return Err(format!("Minimum speech duration must be at most {} ms, got {}", MAX_MIN_SPEECH_MS, self.min_speech_ms));
+        }
+        Ok(())
+    }
+}
+
+/// Adaptive VAD threshold for recordings whose background noise changes over time.
+/// Tracks the floor of the speech probability curve (the level the model assigns to
+/// background noise) and holds the effective threshold `margin` above it. The floor
+/// drops quickly but only rises at `adaptation_rate`, and only on chunks below the
+/// threshold, so speech never moves it while a gradual rise in noise does.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveThreshold {
+    pub adaptation_rate: f32, // How fast the noise floor rises per chunk (0.0-1.0)
+    pub margin: f32,
+}
+
+impl Default for AdaptiveThreshold {
+    fn default() -> Self {
+        Self {
+            adaptation_rate: 0.05,
+            margin: 0.3,
+        }
+    }
+}
+
+// Picture embedded in a file's tags, e.g. album art
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CoverArt {
+    pub mime: String,
+    pub bytes: Vec<u8>,
+}
+
+// How far into a file the decoder has read, for progress when the duration is unknown
+struct ReadPosition {
+    bytes_read: Arc<AtomicU64>,
+    total_bytes: Option<u64>,
+}
+
+impl ReadPosition {
+    fn fraction(&self) -> Option<f64> {
+        self.total_bytes
+            .filter(|&total| total > 0)
+            .map(|total| self.bytes_read.load(Ordering::Relaxed) as f64 / total as f64)
+    }
+}
+
+// Format reader, first decodable track and read position of an opened file
+type OpenedAudio = (Box<dyn FormatReader>, Track, ReadPosition);
+
+// Samples of each kept channel, sample rate and the file's total channel count
+type DecodedChannels = (Vec<Vec<i16>>, u32, usize);
+
+// Mono audio at the VAD rate, and if asked for at the file's own rate along with that rate
+type LoadedAudio = (Vec<i16>, Option<(Vec<i16>, u32)>);
+
+// Merged segments and, if requested, downsampled speech probabilities
+type DetectedSegments = (Vec<AudioSegment>, Option<Vec<f32>>);
+
+// Media source that publishes its current byte offset through a shared counter
+struct TrackedSource {
+    inner: Box<dyn MediaSource>,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl Read for TrackedSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes_read.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl Seek for TrackedSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = self.inner.seek(pos)?;
+        self.bytes_read.store(position, Ordering::Relaxed);
+        Ok(position)
+    }
+}
+
+impl MediaSource for TrackedSource {
+    fn is_seekable(&self) -> bool {
+        self.inner.is_seekable()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.inner.byte_len()
+    }
+}
+
+// Turns decoded buffers into interleaved i16. 8- and 16-bit sources already fit and are
+// copied as they are; 24/32-bit integer sources are rounded to 16 bits and float sources
+// are clamped to full scale before scaling, rather than left to symphonia's truncating
+// conversions. With dither, everything above 16 bits goes through TPDF dither instead.
+struct PcmConverter {
+    short_buf: Option<SampleBuffer<i16>>,
+    int_buf: Option<SampleBuffer<i32>>,
+    float_buf: Option<SampleBuffer<f32>>,
+    dither: Option<dsp::TpdfDither>,
+    samples: Vec<i16>,
+}
+
+impl PcmConverter {
+    fn new(dither: bool) -> Self {
+        Self { short_buf: None, int_buf: None, float_buf: None, dither: dither.then(dsp::TpdfDither::default), samples: Vec::new() }
+    }
+
+    fn convert(&mut self, audio_buf: AudioBufferRef<'_>) -> &[i16] {
+        let spec = *audio_buf.spec();
+        let duration = audio_buf.capacity() as u64;
+        self.samples.clear();
+        match (&audio_buf, &mut self.dither) {
+            (AudioBufferRef::U8(_) | AudioBufferRef::S8(_) | AudioBufferRef::U16(_) | AudioBufferRef::S16(_), _) => {
+                let buf = self.short_buf.get_or_insert_with(|| SampleBuffer::<i16>::new(duration, spec));
+                buf.copy_interleaved_ref(audio_buf);
+                self.samples.extend_from_slice(buf.samples());
+            }
+            (_, Some(dither)) => {
+                let buf = self.float_buf.get_or_insert_with(|| SampleBuffer::<f32>::new(duration, spec));
+                buf.copy_interleaved_ref(audio_buf);
+                self.samples.extend(buf.samples().iter().map(|&sample| dither.quantize(sample)));
+            }
+            (AudioBufferRef::F32(_) | AudioBufferRef::F64(_), None) => {
+                let buf = self.float_buf.get_or_insert_with(|| SampleBuffer::<f32>::new(duration, spec));
+                buf.copy_interleaved_ref(audio_buf);
+                self.samples.extend(buf.samples().iter().map(|&sample| dsp::float_to_i16(sample)));
+            }
+            (_, None) => {
+                let buf = self.int_buf.get_or_insert_with(|| SampleBuffer::<i32>::new(duration, spec));
+                buf.copy_interleaved_ref(audio_buf);
+                self.samples.extend(buf.samples().iter().map(|&sample| dsp::i32_to_i16(sample)));
+            }
+        }
+        &self.samples
+    }
+}
+
+/// Sinc resampling across calls, so block boundaries don't click. Used for live capture
+/// and for files segmented as they decode.
+pub struct StreamResampler {
+    resampler: SincFixedIn<f32>,
+    ratio: f64,
+    pending: Vec<f32>,
+    // Leading output samples that are only filter delay
+    delay_remaining: usize,
+    frames_in: usize,
+    frames_out: usize,
+}
+
+impl StreamResampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Result<Self, String> {
+        let resampler = AudioProcessor::sinc_resampler(from_rate, to_rate, RESAMPLE_CHUNK)
+            .map_err(|e| format!("Failed to create resampler: {}", e))?;
+        Ok(Self {
+            delay_remaining: resampler.output_delay(),
+            resampler,
+            ratio: to_rate as f64 / from_rate as f64,
+            pending: Vec::new(),
+            frames_in: 0,
+            frames_out: 0,
+        })
+    }
+
+    fn emit(&mut self, resampled: &[f32], out: &mut Vec<i16>) {
+        let skip = self.delay_remaining.min(resampled.len());
+        self.delay_remaining -= skip;
+        out.extend(resampled[skip..].iter().map(|&s| (s * 32768.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16));
+        self.frames_out += resampled.len() - skip;
+    }
+
+    /// Resample `samples`, appending whatever output the filter has ready to `out`
+    pub fn push(&mut self, samples: &[i16], out: &mut Vec<i16>) -> Result<(), String> {
+        self.pending.extend(samples.iter().map(|&s| s as f32 / 32768.0));
+        self.frames_in += samples.len();
+        while self.pending.len() >= self.resampler.input_frames_next() {
+            let needed = self.resampler.input_frames_next();
+            let resampled = self.resampler.process(&[&self.pending[..needed]], None)
+                .map_err(|e| format!("Failed to resample audio: {}", e))?;
+            self.pending.drain(..needed);
+            self.emit(&resampled[0], out);
+        }
+        Ok(())
+    }
+
+    /// Push the remaining input through the filter, stopping at the expected length
+    pub fn finish(&mut self, out: &mut Vec<i16>) -> Result<(), String> {
+        let expected = (self.frames_in as f64 * self.ratio).round() as usize;
+        let pending = std::mem::take(&mut self.pending);
+        let mut input = (!pending.is_empty()).then_some(pending.as_slice());
+        while self.frames_out < expected {
+            let resampled = match input.take() {
+                Some(remainder) => self.resampler.process_partial(Some(&[remainder]), None),
+                None => self.resampler.process_partial::<&[f32]>(None, None),
+            }.map_err(|e| format!("Failed to resample audio: {}", e))?;
+            let keep = (expected - self.frames_out + self.delay_remaining).min(resampled[0].len());
+            self.emit(&resampled[0][..keep], out);
+        }
+        Ok(())
+    }
+}
+
+// How chunks of a streamed file are judged: Silero, or the loudness gate fed as the audio arrives
+enum StreamDetector {
+    Silero(VoiceActivityDetector),
+    Rms(RmsGate),
+}
+
+// A file being segmented as it decodes (see AudioProcessor::stream_segments)
+struct SegmentStream {
+    resampler: Option<StreamResampler>, // None when the file is already at the VAD rate
+    detector: StreamDetector,
+    chunk_size: usize,
+    tracker: SpeechRunTracker,
+    // Audio at the VAD rate from sample `window_start` on
+    window: Vec<i16>,
+    window_start: usize,
+    // Speech runs in the window, yet to be merged and finished
+    runs: Vec<AudioSegment>,
+    runs_found: usize,
+    segments: Vec<AudioSegment>,
+    chunk_probabilities: Option<Vec<f32>>,
+}
+
+// What a file contains, read from its headers. `format` is the container sniffed from
+// the file's first bytes, or its extension when the bytes aren't recognized.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AudioMetadata {
+    pub duration_seconds: f64,
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub codec: String,
+    pub format: String,
+    // Fraction of samples at the i16 limits, across all channels. Needs a full decode,
+    // so only measured when asked for or when the headers are incomplete.
+    pub clipping_ratio: Option<f64>,
+}
+
+// Peak levels of a file; dB values bottom out at dsp::DB_FLOOR for silence
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Headroom {
+    pub true_peak_dbtp: f64,
+    pub sample_peak_dbfs: f64,
+    pub at_full_scale: bool, // Samples already hit 0 dBFS, normalizing up will clip
+}
+
+// Wall-clock time spent in each stage of the processing pipeline
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct PipelineTimings {
+    pub decode_ms: f64,
+    pub resample_ms: f64,
+    pub vad_ms: f64,
+    pub merge_ms: f64,
+    pub encode_ms: f64,
+    pub total_ms: f64,
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+pub struct AudioProcessor {
+    sample_rate: utils::SampleRate,
+    detect_dual_mono: bool,
+    channel_mix: ChannelMixMode,
+    vad_config: VadConfig,
+    merge_gap_seconds: f64,
+    crossfade_merges: bool,
+    min_segment_duration_seconds: f64,
+    adaptive_threshold: Option<AdaptiveThreshold>,
+    noise_floor_threshold: bool,
+    segment_cache: Option<SegmentCache>,
+    decoded_cache: Option<Arc<Mutex<DecodedAudioCache>>>,
+    eq_bands: Vec<dsp::EqBand>,
+    skip_silence: bool,
+    trim_silence: bool,
+    lazy_segment_audio: bool,
+    preserve_original_rate: bool,
+    dither: bool,
+    max_segment_seconds: Option<f64>,
+    split_overlap_seconds: f64,
+    vad_backend: VadBackend,
+    probability_resolution_ms: Option<u32>,
+    speech_probabilities: Option<Vec<f32>>,
+    // Collected behind a lock because decoding only borrows the processor
+    warnings: Mutex<Vec<ProcessingWarning>>,
+    normalize_peak_dbfs: Option<f32>,
+    streaming_min_seconds: Option<f64>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+}
+
+impl Default for AudioProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioProcessor {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: utils::SampleRate::SixteenkHz, // Default to 16kHz
+            detect_dual_mono: true,
+            channel_mix: ChannelMixMode::Average,
+            vad_config: VadConfig::default(),
+            merge_gap_seconds: MERGE_GAP_SECONDS,
+            crossfade_merges: false,
+            min_segment_duration_seconds: 0.0,
+            adaptive_threshold: None,
+            noise_floor_threshold: false,
+            segment_cache: None,
+            decoded_cache: None,
+            eq_bands: Vec::new(),
+            skip_silence: false,
+            trim_silence: false,
+            lazy_segment_audio: false,
+            preserve_original_rate: false,
+            dither: false,
+            max_segment_seconds: None,
+            split_overlap_seconds: 0.0,
+            vad_backend: VadBackend::Silero,
+            probability_resolution_ms: None,
+            speech_probabilities: None,
+            warnings: Mutex::new(Vec::new()),
+            normalize_peak_dbfs: None,
+            streaming_min_seconds: Some(STREAMING_MIN_SECONDS),
+            cancel_flag: None,
+        }
+    }
+
+    /// Bring the resampled audio's peak to this level before VAD, so quiet recordings still trigger it
+    pub fn set_normalization(&mut self, target_peak_dbfs: Option<f32>) -> Result<(), String> {
+        if let Some(target) = target_peak_dbfs {
+            if !target.is_finite() || target > 0.0 {
+                return Err(format!("Normalization target must be at most 0 dBFS, got {}", target));
+            }
+        }
+        self.normalize_peak_dbfs = target_peak_dbfs;
+        Ok(())
+    }
+
+    /// Rate audio is resampled to for VAD: 16kHz, or 8kHz for telephony recordings
+    pub fn set_sample_rate(&mut self, hz: u32) -> Result<(), String> {
+        utils::vad_chunk_size_for(hz)?;
+        if let Some(sample_rate) = utils::SampleRate::for_vad(hz) {
+            self.sample_rate = sample_rate;
+        }
+        Ok(())
+    }
+
+    /// Shape the resampled audio with a parametric EQ before VAD and transcription
+    pub fn set_eq_bands(&mut self, eq_bands: Vec<dsp::EqBand>) {
+        self.eq_bands = eq_bands;
+    }
+
+    /// Only run VAD between the first and last audible sample of a file
+    pub fn set_skip_silence(&mut self, enabled: bool) {
+        self.skip_silence = enabled;
+    }
+
+    /// Trim near-silent padding from both ends of each final segment
+    pub fn set_trim_silence(&mut self, enabled: bool) {
+        self.trim_silence = enabled;
+    }
+
+    /// Split final segments longer than `max_segment_seconds` into parts that share
+    /// `overlap_seconds` of audio, cutting at quiet moments where possible
+    pub fn set_segment_splitting(&mut self, max_segment_seconds: Option<f64>, overlap_seconds: f64) -> Result<(), String> {
+        if let Some(max_seconds) = max_segment_seconds {
+            if !max_seconds.is_finite() || max_seconds < 1.0 {
+                return Err(format!("Maximum segment length must be at least 1 second, got {}", max_seconds));
+            }
+            // Each part must still advance past the quiet point search window
+            if !overlap_seconds.is_finite() || overlap_seconds < 0.0 || overlap_seconds >= max_seconds * (1.0 - SPLIT_SEARCH_FRACTION) / 2.0 {
+                return Err(format!("Split overlap must be between 0 and {:.2} seconds, got {}",
+                    max_seconds * (1.0 - SPLIT_SEARCH_FRACTION) / 2.0, overlap_seconds));
+            }
+        }
+        self.max_segment_seconds = max_segment_seconds;
+        self.split_overlap_seconds = overlap_seconds;
+        Ok(())
+    }
+
+    /// Return segments without their audio, to be extracted from the file when needed
+    pub fn set_lazy_segment_audio(&mut self, enabled: bool) {
+        self.lazy_segment_audio = enabled;
+    }
+
+    /// Cut segment audio from the file at its own sample rate (e.g. 48kHz for archiving)
+    /// instead of from the VAD's 16kHz copy. Detection and the segments' sample indices
+    /// stay at the VAD rate; normalization and EQ only shape what VAD sees and don't
+    /// apply to the original-rate audio. Keeps a second copy of the file in memory.
+    pub fn set_preserve_original_rate(&mut self, enabled: bool) {
+        self.preserve_original_rate = enabled;
+    }
+
+    /// Segment files of at least `min_seconds` as they decode, holding a window of audio at
+    /// a time rather than the whole track (an hour by default), or never with None. Settings
+    /// that need the whole file at once keep such files on the regular path: normalization,
+    /// EQ, the noise floor threshold, skipping silence and preserving the original rate.
+    pub fn set_streaming_threshold(&mut self, min_seconds: Option<f64>) -> Result<(), String> {
+        if let Some(min_seconds) = min_seconds {
+            if !min_seconds.is_finite() || min_seconds < 0.0 {
+                return Err(format!("Streaming threshold must be a non-negative number of seconds, got {}", min_seconds));
+            }
+        }
+        self.streaming_min_seconds = min_seconds;
+        Ok(())
+    }
+
+    /// Dither float and 24/32-bit sources down to i16 instead of rounding them
+    pub fn set_dither(&mut self, enabled: bool) {
+        self.dither = enabled;
+    }
+
+    /// Keep the VAD's speech probabilities, averaged to one value per `resolution_ms`,
+    /// for take_speech_probabilities. Off by default.
+    pub fn set_probability_resolution(&mut self, resolution_ms: Option<u32>) -> Result<(), String> {
+        if resolution_ms == Some(0) {
+            return Err("Speech probability resolution must be at least 1 ms".to_string());
+        }
+        self.probability_resolution_ms = resolution_ms;
+        Ok(())
+    }
+
+    /// Speech probabilities from the last run, if they were requested and VAD ran
+    pub fn take_speech_probabilities(&mut self) -> Option<Vec<f32>> {
+        self.speech_probabilities.take()
+    }
+
+    /// Warnings about the input noticed during the last run
+    pub fn take_warnings(&mut self) -> Vec<ProcessingWarning> {
+        std::mem::take(self.warnings.get_mut().unwrap())
+    }
+
+    fn warn(&self, kind: WarningKind, message: String) {
+        debug_log!("Warning: {}", message);
+        self.warnings.lock().unwrap().push(ProcessingWarning { kind, message });
+    }
+
+    // Share of clipped samples, warned about above CLIPPING_WARN_RATIO. Only reported;
+    // overloaded input can't be repaired here.
+    fn check_clipping<F>(&self, clipped: usize, decoded: usize, progress_callback: &F)
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        let clipping_ratio = clipped as f64 / decoded.max(1) as f64;
+        if clipping_ratio > CLIPPING_WARN_RATIO {
+            let warning = format!("{:.1}% of samples are clipped, the recording was made too loud", clipping_ratio * 100.0);
+            progress_callback("Clipping detected", 24.0, Some(&warning));
+            self.warn(WarningKind::Clipping, warning);
+        }
+    }
+
+    // Warn when a WAV file's data size doesn't match its contents, which usually means a
+    // damaged or half-written file whose audio may come out cut short
+    fn check_wav_data_length<R: Read + Seek>(&self, reader: &mut R) {
+        let Ok(Some(mismatch)) = wav::check_data_length(reader) else {
+            return;
+        };
+        let message = if mismatch.is_placeholder() {
+            format!("The WAV header doesn't state the audio length, all {} bytes after it were decoded", mismatch.actual)
+        } else {
+            format!("The WAV header declares {} bytes of audio but the file has {}; the audio may be cut short or damaged",
+                mismatch.declared, mismatch.actual)
+        };
+        self.warn(WarningKind::WavDataLength, message);
+    }
+
+    // Warn about final segments that hint at unsuitable audio or VAD settings
+    fn check_segments(&self, segments: &[AudioSegment]) {
+        if let Some(first) = segments.iter().map(|segment| segment.start_time_seconds).reduce(f64::min) {
+            if first > LATE_SPEECH_SECONDS {
+                self.warn(WarningKind::LateSpeech, format!(
+                    "No speech in the first {:.0}s, the first segment starts at {:.1}s", LATE_SPEECH_SECONDS, first));
+            }
+        }
+        let short = segments.iter()
+            .filter(|segment| segment.end_time_seconds - segment.start_time_seconds < SHORT_SEGMENT_SECONDS)
+            .count();
+        if segments.len() >= MANY_SHORT_SEGMENTS_MIN_COUNT && short * 2 > segments.len() {
+            self.warn(WarningKind::ManyShortSegments, format!(
+                "{} of {} segments are shorter than {:.0}s; try a higher VAD threshold or merge gap",
+                short, segments.len(), SHORT_SEGMENT_SECONDS));
+        }
+    }
+
+    /// Stop decoding and VAD with `ProcessingCancelled` once this flag is set
+    pub fn set_cancel_flag(&mut self, cancel_flag: Option<Arc<AtomicBool>>) {
+        self.cancel_flag = cancel_flag;
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_flag.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    fn check_cancelled(&self) -> Result<(), ProcessingCancelled> {
+        if self.is_cancelled() {
+            return Err(ProcessingCancelled);
+        }
+        Ok(())
+    }
+
+    /// Cache segment boundaries on disk, keyed by file content and configuration
+    pub fn set_segment_cache(&mut self, segment_cache: Option<SegmentCache>) {
+        self.segment_cache = segment_cache;
+    }
+
+    /// Keep decoded audio at the VAD rate in memory, so later runs on the same file skip
+    /// decoding and resampling
+    pub fn set_decoded_audio_cache(&mut self, decoded_cache: Option<Arc<Mutex<DecodedAudioCache>>>) {
+        self.decoded_cache = decoded_cache;
+    }
+
+    /// Choose between the Silero model and the loudness gate for speech detection
+    pub fn set_vad_backend(&mut self, vad_backend: VadBackend) {
+        self.vad_backend = vad_backend;
+    }
+
+    /// Set the VAD threshold, padding and minimum speech duration
+    pub fn set_vad_config(&mut self, vad_config: VadConfig) -> Result<(), String> {
+        vad_config.validate()?;
+        self.vad_config = vad_config;
+        Ok(())
+    }
+
+    /// Merge detected segments separated by at most this many seconds
+    pub fn set_merge_gap(&mut self, seconds: f64) {
+        self.merge_gap_seconds = seconds;
+    }
+
+    /// Crossfade over MERGE_CROSSFADE_MS where merged segment audio joins audio that
+    /// doesn't continue it seamlessly, so playback doesn't click there. Off by default.
+    pub fn set_crossfade_merges(&mut self, enabled: bool) {
+        self.crossfade_merges = enabled;
+    }
+
+    /// Drop detected segments shorter than this before merging
+    pub fn set_min_segment_duration(&mut self, seconds: f64) {
+        self.min_segment_duration_seconds = seconds;
+    }
+
+    /// Enable (Some) or disable (None) the adaptive VAD threshold
+    pub fn set_adaptive_threshold(&mut self, adaptive_threshold: Option<AdaptiveThreshold>) {
+        self.adaptive_threshold = adaptive_threshold;
+    }
+
+    /// Raise the VAD threshold for noisy recordings, judged once per file from the level
+    /// of its quietest stretches. Clean audio keeps the configured threshold.
+    pub fn set_noise_floor_threshold(&mut self, enabled: bool) {
+        self.noise_floor_threshold = enabled;
+    }
+
+    /// Enable or disable the dual-mono fast path when downmixing multichannel audio
+    pub fn set_detect_dual_mono(&mut self, enabled: bool) {
+        self.detect_dual_mono = enabled;
+    }
+
+    /// Choose how multichannel audio is collapsed to mono
+    pub fn set_channel_mix_mode(&mut self, mode: ChannelMixMode) {
+        self.channel_mix = mode;
+    }
+
+    // Decode audio using Symphonia (supports MP3, WAV, FLAC, etc.)
+    pub fn decode_audio_symphonia(&self, file_path: &str) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>> {
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        self.decode_audio_symphonia_with_progress(file_path, &dummy_callback)
+    }
+
+    // Probe a file and return its format reader together with the first decodable track
+    fn open_audio_file(file_path: &str) -> Result<OpenedAudio, Box<dyn std::error::Error>> {
+        Self::first_audio_track(Self::probe_audio_file(file_path)?)
+    }
+
+    // Same as `open_audio_file` for the contents of a file held in memory
+    fn open_audio_bytes(data: &[u8], hint_ext: Option<&str>) -> Result<OpenedAudio, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Err(DecodeError::EmptyFile.into());
+        }
+        // The stream has to own its source, so the bytes are copied once
+        let mut data = if wav::is_rifx(data) {
+            debug_log!("Converting big-endian RIFX WAV to RIFF");
+            wav::rifx_to_riff(data)?
+        } else {
+            data.to_vec()
+        };
+        if let Ok(Some(mismatch)) = wav::check_data_length(&mut Cursor::new(&data)) {
+            if mismatch.is_placeholder() {
+                debug_log!("WAV data size is unset, reading to the end of the data");
+                wav::patch_data_length(&mut data, &mismatch);
+            }
+        }
+        Self::first_audio_track(Self::probe_source(Box::new(Cursor::new(data)), hint_ext)?)
+    }
+
+    fn first_audio_track((probed, read_position): (ProbeResult, ReadPosition)) -> Result<OpenedAudio, Box<dyn std::error::Error>> {
+        let format = probed.format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(DecodeError::NoAudioTrack)?
+            .clone();
+
+        Ok((format, track, read_position))
+    }
+
+    fn probe_audio_file(file_path: &str) -> Result<(ProbeResult, ReadPosition), Box<dyn std::error::Error>> {
+        let mut file = File::open(file_path).map_err(DecodeError::Io)?;
+        if file.metadata().map_err(DecodeError::Io)?.len() == 0 {
+            return Err(DecodeError::EmptyFile.into());
+        }
+
+        // Symphonia only reads little-endian RIFF, so convert big-endian RIFX up front
+        let mut header = [0u8; 12];
+        let is_rifx = file.read_exact(&mut header).is_ok() && wav::is_rifx(&header);
+        // Streamed recordings leave the data size unset, which would read as no audio at all
+        let unsized_data = wav::check_data_length(&mut file).ok().flatten()
+            .filter(wav::DataLengthMismatch::is_placeholder);
+        file.seek(SeekFrom::Start(0))?;
+
+        let source: Box<dyn MediaSource> = if is_rifx {
+            debug_log!("Converting big-endian RIFX WAV to RIFF: {}", file_path);
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            Box::new(Cursor::new(wav::rifx_to_riff(&data)?))
+        } else if let Some(mismatch) = unsized_data {
+            debug_log!("WAV data size of {} is unset, reading to the end of the file", file_path);
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            wav::patch_data_length(&mut data, &mismatch);
+            Box::new(Cursor::new(data))
+        } else {
+            Box::new(file)
+        };
+        let extension = std::path::Path::new(file_path).extension().and_then(|extension| extension.to_str());
+        Self::probe_source(source, extension)
+    }
+
+    // Probe a media source, counting the bytes read from it for progress
+    fn probe_source(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<(ProbeResult, ReadPosition), Box<dyn std::error::Error>> {
+        let read_position = ReadPosition {
+            bytes_read: Arc::new(AtomicU64::new(0)),
+            total_bytes: source.byte_len(),
+        };
+        let source = TrackedSource { inner: source, bytes_read: read_position.bytes_read.clone() };
+        let mss = MediaSourceStream::new(Box::new(source), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
+        }
+
+        let meta_opts: MetadataOptions = Default::default();
+        let fmt_opts: FormatOptions = Default::default();
+
+        let probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)
+            .map_err(DecodeError::from)?;
+        Ok((probed, read_position))
+    }
+
+    /// Duration, sample rate, channels, codec and container of a file. Taken from the
+    /// headers where they are complete, which is fast at any size; only files that don't
+    /// state their length (or rate or channels) are decoded to find it.
+    pub fn probe_metadata(&self, file_path: &str, measure_clipping: bool) -> Result<AudioMetadata, Box<dyn std::error::Error>> {
+        let (_, track, _) = Self::open_audio_file(file_path)?;
+        let params = &track.codec_params;
+
+        let mut header = Vec::with_capacity(12);
+        File::open(file_path)?.take(12).read_to_end(&mut header)?;
+        let format = sniff_format(&header).map(str::to_string)
+            .or_else(|| std::path::Path::new(file_path).extension().map(|ext| ext.to_string_lossy().to_lowercase()))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let duration_seconds = match (params.n_frames, params.time_base, params.sample_rate) {
+            (Some(frames), Some(time_base), _) => {
+                let time = time_base.calc_time(frames);
+                Some(time.seconds as f64 + time.frac)
+            }
+            (Some(frames), None, Some(sample_rate)) => Some(frames as f64 / sample_rate as f64),
+            _ => None,
+        };
+        let channels = params.channels.map(|channels| channels.count());
+
+        let (duration_seconds, sample_rate, channels, clipping_ratio) = match (duration_seconds, params.sample_rate, channels) {
+            (Some(duration), Some(sample_rate), Some(channels)) if !measure_clipping => (duration, sample_rate, channels, None),
+            (duration, _, _) => {
+                if duration.is_none() {
+                    debug_log!("Headers of {} are incomplete, decoding to measure it", file_path);
+                }
+                let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+                let (mut samples, mut clipped) = (0usize, 0usize);
+                let (sample_rate, channels) = self.decode_packets(Self::open_audio_file(file_path)?, &dummy_callback, false, |interleaved, _| {
+                    samples += interleaved.len();
+                    clipped += dsp::count_clipped(interleaved);
+                })?;
+                let channels = channels.max(1);
+                let clipping_ratio = if samples > 0 { clipped as f64 / samples as f64 } else { 0.0 };
+                // The container's duration is exact where the decoded length may include padding
+                let duration = duration.unwrap_or(samples as f64 / channels as f64 / sample_rate as f64);
+                (duration, sample_rate, channels, Some(clipping_ratio))
+            }
+        };
+
+        Ok(AudioMetadata {
+            duration_seconds,
+            sample_rate,
+            channels,
+            codec: codec_name(params.codec),
+            format,
+            clipping_ratio,
+        })
+    }
+
+    // Embedded picture, preferring the front cover. Tags read ahead of the container
+    // (ID3v2 on MP3) are checked before the container's own metadata (FLAC, MP4).
+    pub fn read_cover_art(&self, file_path: &str) -> Result<Option<CoverArt>, Box<dyn std::error::Error>> {
+        let (mut probed, _) = Self::probe_audio_file(file_path)?;
+
+        let mut visuals: Vec<Visual> = Vec::new();
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|metadata| metadata.current()) {
+            visuals.extend_from_slice(revision.visuals());
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            visuals.extend_from_slice(revision.visuals());
+        }
+
+        let cover = visuals.iter()
+            .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+            .or_else(|| visuals.first());
+
+        Ok(cover.map(|visual| CoverArt {
+            mime: visual.media_type.clone(),
+            bytes: visual.data.to_vec(),
+        }))
+    }
+
+    // Dual-mono detection for one file's decode, off unless enabled on this processor
+    fn dual_mono_detector(&self, full_scale: f64) -> DualMonoDetector {
+        DualMonoDetector::new(self.detect_dual_mono, full_scale)
+    }
+
+    // Collapse interleaved samples to mono according to the channel mix mode and
+    // append them to `out`. `weights` from surround_weights replace the even average and
+    // power mixes for surround audio. `dual_mono` carries the file's dual-mono decision
+    // from buffer to buffer.
+    fn downmix_into(&self, interleaved: &[i16], channels: usize, weights: Option<&[f64]>, dual_mono: &mut DualMonoDetector, out: &mut Vec<i16>) {
+        if channels <= 1 {
+            out.extend_from_slice(interleaved);
+            return;
+        }
+
+        match self.channel_mix {
+            ChannelMixMode::Average | ChannelMixMode::Power => {
+                // Identical channels carry the same signal, so take the first one as-is. Frames
+                // still being probed do the same, or the power mix would drop 3 dB once the
+                // file is found to be dual-mono.
+                let identical = dual_mono.identical_frames(interleaved, channels) * channels;
+                out.extend(interleaved[..identical].iter().step_by(channels));
+                let interleaved = &interleaved[identical..];
+
+                if let Some(weights) = weights.filter(|weights| weights.len() == channels) {
+                    let gain = self.surround_gain();
+                    for chunk in interleaved.chunks(channels) {
+                        let mono_sample = chunk.iter().zip(weights).map(|(&s, &weight)| s as f64 * weight).sum::<f64>() * gain;
+                        out.push(mono_sample.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16);
+                    }
+                } else if self.channel_mix == ChannelMixMode::Power {
+                    let scale = 1.0 / (channels as f64).sqrt();
+                    for chunk in interleaved.chunks(channels) {
+                        let mono_sample = chunk.iter().map(|&s| s as f64).sum::<f64>() * scale;
+                        out.push(mono_sample.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16);
+                    }
+                } else {
+                    for chunk in interleaved.chunks(channels) {
+                        let mono_sample = chunk.iter().map(|&s| s as i32).sum::<i32>() / chunk.len() as i32;
+                        out.push(mono_sample as i16);
+                    }
+                }
+            }
+            ChannelMixMode::Left => out.extend(interleaved.iter().step_by(channels)),
+            ChannelMixMode::Right => out.extend(interleaved.iter().skip(1).step_by(channels)),
+            ChannelMixMode::Max => {
+                for chunk in interleaved.chunks(channels) {
+                    let loudest = chunk.iter().copied().max_by_key(|&s| (s as i32).abs()).unwrap_or(0);
+                    out.push(loudest);
+                }
+            }
+        }
+    }
+
+    // Surround weights fold down to the average of a BS.775 stereo mix; the power mix
+    // takes that stereo mix's sum over sqrt(2), like it does for stereo sources
+    fn surround_gain(&self) -> f64 {
+        if self.channel_mix == ChannelMixMode::Power { std::f64::consts::SQRT_2 } else { 1.0 }
+    }
+
+    // Float counterpart of downmix_into. Identical channels average to themselves, so
+    // only the power mix needs a dual-mono path.
+    fn downmix_f32_into(&self, interleaved: &[f32], channels: usize, weights: Option<&[f64]>, dual_mono: &mut DualMonoDetector, out: &mut Vec<f32>) {
+        if channels <= 1 {
+            out.extend_from_slice(interleaved);
+            return;
+        }
+
+        let interleaved = if self.channel_mix == ChannelMixMode::Power {
+            let identical = dual_mono.identical_frames(interleaved, channels) * channels;
+            out.extend(interleaved[..identical].iter().step_by(channels));
+            &interleaved[identical..]
+        } else {
+            interleaved
+        };
+
+        match self.channel_mix {
+            ChannelMixMode::Average | ChannelMixMode::Power if weights.is_some_and(|weights| weights.len() == channels) => {
+                let (weights, gain) = (weights.unwrap_or_default(), self.surround_gain());
+                out.extend(interleaved.chunks(channels).map(|chunk| {
+                    let mono_sample = chunk.iter().zip(weights).map(|(&s, &weight)| s as f64 * weight).sum::<f64>() * gain;
+                    (mono_sample as f32).clamp(-1.0, 1.0)
+                }));
+            }
+            ChannelMixMode::Power => {
+                let scale = 1.0 / (channels as f32).sqrt();
+                out.extend(interleaved.chunks(channels).map(|chunk| (chunk.iter().sum::<f32>() * scale).clamp(-1.0, 1.0)));
+            }
+            ChannelMixMode::Average => out.extend(interleaved.chunks(channels).map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)),
+            ChannelMixMode::Left => out.extend(interleaved.iter().step_by(channels)),
+            ChannelMixMode::Right => out.extend(interleaved.iter().skip(1).step_by(channels)),
+            ChannelMixMode::Max => {
+                out.extend(interleaved.chunks(channels)
+                    .map(|chunk| chunk.iter().copied().max_by(|a, b| a.abs().total_cmp(&b.abs())).unwrap_or(0.0)));
+            }
+        }
+    }
+
+    /// Decode to mono f32 samples in [-1, 1] at the file's own rate, keeping the
+    /// precision of 24-bit and float sources for export
+    pub fn decode_audio_f32(&self, file_path: &str) -> Result<(Vec<f32>, u32), Box<dyn std::error::Error>> {
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        let mut samples = Vec::new();
+        let mut sample_buf = None;
+
+        let audio = Self::open_audio_file(file_path)?;
+        let weights = surround_weights(audio.1.codec_params.channels);
+        let mut dual_mono = self.dual_mono_detector(1.0);
+        let (sample_rate, _) = self.decode_buffers(audio, &dummy_callback, |audio_buf, channels| {
+            let buf = sample_buf.get_or_insert_with(|| SampleBuffer::<f32>::new(audio_buf.capacity() as u64, *audio_buf.spec()));
+            buf.copy_interleaved_ref(audio_buf);
+            self.downmix_f32_into(buf.samples(), channels, weights.as_deref(), &mut dual_mono, &mut samples);
+        })?;
+
+        if samples.is_empty() {
+            return Err(DecodeError::NoSamples.into());
+        }
+        Ok((samples, sample_rate))
+    }
+
+    /// Decode audio already in memory, as `decode_audio_symphonia` does for a file.
+    /// `hint_ext` is the extension of the file the bytes came from, if known.
+    pub fn decode_audio_bytes(&self, data: &[u8], hint_ext: Option<&str>) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>> {
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        self.check_wav_data_length(&mut Cursor::new(data));
+        self.decode_mono(Self::open_audio_bytes(data, hint_ext)?, &dummy_callback)
+    }
+
+    fn decode_audio_symphonia_with_progress<F>(&self, file_path: &str, progress_callback: &F) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        if let Ok(mut file) = File::open(file_path) {
+            self.check_wav_data_length(&mut file);
+        }
+        self.decode_mono(Self::open_audio_file(file_path)?, progress_callback)
+    }
+
+    // Decode an opened track to mono
+    fn decode_mono<F>(&self, audio: OpenedAudio, progress_callback: &F) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        let mut samples = Vec::new();
+        let sample_rate = self.decode_mono_blocks(audio, progress_callback, |block| samples.extend_from_slice(block))?;
+
+        if samples.is_empty() {
+            return Err(DecodeError::NoSamples.into());
+        }
+        Ok((samples, sample_rate))
+    }
+
+    // Decode an opened track to mono, handing it to `on_block` one decoded buffer at a
+    // time. Returns the sample rate.
+    fn decode_mono_blocks<F, B>(&self, audio: OpenedAudio, progress_callback: &F, mut on_block: B) -> Result<u32, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        B: FnMut(&[i16]),
+    {
+        let mut block = Vec::new();
+        let mut dual_mono = self.dual_mono_detector(i16::MAX as f64);
+        let (mut decoded, mut clipped) = (0usize, 0usize);
+
+        let weights = surround_weights(audio.1.codec_params.channels);
+        if weights.is_some() {
+            debug_log!("Folding down {} channels with surround weights", audio.1.codec_params.channels.map_or(0, |layout| layout.count()));
+        }
+        let (sample_rate, _) = self.decode_packets(audio, progress_callback, self.dither, |interleaved, channels| {
+            decoded += interleaved.len();
+            clipped += dsp::count_clipped(interleaved);
+            // Convert to mono if stereo
+            block.clear();
+            self.downmix_into(interleaved, channels, weights.as_deref(), &mut dual_mono, &mut block);
+            on_block(&block);
+        })?;
+
+        self.check_clipping(clipped, decoded, progress_callback);
+        Ok(sample_rate)
+    }
+
+    // Decode every packet of the opened track, handing each interleaved i16 buffer
+    // to `on_buffer` along with the channel count. With `dither`, sources with more than
+    // 16 bits of precision are dithered down instead of rounded. Returns (sample_rate, channels).
+    fn decode_packets<F, B>(&self, audio: OpenedAudio, progress_callback: &F, dither: bool, mut on_buffer: B) -> Result<(u32, usize), Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        B: FnMut(&[i16], usize),
+    {
+        let mut converter = PcmConverter::new(dither);
+        self.decode_buffers(audio, progress_callback, |audio_buf, channels| {
+            on_buffer(converter.convert(audio_buf), channels);
+        })
+    }
+
+    // Decode every packet of the opened track, handing each decoded buffer to
+    // `on_buffer` along with the channel count. Returns (sample_rate, channels).
+    fn decode_buffers<F, B>(&self, audio: OpenedAudio, progress_callback: &F, mut on_buffer: B) -> Result<(u32, usize), Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        B: FnMut(AudioBufferRef<'_>, usize),
+    {
+        let (mut format, track, read_position) = audio;
+
+        let dec_opts: DecoderOptions = Default::default();
+        let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &dec_opts)
+            .map_err(|_| DecodeError::UnsupportedCodec(codec_name(track.codec_params.codec)))?;
+
+        let track_id = track.id;
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
+        let channels = track.codec_params.channels.unwrap_or_default().count();
+
+        // Total duration in seconds when the container reports it, for progress tracking
+        let total_seconds = track.codec_params.n_frames
+            .map(|frames| frames as f64 / sample_rate as f64)
+            .filter(|&seconds| seconds > 0.0);
+        let time_base = track.codec_params.time_base;
+
+        let mut packet_count = 0;
+        // Highest progress reported so far; packet timestamps aren't guaranteed to only move forward
+        let mut reported_progress = 10.0f64;
+
+        loop {
+            self.check_cancelled()?;
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::ResetRequired) => {
+                    break;
+                }
+                Err(SymphoniaError::IoError(err))
+                    if err.kind() == std::io::ErrorKind::UnexpectedEof
+                        && err.to_string() == "end of stream" =>
+                {
+                    break;
+                }
+                Err(err) => return Err(DecodeError::from(err).into()),
+            };
+
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            packet_count += 1;
+            
+            // Update progress every 50 packets, from the packet timestamp against the track
+            // duration, or failing that from how far into the file we have read
+            if packet_count % 50 == 0 {
+                let position_seconds = match (time_base, total_seconds) {
+                    (Some(time_base), Some(_)) => {
+                        let time = time_base.calc_time(packet.ts() + packet.dur());
+                        Some(time.seconds as f64 + time.frac)
+                    }
+                    (None, Some(_)) => Some((packet.ts() + packet.dur()) as f64 / sample_rate as f64),
+                    _ => None,
+                };
+                let fraction = position_seconds
+                    .zip(total_seconds)
+                    .map(|(position, total)| position / total)
+                    .or_else(|| read_position.fraction())
+                    .unwrap_or(0.0);
+                let decode_progress = (10.0 + fraction.clamp(0.0, 1.0) * 15.0).min(24.0);
+                reported_progress = reported_progress.max(decode_progress);
+                progress_callback("Decoding audio packets", reported_progress, Some(&format!("Processed {} packets", packet_count)));
+            }
+
+            match decoder.decode(&packet) {
+                Ok(audio_buf) => on_buffer(audio_buf, channels),
+                Err(SymphoniaError::IoError(_)) => break,
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(err) => return Err(DecodeError::from(err).into()),
+            }
+        }
+
+        Ok((sample_rate, channels))
+    }
+
+    /// Measure sample peak and oversampled true peak across all channels of a file
+    pub fn measure_headroom(&self, file_path: &str) -> Result<Headroom, Box<dyn std::error::Error>> {
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        let mut meter: Option<dsp::TruePeakMeter> = None;
+
+        // Dither noise would only blur the measured peaks
+        self.decode_packets(Self::open_audio_file(file_path)?, &dummy_callback, false, |interleaved, channels| {
+            meter.get_or_insert_with(|| dsp::TruePeakMeter::new(channels)).process_interleaved(interleaved);
+        })?;
+
+        let meter = meter.ok_or(DecodeError::NoSamples)?;
+        Ok(Headroom {
+            true_peak_dbtp: dsp::amplitude_to_dbfs(meter.true_peak()),
+            sample_peak_dbfs: dsp::amplitude_to_dbfs(meter.sample_peak()),
+            at_full_scale: meter.sample_peak() >= i16::MAX as f64,
+        })
+    }
+
+    pub fn process_audio_file(&mut self, file_path: &str, _model_path: &str) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>> {
+        // Default progress callback that does nothing
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        self.process_audio_file_with_progress(file_path, _model_path, dummy_callback)
+    }
+
+    pub fn process_audio_file_with_progress<F>(&mut self, file_path: &str, _model_path: &str, progress_callback: F) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        self.run_pipeline(file_path, &progress_callback, &|_| {}).map(|(segments, _)| segments)
+    }
+
+    // Like process_audio_file_with_progress, but also reports segments as they are found
+    pub fn process_audio_file_streaming<F, S>(&mut self, file_path: &str, progress_callback: F, segment_callback: S) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        S: Fn(SegmentUpdate),
+    {
+        self.run_pipeline(file_path, &progress_callback, &segment_callback).map(|(segments, _)| segments)
+    }
+
+    /// Segment boundaries from the same pipeline a full run uses, so counts match, but
+    /// without encoding or returning any segment audio
+    pub fn estimate_segments(&mut self, file_path: &str) -> Result<Vec<SegmentInfo>, Box<dyn std::error::Error>> {
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        let lazy_segment_audio = std::mem::replace(&mut self.lazy_segment_audio, true);
+        let result = self.run_pipeline(file_path, &dummy_callback, &|_| {});
+        self.lazy_segment_audio = lazy_segment_audio;
+
+        let (segments, _) = result?;
+        Ok(segments.iter()
+            .map(|segment| SegmentInfo {
+                start_time_seconds: segment.start_time_seconds,
+                end_time_seconds: segment.end_time_seconds,
+            })
+            .collect())
+    }
+
+    // Run the full pipeline and report how long each stage took
+    pub fn benchmark_pipeline(&mut self, file_path: &str) -> Result<PipelineTimings, Box<dyn std::error::Error>> {
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        self.run_pipeline(file_path, &dummy_callback, &|_| {}).map(|(_, timings)| timings)
+    }
+
+    fn run_pipeline<F, S>(&mut self, file_path: &str, progress_callback: &F, segment_callback: &S) -> Result<(Vec<AudioSegment>, PipelineTimings), Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        S: Fn(SegmentUpdate),
+    {
+        let pipeline_start = Instant::now();
+        let mut timings = PipelineTimings::default();
+        self.warnings.get_mut().unwrap().clear();
+
+        // Check file extension to provide better error messages
+        let path = std::path::Path::new(file_path);
+        let extension = path.extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        
+        debug_log!("Processing audio file: {} (format: {})", file_path, extension);
+        progress_callback("Validating file format", 5.0, Some(&format!("Detected format: {}", extension)));
+        
+        // A missing or wrong extension is fine as long as Symphonia recognizes the content
+        if !is_supported_format(&extension) {
+            if Self::open_audio_file(file_path).is_err() {
+                return Err(format!("Unsupported audio format: '{}'. Supported formats: {}",
+                    extension, SUPPORTED_FORMATS.join(", ").to_uppercase()).into());
+            }
+            debug_log!("Extension '{}' is not a known audio format, but the content is decodable", extension);
+        }
+        
+        // Long files are segmented as they decode instead of being decoded whole first
+        let audio = Self::open_audio_file(file_path)?;
+        let merged_segments = if self.should_stream(&audio) {
+            self.stream_segments(file_path, audio, progress_callback, segment_callback, &mut timings)?
+        } else {
+            // Lazy segment audio is fetched by time later, so there would be nothing to cut
+            let keep_original = self.preserve_original_rate && !self.lazy_segment_audio;
+            let (content, original) = self.load_audio(file_path, keep_original, progress_callback, &mut timings)?;
+            let mut merged_segments = self.segment_audio(content, Some(file_path), progress_callback, segment_callback, &mut timings)?;
+
+            let mut output_rate = u32::from(self.sample_rate);
+            if let Some((original, original_rate)) = &original {
+                self.cut_original_audio(&mut merged_segments, original, *original_rate);
+                output_rate = *original_rate;
+            }
+
+            let stage_start = Instant::now();
+            self.encode_segments(&mut merged_segments, output_rate, progress_callback);
+            timings.encode_ms = elapsed_ms(stage_start);
+            merged_segments
+        };
+
+        progress_callback("Segmentation complete", 99.0, Some(&format!("Optimized to {} final segments", merged_segments.len())));
+
+        timings.total_ms = elapsed_ms(pipeline_start);
+        Ok((merged_segments, timings))
+    }
+
+    // Whether run_pipeline segments this file as it decodes: when it is at least the
+    // streaming threshold long and no setting needs the whole file at once
+    fn should_stream(&self, audio: &OpenedAudio) -> bool {
+        let params = &audio.1.codec_params;
+        let duration_seconds = params.n_frames.zip(params.sample_rate).map(|(frames, rate)| frames as f64 / rate as f64);
+        let long_enough = self.streaming_min_seconds.zip(duration_seconds).is_some_and(|(min_seconds, duration)| duration >= min_seconds);
+
+        // Original-rate audio would need a second stream of its own
+        let needs_whole_file = self.normalize_peak_dbfs.is_some() || !self.eq_bands.is_empty() || self.noise_floor_threshold
+            || self.skip_silence || (self.preserve_original_rate && !self.lazy_segment_audio);
+        if long_enough && needs_whole_file {
+            debug_log!("Decoding {:.0}s of audio whole, as the settings need the entire file", duration_seconds.unwrap_or_default());
+        }
+        long_enough && !needs_whole_file
+    }
+
+    // Segment a file as it decodes, for files too long to hold in memory decoded. Audio
+    // goes through the resampler and VAD a buffer at a time, and once no later speech run
+    // could merge with the runs found so far, their stretch of audio is merged, finished
+    // and encoded on its own and dropped (see settle_stream). Segments come out as
+    // run_pipeline's would, except that a segment with too little quiet audio around it
+    // takes its SNR against the noise of its own stretch rather than of the whole file.
+    // The segment and decoded-audio caches are bypassed, as both work on whole files.
+    fn stream_segments<F, S>(&mut self, file_path: &str, audio: OpenedAudio, progress_callback: &F, segment_callback: &S, timings: &mut PipelineTimings) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        S: Fn(SegmentUpdate),
+    {
+        if let Ok(mut file) = File::open(file_path) {
+            self.check_wav_data_length(&mut file);
+        }
+
+        // decode_buffers assumes the same rate for tracks that don't state theirs
+        let input_rate = audio.1.codec_params.sample_rate.unwrap_or(44100);
+        let vad_rate = u32::from(self.sample_rate);
+        let (detector, chunk_size) = match self.vad_backend {
+            VadBackend::Silero => {
+                let (vad, chunk_size) = self.voice_activity_detector()?;
+                (StreamDetector::Silero(vad), chunk_size)
+            }
+            VadBackend::Rms => (StreamDetector::Rms(self.rms_gate(RMS_FRAME_MS, RMS_THRESHOLD_DBFS, RMS_HANG_MS)), self.vad_chunk_size()?),
+        };
+        let mut stream = SegmentStream {
+            resampler: if input_rate == vad_rate { None } else { Some(StreamResampler::new(input_rate, vad_rate)?) },
+            detector,
+            chunk_size,
+            tracker: self.run_tracker(self.vad_config, chunk_size),
+            window: Vec::new(),
+            window_start: 0,
+            runs: Vec::new(),
+            runs_found: 0,
+            segments: Vec::new(),
+            chunk_probabilities: self.probability_resolution_ms.map(|_| Vec::new()),
+        };
+
+        debug_log!("Streaming {} Hz -> {} Hz through {:?} VAD", input_rate, vad_rate, self.vad_backend);
+        progress_callback("Decoding audio file", 10.0, Some("Detecting speech while the audio decodes"));
+        // Decoding paces the whole pass, so its progress stands for all of it
+        let stream_callback = |step: &str, progress: f64, details: Option<&str>| {
+            progress_callback(step, 10.0 + (progress - 10.0) / 15.0 * 80.0, details);
+        };
+
+        let stage_start = Instant::now();
+        let mut failure = None;
+        self.decode_mono_blocks(audio, &stream_callback, |block| {
+            if failure.is_none() {
+                failure = self.stream_block(&mut stream, block, segment_callback, timings).err();
+            }
+        })?;
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        self.finish_stream(&mut stream, segment_callback, timings)?;
+        timings.decode_ms = elapsed_ms(stage_start) - timings.resample_ms - timings.vad_ms - timings.merge_ms - timings.encode_ms;
+
+        let total_samples = stream.window_start + stream.window.len();
+        if total_samples == 0 {
+            return Err(DecodeError::NoSamples.into());
+        }
+        progress_callback("Speech detection complete", 90.0, Some(&format!("Processed {} audio chunks", stream.tracker.chunk_count())));
+        debug_log!("Streamed {} samples into {} final segments", total_samples, stream.segments.len());
+
+        self.speech_probabilities = stream.chunk_probabilities.zip(self.probability_resolution_ms).map(|(chunk_probabilities, resolution_ms)| {
+            Self::downsample_probabilities(&chunk_probabilities, chunk_size, total_samples, vad_rate, resolution_ms)
+        });
+        self.check_segments(&stream.segments);
+        segment_callback(SegmentUpdate::Merged(&stream.segments));
+        Ok(stream.segments)
+    }
+
+    // Resample one decoded block of a streamed file and run VAD over it
+    fn stream_block<S>(&self, stream: &mut SegmentStream, block: &[i16], segment_callback: &S, timings: &mut PipelineTimings) -> Result<(), Box<dyn std::error::Error>>
+    where
+        S: Fn(SegmentUpdate),
+    {
+        let Some(resampler) = &mut stream.resampler else {
+            return self.stream_vad(stream, block, segment_callback, timings);
+        };
+        let stage_start = Instant::now();
+        let mut resampled = Vec::new();
+        resampler.push(block, &mut resampled)?;
+        timings.resample_ms += elapsed_ms(stage_start);
+        self.stream_vad(stream, &resampled, segment_callback, timings)
+    }
+
+    // Append audio at the VAD rate to a stream and run VAD over every chunk that can be
+    // judged yet, turning the runs that close into segments
+    fn stream_vad<S>(&self, stream: &mut SegmentStream, samples: &[i16], segment_callback: &S, timings: &mut PipelineTimings) -> Result<(), Box<dyn std::error::Error>>
+    where
+        S: Fn(SegmentUpdate),
+    {
+        let stage_start = Instant::now();
+        stream.window.extend_from_slice(samples);
+        if let StreamDetector::Rms(gate) = &mut stream.detector {
+            gate.push(samples);
+        }
+
+        let chunk_size = stream.chunk_size;
+        let stream_end = stream.window_start + stream.window.len();
+        loop {
+            let chunk_start = stream.tracker.chunk_count() * chunk_size;
+            let chunk = chunk_start..chunk_start + chunk_size;
+            if chunk.end > stream_end {
+                break;
+            }
+            let probability = match &mut stream.detector {
+                StreamDetector::Silero(vad) => vad.predict(stream.window[chunk.start - stream.window_start..chunk.end - stream.window_start].iter().copied()),
+                StreamDetector::Rms(gate) => match gate.is_speech(chunk) {
+                    Some(is_speech) => if is_speech { 1.0 } else { 0.0 },
+                    None => break,
+                },
+            };
+            if let Some(chunk_probabilities) = &mut stream.chunk_probabilities {
+                chunk_probabilities.push(probability);
+            }
+            if let Some(run) = stream.tracker.push(probability) {
+                self.add_stream_run(stream, run.start * chunk_size, run.end * chunk_size, segment_callback);
+            }
+        }
+        if let StreamDetector::Rms(gate) = &mut stream.detector {
+            gate.forget_before(stream.tracker.chunk_count() * chunk_size);
+        }
+        timings.vad_ms += elapsed_ms(stage_start);
+
+        self.settle_stream(stream, timings)
+    }
+
+    // Flush the resampler and the loudness gate at the end of a streamed file, then
+    // close the run still open and finish the last stretch of audio
+    fn finish_stream<S>(&self, stream: &mut SegmentStream, segment_callback: &S, timings: &mut PipelineTimings) -> Result<(), Box<dyn std::error::Error>>
+    where
+        S: Fn(SegmentUpdate),
+    {
+        if let Some(resampler) = &mut stream.resampler {
+            let mut tail = Vec::new();
+            resampler.finish(&mut tail)?;
+            self.stream_vad(stream, &tail, segment_callback, timings)?;
+        }
+        if let StreamDetector::Rms(gate) = &mut stream.detector {
+            gate.finish();
+            self.stream_vad(stream, &[], segment_callback, timings)?;
+        }
+
+        // As in speech_runs, a run reaching the last chunk also covers the partial chunk VAD never saw
+        let total_samples = stream.window_start + stream.window.len();
+        if let Some(run) = stream.tracker.finish() {
+            let end_sample = if run.end >= stream.tracker.chunk_count() { total_samples } else { run.end * stream.chunk_size };
+            self.add_stream_run(stream, (run.start * stream.chunk_size).min(total_samples), end_sample, segment_callback);
+        }
+
+        let runs = std::mem::take(&mut stream.runs);
+        let segments = self.finish_window(runs, &stream.window, stream.window_start, timings)?;
+        stream.segments.extend(segments);
+        Ok(())
+    }
+
+    // Turn samples `start..end` of a stream into a segment and report it
+    fn add_stream_run<S>(&self, stream: &mut SegmentStream, start: usize, end: usize, segment_callback: &S)
+    where
+        S: Fn(SegmentUpdate),
+    {
+        let segment_audio = stream.window[start - stream.window_start..end - stream.window_start].to_vec();
+        if segment_audio.is_empty() {
+            return;
+        }
+
+        let sample_rate = u32::from(self.sample_rate) as f64;
+        let segment = AudioSegment {
+            start_sample: start as i64,
+            end_sample: end as i64,
+            start_time_seconds: start as f64 / sample_rate,
+            end_time_seconds: end as f64 / sample_rate,
+            audio_data: segment_audio,
+            audio_base64: String::new(), // Filled in once segments are final
+            snr_db: None,
+            overlap_seconds: 0.0,
+            channel: None,
+        };
+        segment_callback(SegmentUpdate::Found(stream.runs_found, &segment));
+        stream.runs_found += 1;
+
+        // Merging drops blips anyway; dropping them now keeps them from holding the window open
+        if segment.end_time_seconds - segment.start_time_seconds >= self.min_segment_duration_seconds {
+            stream.runs.push(segment);
+        }
+    }
+
+    // Finish the runs found so far once no later run can merge with them or reach into
+    // the SNR context after them, and drop the audio nothing will need again. The
+    // context before the next run is kept for its SNR.
+    fn settle_stream(&self, stream: &mut SegmentStream, timings: &mut PipelineTimings) -> Result<(), Box<dyn std::error::Error>> {
+        let sample_rate = u32::from(self.sample_rate) as f64;
+        let context = (SNR_CONTEXT_SECONDS * sample_rate) as usize;
+        // No run reported from here on can start any earlier
+        let pending_from = stream.tracker.earliest_pending_chunk() * stream.chunk_size;
+
+        if let Some(last) = stream.runs.last() {
+            let last_end = last.end_sample as usize;
+            if pending_from < last_end + context || pending_from as f64 / sample_rate - last.end_time_seconds <= self.merge_gap_seconds {
+                return Ok(());
+            }
+            let runs = std::mem::take(&mut stream.runs);
+            let settled = &stream.window[..pending_from - stream.window_start];
+            let segments = self.finish_window(runs, settled, stream.window_start, timings)?;
+            stream.segments.extend(segments);
+        }
+
+        let keep_from = pending_from.saturating_sub(context).max(stream.window_start);
+        stream.window.drain(..keep_from - stream.window_start);
+        stream.window_start = keep_from;
+        Ok(())
+    }
+
+    // Merge, finish and encode the runs found in `window`, audio at the VAD rate starting
+    // at sample `window_start`, the same way run_pipeline does for a whole file
+    fn finish_window(&self, runs: Vec<AudioSegment>, window: &[i16], window_start: usize, timings: &mut PipelineTimings) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>> {
+        self.check_cancelled()?;
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        let sample_rate = u32::from(self.sample_rate) as f64;
+        let offset = window_start as i64;
+
+        let stage_start = Instant::now();
+        let runs = runs.into_iter().map(|run| Self::shift_segment(run, -offset, sample_rate)).collect();
+        let mut segments = self.merge_close_segments_with_progress(runs, window, self.merge_gap_seconds, self.min_segment_duration_seconds, &dummy_callback);
+        self.validate_segments(&mut segments, window.len(), sample_rate)?;
+        let mut segments = self.finish_segments(segments, window);
+        timings.merge_ms += elapsed_ms(stage_start);
+
+        let stage_start = Instant::now();
+        self.encode_segments(&mut segments, u32::from(self.sample_rate), &dummy_callback);
+        timings.encode_ms += elapsed_ms(stage_start);
+        Ok(segments.into_iter().map(|segment| Self::shift_segment(segment, offset, sample_rate)).collect())
+    }
+
+    // `segment` moved by `offset` samples, with its times following
+    fn shift_segment(mut segment: AudioSegment, offset: i64, sample_rate: f64) -> AudioSegment {
+        segment.start_sample += offset;
+        segment.end_sample += offset;
+        segment.start_time_seconds = segment.start_sample as f64 / sample_rate;
+        segment.end_time_seconds = segment.end_sample as f64 / sample_rate;
+        segment
+    }
+
+    /// Run the pipeline over several files as one timeline, e.g. a meeting exported in
+    /// parts. Each file is decoded, mixed to mono and resampled to the VAD rate on its own,
+    /// then the parts are joined end to end, so segment times and sample indices carry on
+    /// across file boundaries. Segments always come with their audio, as there is no
+    /// single file to cut it from later. Returns the segments and the total duration in seconds.
+    pub fn process_audio_files_concatenated<F>(&mut self, file_paths: &[&str], progress_callback: F) -> Result<(Vec<AudioSegment>, f64), Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        if file_paths.is_empty() {
+            return Err("No files to concatenate".into());
+        }
+        let mut timings = PipelineTimings::default();
+        self.warnings.get_mut().unwrap().clear();
+
+        // Each part gets an equal share of the decoding progress
+        let mut content = Vec::new();
+        for (index, file_path) in file_paths.iter().enumerate() {
+            debug_log!("Loading part {} of {}: {} at {:.3}s", index + 1, file_paths.len(), file_path,
+                content.len() as f64 / f64::from(u32::from(self.sample_rate)));
+            let part_callback = |step: &str, progress: f64, details: Option<&str>| {
+                progress_callback(step, (index as f64 + progress / 45.0) / file_paths.len() as f64 * 45.0, details);
+            };
+            let (part, _) = self.load_audio(file_path, false, &part_callback, &mut timings)?;
+            content.extend(part);
+        }
+        let duration_seconds = content.len() as f64 / f64::from(u32::from(self.sample_rate));
+        progress_callback("Audio concatenated", 45.0, Some(&format!("{} files, {:.1}s in total", file_paths.len(), duration_seconds)));
+
+        let lazy_segment_audio = std::mem::replace(&mut self.lazy_segment_audio, false);
+        let mut result = self.segment_audio(content, None, &progress_callback, &|_| {}, &mut timings);
+        if let Ok(segments) = &mut result {
+            self.encode_segments(segments, u32::from(self.sample_rate), &progress_callback);
+        }
+        self.lazy_segment_audio = lazy_segment_audio;
+        let segments = result?;
+
+        progress_callback("Segmentation complete", 99.0, Some(&format!("Optimized to {} final segments", segments.len())));
+        Ok((segments, duration_seconds))
+    }
+
+    // Condition audio at the VAD rate and find its final segments, with their audio unless
+    // it's fetched lazily. With `cache_file`, boundaries are reused from and stored in
+    // the segment cache for that file.
+    fn segment_audio<F, S>(&mut self, content: Vec<i16>, cache_file: Option<&str>, progress_callback: &F, segment_callback: &S, timings: &mut PipelineTimings) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        S: Fn(SegmentUpdate),
+    {
+        let content = self.condition_audio(content, progress_callback)?;
+        self.check_cancelled()?;
+
+        // Reuse segment boundaries from an earlier identical run when available. Cached
+        // runs have no probabilities, so VAD always runs when those are wanted.
+        let cache_key = self.segment_cache.as_ref()
+            .zip(cache_file)
+            .and_then(|(cache, file_path)| cache.key(file_path, &self.config_fingerprint()).ok());
+        let cached = match (&self.segment_cache, &cache_key) {
+            (Some(cache), Some(key)) if self.probability_resolution_ms.is_none() => cache.load(key),
+            _ => None,
+        };
+        self.speech_probabilities = None;
+
+        let merged_segments = match cached {
+            Some(cached) => {
+                debug_log!("Using {} cached segments for {}", cached.len(), cache_file.unwrap_or_default());
+                progress_callback("Loaded cached segments", 90.0, Some(&format!("Found {} segments from a previous run", cached.len())));
+                cached.iter().map(|segment| segment.to_audio_segment(&content)).collect()
+            }
+            None => {
+                let (segments, probabilities) = self.detect_segments(&content, progress_callback, segment_callback, timings)?;
+                self.speech_probabilities = probabilities;
+                if let (Some(cache), Some(key)) = (&self.segment_cache, &cache_key) {
+                    if let Err(e) = cache.store(key, &segments) {
+                        debug_log!("Failed to write segment cache: {}", e);
+                    }
+                }
+                segments
+            }
+        };
+
+        let merged_segments = self.finish_segments(merged_segments, &content);
+        self.check_segments(&merged_segments);
+        segment_callback(SegmentUpdate::Merged(&merged_segments));
+        Ok(merged_segments)
+    }
+
+    // Mono audio of a file at the VAD rate, from the decoded-audio cache when an earlier
+    // run already decoded the unchanged file with the same settings. With `keep_original`
+    // the audio at the file's own rate comes along, decoded again if the cache was used.
+    fn load_audio<F>(&self, file_path: &str, keep_original: bool, progress_callback: &F, timings: &mut PipelineTimings) -> Result<LoadedAudio, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        // Target the VAD rate: 16kHz unless 8kHz was requested
+        let target_rate_hz = u32::from(self.sample_rate);
+
+        // Reuse audio decoded by an earlier run on the unchanged file when available
+        let decoded_key = self.decoded_cache.as_ref()
+            .and_then(|_| DecodedAudioKey::for_file(file_path, self.decode_fingerprint()).ok());
+        let cached_audio = match (&self.decoded_cache, &decoded_key) {
+            (Some(cache), Some(key)) => cache.lock().unwrap().get(key),
+            _ => None,
+        };
+
+        let mut original = None;
+        let content = match cached_audio {
+            Some(content) => {
+                debug_log!("Using {} cached samples at {} Hz for {}", content.len(), target_rate_hz, file_path);
+                progress_callback("Loaded decoded audio", 45.0, Some(&format!("{} samples at {} Hz from an earlier run", content.len(), target_rate_hz)));
+                if keep_original {
+                    // Only audio at the VAD rate is cached
+                    let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+                    original = Some(self.decode_audio_symphonia_with_progress(file_path, &dummy_callback)?);
+                }
+                content
+            }
+            None => {
+                // Decode audio using Symphonia
+                progress_callback("Decoding audio file", 10.0, Some("Reading and decoding audio data"));
+                let stage_start = Instant::now();
+                let (mut content, original_sample_rate) = self.decode_audio_symphonia_with_progress(file_path, progress_callback)?;
+                timings.decode_ms = elapsed_ms(stage_start);
+                self.check_cancelled()?;
+
+                debug_log!("Processing audio file: {} Hz -> {} Hz", original_sample_rate, target_rate_hz);
+                progress_callback("Audio decoded", 25.0, Some(&format!("{} samples at {} Hz", content.len(), original_sample_rate)));
+
+                if content.is_empty() {
+                    return Err(DecodeError::NoSamples.into());
+                }
+
+                debug_log!("Original audio: {} samples at {} Hz", content.len(), original_sample_rate);
+                if keep_original {
+                    original = Some((content.clone(), original_sample_rate));
+                }
+
+                // Resample to the VAD rate if needed
+                let stage_start = Instant::now();
+                if original_sample_rate != target_rate_hz {
+                    progress_callback("Resampling audio", 35.0, Some(&format!("Converting from {} Hz to {} Hz", original_sample_rate, target_rate_hz)));
+                    content = self.resample_audio(&content, original_sample_rate, target_rate_hz)?;
+                    debug_log!("Resampled to: {} samples at {} Hz", content.len(), target_rate_hz);
+                    progress_callback("Audio resampled", 45.0, Some(&format!("{} samples at {} Hz", content.len(), target_rate_hz)));
+                }
+                timings.resample_ms = elapsed_ms(stage_start);
+
+                if let (Some(cache), Some(key)) = (&self.decoded_cache, decoded_key) {
+                    cache.lock().unwrap().insert(key, content.clone());
+                }
+                content
+            }
+        };
+        Ok((content, original))
+    }
+
+    /// Samples `start_sample..end_sample` of a file exactly as the pipeline handed them to
+    /// VAD and transcription: decoded, mixed and resampled to the VAD rate (16kHz unless
+    /// 8kHz was set), then normalized and equalized as configured. Segment sample indices
+    /// count at that rate, so the processor has to be set up like the run that reported
+    /// them. extract_segment_from_file instead cuts by time at the file's own rate, which
+    /// can land a fraction of a VAD sample off.
+    pub fn extract_vad_segment(&mut self, file_path: &str, start_sample: i64, end_sample: i64) -> Result<Vec<i16>, Box<dyn std::error::Error>> {
+        if start_sample < 0 || end_sample <= start_sample {
+            return Err(format!("Invalid segment samples {}..{}", start_sample, end_sample).into());
+        }
+
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        let mut timings = PipelineTimings::default();
+        let (content, _) = self.load_audio(file_path, false, &dummy_callback, &mut timings)?;
+        let content = self.condition_audio(content, &dummy_callback)?;
+        if start_sample >= content.len() as i64 {
+            return Err(format!("Segment starts at sample {}, past the end of the audio ({} samples at {} Hz)",
+                start_sample, content.len(), u32::from(self.sample_rate)).into());
+        }
+        Ok(self.extract_audio_chunk(&content, start_sample, end_sample))
+    }
+
+    /// Min/max pairs of a file's mono audio at the VAD rate, one for each of `buckets`
+    /// equal stretches of it, for drawing a waveform without sending every sample.
+    /// Decodes through the decoded-audio cache like a full run.
+    pub fn waveform_peaks<F>(&self, file_path: &str, buckets: usize, progress_callback: F) -> Result<Vec<(i16, i16)>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        if buckets == 0 {
+            return Err("A waveform needs at least one bucket".into());
+        }
+
+        let mut timings = PipelineTimings::default();
+        let (content, _) = self.load_audio(file_path, false, &progress_callback, &mut timings)?;
+        self.check_cancelled()?;
+
+        progress_callback("Computing waveform", 90.0, Some(&format!("{} samples into {} buckets", content.len(), buckets.min(content.len()))));
+        Ok(dsp::min_max_peaks(&content, buckets))
+    }
+
+    /// Cut a file into `chunk_seconds` pieces on a fixed grid instead of running VAD,
+    /// each starting `overlap_seconds` before the previous one ends. Chunk k starts at
+    /// exactly k * (chunk_seconds - overlap_seconds); the last one ends with the file.
+    pub fn chunk_audio_fixed<F>(&mut self, file_path: &str, chunk_seconds: f64, overlap_seconds: f64, progress_callback: F) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        if !chunk_seconds.is_finite() || chunk_seconds < 1.0 {
+            return Err(format!("Chunk length must be at least 1 second, got {}", chunk_seconds).into());
+        }
+        if !overlap_seconds.is_finite() || overlap_seconds < 0.0 || overlap_seconds >= chunk_seconds {
+            return Err(format!("Chunk overlap must be at least 0 and shorter than the chunk, got {}", overlap_seconds).into());
+        }
+
+        let mut timings = PipelineTimings::default();
+        let (content, _) = self.load_audio(file_path, false, &progress_callback, &mut timings)?;
+        self.check_cancelled()?;
+
+        let sample_rate = u32::from(self.sample_rate) as f64;
+        let step_seconds = chunk_seconds - overlap_seconds;
+        let mut segments = Vec::new();
+        for index in 0.. {
+            let start_time_seconds = index as f64 * step_seconds;
+            let start_sample = (start_time_seconds * sample_rate).round() as i64;
+            let end_sample = (((start_time_seconds + chunk_seconds) * sample_rate).round() as i64).min(content.len() as i64);
+            segments.push(AudioSegment {
+                start_sample,
+                end_sample,
+                start_time_seconds,
+                end_time_seconds: end_sample as f64 / sample_rate,
+                audio_data: self.extract_audio_chunk(&content, start_sample, end_sample),
+                audio_base64: String::new(),
+                snr_db: None,
+                overlap_seconds: if index == 0 { 0.0 } else { overlap_seconds },
+                channel: None,
+            });
+            if end_sample >= content.len() as i64 {
+                break;
+            }
+        }
+        debug_log!("Cut {} into {} fixed chunks of {:.1}s ({:.1}s overlap)", file_path, segments.len(), chunk_seconds, overlap_seconds);
+        progress_callback("Chunking audio", 90.0, Some(&format!("{} chunks of {:.1}s", segments.len(), chunk_seconds)));
+
+        self.encode_segments(&mut segments, u32::from(self.sample_rate), &progress_callback);
+        progress_callback("Chunking complete", 99.0, Some(&format!("Cut into {} chunks", segments.len())));
+        Ok(segments)
+    }
+
+    /// Segment each of the first two channels on its own, for interviews with one speaker
+    /// per channel. Segments carry their channel and are sorted by start time. Only the
+    /// first two channels of files with more are used; a mono file is its own channel 0.
+    /// Decoded audio and segments are not cached in this mode.
+    pub fn process_audio_file_stereo<F, S>(&mut self, file_path: &str, progress_callback: F, segment_callback: S) -> Result<Vec<AudioSegment>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        S: Fn(SegmentUpdate),
+    {
+        self.warnings.get_mut().unwrap().clear();
+        progress_callback("Decoding audio file", 10.0, Some("Reading and decoding audio data per channel"));
+        let (channels, original_sample_rate, total_channels) = self.decode_channels(file_path, &progress_callback, 2)?;
+        self.check_cancelled()?;
+        if total_channels > channels.len() {
+            let warning = format!("Only the first {} of {} channels are segmented", channels.len(), total_channels);
+            progress_callback("Audio decoded", 25.0, Some(&warning));
+            self.warn(WarningKind::ExtraChannels, warning);
+        }
+
+        let target_rate_hz = u32::from(self.sample_rate);
+        let keep_original = self.preserve_original_rate && !self.lazy_segment_audio;
+        let channel_count = channels.len();
+        let mut timings = PipelineTimings::default();
+        let mut segments = Vec::new();
+        for (channel, original) in channels.into_iter().enumerate() {
+            debug_log!("Segmenting channel {} of {}", channel + 1, channel_count);
+            progress_callback("Segmenting channel", 30.0, Some(&format!("Channel {} of {}", channel + 1, channel_count)));
+            let content = if original_sample_rate != target_rate_hz {
+                self.resample_audio(&original, original_sample_rate, target_rate_hz)?
+            } else {
+                original.clone()
+            };
+            let content = self.condition_audio(content, &progress_callback)?;
+            self.check_cancelled()?;
+
+            // Per-channel indices would collide, so only the final set is reported
+            let (detected, _) = self.detect_segments(&content, &progress_callback, &|_| {}, &mut timings)?;
+            let mut channel_segments = self.finish_segments(detected, &content);
+            for segment in channel_segments.iter_mut() {
+                segment.channel = Some(channel as u8);
+            }
+            if keep_original {
+                self.cut_original_audio(&mut channel_segments, &original, original_sample_rate);
+            }
+            segments.extend(channel_segments);
+        }
+
+        segments.sort_by(|a, b| a.start_time_seconds.total_cmp(&b.start_time_seconds));
+        self.check_segments(&segments);
+        segment_callback(SegmentUpdate::Merged(&segments));
+        let output_rate = if keep_original { original_sample_rate } else { target_rate_hz };
+        self.encode_segments(&mut segments, output_rate, &progress_callback);
+
+        progress_callback("Segmentation complete", 99.0, Some(&format!("Found {} segments across {} channels", segments.len(), channel_count)));
+        Ok(segments)
+    }
+
+    // Decode up to `max_channels` channels of a file without mixing them
+    fn decode_channels<F>(&self, file_path: &str, progress_callback: &F, max_channels: usize) -> Result<DecodedChannels, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        if let Ok(mut file) = File::open(file_path) {
+            self.check_wav_data_length(&mut file);
+        }
+        let mut channel_samples: Vec<Vec<i16>> = Vec::new();
+        let (mut decoded, mut clipped) = (0usize, 0usize);
+        let (sample_rate, channels) = self.decode_packets(Self::open_audio_file(file_path)?, progress_callback, self.dither, |interleaved, channels| {
+            decoded += interleaved.len();
+            clipped += dsp::count_clipped(interleaved);
+            let channels = channels.max(1);
+            channel_samples.resize_with(channels.min(max_channels), Vec::new);
+            for frame in interleaved.chunks_exact(channels) {
+                for (channel, samples) in channel_samples.iter_mut().enumerate() {
+                    samples.push(frame[channel]);
+                }
+            }
+        })?;
+
+        if channel_samples.iter().all(|samples| samples.is_empty()) {
+            return Err(DecodeError::NoSamples.into());
+        }
+        self.check_clipping(clipped, decoded, progress_callback);
+        Ok((channel_samples, sample_rate, channels))
+    }
+
+    // Normalization and EQ, applied to audio at the VAD rate before detection
+    fn condition_audio<F>(&mut self, mut content: Vec<i16>, progress_callback: &F) -> Result<Vec<i16>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        if let Some(target_peak_dbfs) = self.normalize_peak_dbfs {
+            progress_callback("Normalizing audio", 46.0, Some(&format!("Peak to {:.1} dBFS", target_peak_dbfs)));
+            self.normalize_samples(&mut content, target_peak_dbfs);
+        }
+
+        if !self.eq_bands.is_empty() {
+            progress_callback("Applying EQ", 47.0, Some(&format!("{} band(s)", self.eq_bands.len())));
+            content = dsp::apply_eq(&content, &self.eq_bands, u32::from(self.sample_rate))?;
+        }
+        Ok(content)
+    }
+
+    // Trimming, SNR and splitting for merged segments of `content`
+    fn finish_segments(&self, mut segments: Vec<AudioSegment>, content: &[i16]) -> Vec<AudioSegment> {
+        let sample_rate = u32::from(self.sample_rate) as f64;
+        if self.trim_silence {
+            for segment in segments.iter_mut() {
+                self.trim_segment(segment, sample_rate);
+            }
+        }
+        Self::estimate_snr(&mut segments, content, sample_rate);
+
+        if let Some(max_segment_seconds) = self.max_segment_seconds {
+            let max_samples = (max_segment_seconds * sample_rate) as usize;
+            let overlap_samples = (self.split_overlap_seconds * sample_rate) as usize;
+            let count = segments.len();
+            segments = segments.into_iter()
+                .flat_map(|segment| self.split_segment(segment, content, max_samples, overlap_samples, sample_rate))
+                .collect();
+            if segments.len() > count {
+                debug_log!("Split long segments: {} -> {} segments (max {:.1}s, overlap {:.2}s)",
+                    count, segments.len(), max_segment_seconds, self.split_overlap_seconds);
+            }
+        }
+        segments
+    }
+
+    // Encode each final segment as WAV for browser playback, or drop its audio
+    // entirely when the frontend will fetch it on demand
+    fn encode_segments<F>(&self, segments: &mut [AudioSegment], sample_rate: u32, progress_callback: &F)
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        let total_segments = segments.len();
+        for (index, segment) in segments.iter_mut().enumerate() {
+            if self.lazy_segment_audio {
+                segment.audio_data = Vec::new();
+                continue;
+            }
+            segment.audio_base64 = self.samples_to_wav_base64(&segment.audio_data, sample_rate)
+                .unwrap_or_else(|_| String::new());
+
+            let encoded = index + 1;
+            if encoded % 10 == 0 || encoded == total_segments {
+                let encode_progress = 95.0 + (encoded as f64 / total_segments as f64) * 4.0;
+                progress_callback("Encoding segments", encode_progress, Some(&format!("Encoded {}/{} segments", encoded, total_segments)));
+            }
+        }
+    }
+
+    // Replace the audio of segments found at the VAD rate with the same stretch of
+    // `original`, the file's audio at `original_rate`. Indices and times stay as detected.
+    fn cut_original_audio(&self, segments: &mut [AudioSegment], original: &[i16], original_rate: u32) {
+        let rate = original_rate as f64;
+        for segment in segments.iter_mut() {
+            let end = ((segment.end_time_seconds * rate).round() as usize).min(original.len());
+            let start = ((segment.start_time_seconds.max(0.0) * rate).round() as usize).min(end);
+            segment.audio_data = original[start..end].to_vec();
+        }
+    }
+
+    /// Silero VAD for the configured rate, with its chunk size: 512 samples at 16kHz,
+    /// 256 at 8kHz
+    pub fn voice_activity_detector(&self) -> Result<(VoiceActivityDetector, usize), Box<dyn std::error::Error>> {
+        let chunk_size = self.vad_chunk_size()?;
+        let vad = VoiceActivityDetector::builder()
+            .sample_rate(self.sample_rate)
+            .chunk_size(chunk_size)
+            .build()
+            .map_err(|e| format!("Failed to create VAD: {}", e))?;
+        Ok((vad, chunk_size))
+    }
+
+    /// Sample rate audio is resampled to before VAD
+    pub fn vad_sample_rate(&self) -> u32 {
+        u32::from(self.sample_rate)
+    }
+
+    // Samples per VAD chunk at the configured rate. The loudness gate uses the same
+    // chunks, so both backends feed speech_runs alike.
+    fn vad_chunk_size(&self) -> Result<usize, String> {
+        utils::vad_chunk_size_for(self.vad_sample_rate())
+    }
+
+    // Run VAD over mono audio at the VAD rate and turn the result into merged, validated
+    // segments, along with the downsampled speech probabilities if they were requested
+    fn detect_segments<F, S>(&self, content: &[i16], progress_callback: &F, segment_callback: &S, timings: &mut PipelineTimings) -> Result<DetectedSegments, Box<dyn std::error::Error>>
+    where
+        F: Fn(&str, f64, Option<&str>),
+        S: Fn(SegmentUpdate),
+    {
+        // Digital silence can't contain speech; don't spin up VAD for it
+        if content.iter().all(|sample| sample.unsigned_abs() <= 1) {
+            debug_log!("Audio is silent throughout, skipping voice activity detection");
+            progress_callback("Speech detection complete", 75.0, Some("Audio is silent throughout"));
+            let probabilities = self.probability_resolution_ms
+                .map(|resolution_ms| vec![0.0; Self::probability_steps(content.len(), u32::from(self.sample_rate), resolution_ms)]);
+            return Ok((Vec::new(), probabilities));
+        }
+
+        debug_log!("Running voice activity detection ({:?})...", self.vad_backend);
+        let stage_start = Instant::now();
+
+        // Silero VAD through the voice_activity_detector crate, or the loudness gate with
+        // the same chunk size so both feed speech_runs alike
+        let (mut vad, chunk_size) = match self.vad_backend {
+            VadBackend::Silero => {
+                progress_callback("Running voice activity detection", 50.0, Some("Initializing AI voice detection"));
+                let (vad, chunk_size) = self.voice_activity_detector()?;
+                (Some(vad), chunk_size)
+            }
+            VadBackend::Rms => {
+                progress_callback("Running voice activity detection", 50.0, Some("Using loudness-based detection"));
+                (None, self.vad_chunk_size()?)
+            }
+        };
+
+        // Only run VAD where there is sound at all when leading/trailing silence is skipped
+        let sample_rate_f64 = u32::from(self.sample_rate) as f64; // Audio is at the VAD rate after resampling
+        let span = if self.skip_silence {
+            Self::audible_span(content, (SILENCE_MARGIN_SECONDS * sample_rate_f64) as usize)
+        } else {
+            0..content.len()
+        };
+        if span.len() < content.len() {
+            debug_log!("Skipping silence: running VAD on {:.2}s-{:.2}s of {:.2}s",
+                span.start as f64 / sample_rate_f64, span.end as f64 / sample_rate_f64, content.len() as f64 / sample_rate_f64);
+        }
+
+        // Raise the bar for noisy recordings, judging by their quietest stretches
+        let mut vad_config = self.vad_config;
+        let mut rms_threshold_dbfs = RMS_THRESHOLD_DBFS;
+        if self.noise_floor_threshold {
+            let frame_len = NOISE_FLOOR_FRAME_MS * usize::from(self.sample_rate) / 1000;
+            if let Some(noise_floor) = dsp::noise_floor_dbfs(&content[span.clone()], frame_len, NOISE_FLOOR_FRACTION) {
+                vad_config.threshold = self.threshold_for_noise_floor(noise_floor);
+                rms_threshold_dbfs = rms_threshold_dbfs.max(noise_floor + RMS_NOISE_MARGIN_DB);
+                let details = match self.vad_backend {
+                    VadBackend::Silero => format!("Noise floor {:.1} dBFS, speech threshold {:.2}", noise_floor, vad_config.threshold),
+                    VadBackend::Rms => format!("Noise floor {:.1} dBFS, loudness threshold {:.1} dBFS", noise_floor, rms_threshold_dbfs),
+                };
+                debug_log!("{}", details);
+                progress_callback("Estimated noise floor", 55.0, Some(&details));
+            }
+        }
+
+        // Segments are finalized while VAD is still running so they can be reported early
+        progress_callback("Analyzing speech patterns", 60.0, Some("Processing audio chunks for speech detection"));
+        let mut segments = Vec::new();
+        // Stopping early looks like the end of the audio to speech_runs, so the run it
+        // flushes then is dropped
+        let probabilities: Box<dyn Iterator<Item = f32>> = match &mut vad {
+            Some(vad) => Box::new(content[span.clone()].iter().cloned().predict(vad).map(|(_, probability)| probability)),
+            None => {
+                let speech = self.rms_vad(&content[span.clone()], RMS_FRAME_MS, rms_threshold_dbfs, RMS_HANG_MS);
+                // Like Silero, only whole chunks get a verdict
+                Box::new((0..span.len() / chunk_size).map(move |chunk| {
+                    let chunk_range = chunk * chunk_size..(chunk + 1) * chunk_size;
+                    let is_speech = speech.iter().any(|range| range.start < chunk_range.end && chunk_range.start < range.end);
+                    if is_speech { 1.0 } else { 0.0 }
+                }))
+            }
+        };
+        // Chunks skipped as silence count as certain non-speech
+        let collect_probabilities = self.probability_resolution_ms.is_some();
+        let mut chunk_probabilities = if collect_probabilities { vec![0.0; span.start / chunk_size] } else { Vec::new() };
+        let probabilities = probabilities
+            .take_while(|_| !self.is_cancelled())
+            .inspect(|&probability| if collect_probabilities { chunk_probabilities.push(probability) });
+        let chunk_count = self.speech_runs(probabilities, vad_config, chunk_size, span.len(), |run_start, run_end| {
+            if self.is_cancelled() {
+                return;
+            }
+            let (speech_start, speech_end) = (span.start + run_start, span.start + run_end);
+            // Extract audio data for this segment
+            let segment_audio = content[speech_start..speech_end].to_vec();
+            if segment_audio.is_empty() {
+                return;
+            }
+
+            let segment = AudioSegment {
+                start_sample: speech_start as i64,
+                end_sample: speech_end as i64,
+                start_time_seconds: speech_start as f64 / sample_rate_f64,
+                end_time_seconds: speech_end as f64 / sample_rate_f64,
+                audio_data: segment_audio,
+                audio_base64: String::new(), // Filled in once segments are final
+                snr_db: None,
+                overlap_seconds: 0.0,
+                channel: None,
+            };
+            segment_callback(SegmentUpdate::Found(segments.len(), &segment));
+            segments.push(segment);
+        });
+        self.check_cancelled()?;
+        progress_callback("Speech detection complete", 75.0, Some(&format!("Processed {} audio chunks", chunk_count)));
+        timings.vad_ms = elapsed_ms(stage_start);
+        let stage_start = Instant::now();
+
+        debug_log!("Generated {} initial speech segments using {:?} VAD", segments.len(), self.vad_backend);
+        progress_callback("Optimizing segments", 90.0, Some(&format!("Found {} initial segments", segments.len())));
+
+        // Merge segments that are close together
+        let mut merged_segments = self.merge_close_segments_with_progress(segments, content, self.merge_gap_seconds, self.min_segment_duration_seconds, progress_callback);
+        self.validate_segments(&mut merged_segments, content.len(), sample_rate_f64)?;
+        timings.merge_ms = elapsed_ms(stage_start);
+        
+        debug_log!("After merging close segments: {} final segments", merged_segments.len());
+
+        let probabilities = self.probability_resolution_ms.map(|resolution_ms| {
+            Self::downsample_probabilities(&chunk_probabilities, chunk_size, content.len(), u32::from(self.sample_rate), resolution_ms)
+        });
+        Ok((merged_segments, probabilities))
+    }
+
+    // Number of `resolution_ms` steps needed to cover `total_samples`
+    fn probability_steps(total_samples: usize, sample_rate: u32, resolution_ms: u32) -> usize {
+        let step_samples = (sample_rate as u64 * resolution_ms as u64).div_ceil(1000).max(1) as usize;
+        total_samples.div_ceil(step_samples)
+    }
+
+    // Average per-chunk probabilities into one value per `resolution_ms` step. Steps
+    // shorter than a chunk take the value of the chunk they fall in; steps past the last
+    // whole chunk are 0.
+    fn downsample_probabilities(chunk_probabilities: &[f32], chunk_size: usize, total_samples: usize, sample_rate: u32, resolution_ms: u32) -> Vec<f32> {
+        let step_samples = sample_rate as f64 * resolution_ms as f64 / 1000.0;
+        (0..Self::probability_steps(total_samples, sample_rate, resolution_ms))
+            .map(|step| {
+                let first = (step as f64 * step_samples / chunk_size as f64) as usize;
+                let last = (((step + 1) as f64 * step_samples / chunk_size as f64).ceil() as usize).max(first + 1);
+                let chunks = &chunk_probabilities[first.min(chunk_probabilities.len())..last.min(chunk_probabilities.len())];
+                if chunks.is_empty() {
+                    0.0
+                } else {
+                    chunks.iter().sum::<f32>() / chunks.len() as f32
+                }
+            })
+            .collect()
+    }
+
+    // Settings that change the decoded audio itself, as opposed to how it is segmented
+    fn decode_fingerprint(&self) -> String {
+        format!("rate={:?};dual_mono={};channel_mix={:?};dither={}", self.sample_rate, self.detect_dual_mono, self.channel_mix, self.dither)
+    }
+
+    // Identifies every setting that affects segmentation, for cache keys
+    fn config_fingerprint(&self) -> String {
+        format!("rate={:?};backend={:?};vad={:?};merge_gap={};min_duration={};dual_mono={};channel_mix={:?};adaptive={:?};noise_floor={};eq={:?};skip_silence={};normalize={:?};dither={}",
+            self.sample_rate, self.vad_backend, self.vad_config, self.merge_gap_seconds, self.min_segment_duration_seconds, self.detect_dual_mono, self.channel_mix, self.adaptive_threshold, self.noise_floor_threshold, self.eq_bands, self.skip_silence, self.normalize_peak_dbfs, self.dither)
+    }
+
+    // Shrink a segment to the range trim_silence keeps, moving its boundaries with it
+    fn trim_segment(&self, segment: &mut AudioSegment, sample_rate: f64) {
+        let keep = self.trim_silence(&segment.audio_data, TRIM_THRESHOLD_RMS, TRIM_MAX_MS);
+        if keep.len() == segment.audio_data.len() {
+            return;
+        }
+
+        let start_sample = segment.start_sample + keep.start as i64;
+        segment.end_sample = segment.start_sample + keep.end as i64;
+        segment.start_sample = start_sample;
+        segment.start_time_seconds = segment.start_sample as f64 / sample_rate;
+        segment.end_time_seconds = segment.end_sample as f64 / sample_rate;
+        segment.audio_data.truncate(keep.end);
+        segment.audio_data.drain(..keep.start);
+    }
+
+    // Cut a segment longer than `max_samples` into parts of at most that length, each
+    // starting `overlap_samples` before the previous one ends. Every cut is placed in
+    // the quietest frame of the last quarter of the allowed length, so it is less
+    // likely to fall mid-word. Parts keep the segment's SNR.
+    fn split_segment(&self, segment: AudioSegment, content: &[i16], max_samples: usize, overlap_samples: usize, sample_rate: f64) -> Vec<AudioSegment> {
+        let segment_start = (segment.start_sample.max(0) as usize).min(content.len());
+        let segment_end = (segment.end_sample.max(0) as usize).clamp(segment_start, content.len());
+        if segment_end - segment_start <= max_samples {
+            return vec![segment];
+        }
+
+        let frame = (SPLIT_FRAME_MS * usize::from(self.sample_rate) / 1000).max(1);
+        let search = ((max_samples as f64 * SPLIT_SEARCH_FRACTION) as usize).max(frame);
+        let mut bounds = Vec::new();
+        let mut start = segment_start;
+        while segment_end - start > max_samples {
+            // Window ends where the part would reach its maximum length
+            let window_end = start + max_samples;
+            let mut cut = window_end;
+            let mut quietest = f64::MAX;
+            let mut frame_start = window_end - search;
+            while frame_start + frame <= window_end {
+                let energy = dsp::mean_square(&[&content[frame_start..frame_start + frame]]).unwrap_or(0.0);
+                if energy < quietest {
+                    quietest = energy;
+                    cut = frame_start + frame / 2;
+                }
+                frame_start += frame;
+            }
+            bounds.push((start, cut));
+            start = cut - overlap_samples;
+        }
+        bounds.push((start, segment_end));
+
+        bounds.iter().enumerate()
+            .map(|(index, &(start, end))| AudioSegment {
+                start_sample: start as i64,
+                end_sample: end as i64,
+                start_time_seconds: start as f64 / sample_rate,
+                end_time_seconds: end as f64 / sample_rate,
+                audio_data: content[start..end].to_vec(),
+                audio_base64: String::new(),
+                snr_db: segment.snr_db,
+                overlap_seconds: if index == 0 { 0.0 } else { overlap_samples as f64 / sample_rate },
+                channel: segment.channel,
+            })
+            .collect()
+    }
+
+    /// Range of `samples` left after walking inward from both ends over 10ms frames
+    /// whose RMS is below `threshold_rms`. At most `max_trim_ms` is removed from each
+    /// end, and a segment that is silent throughout keeps its middle.
+    pub fn trim_silence(&self, samples: &[i16], threshold_rms: f64, max_trim_ms: u32) -> std::ops::Range<usize> {
+        let samples_per_ms = usize::from(self.sample_rate) / 1000;
+        let frame = TRIM_FRAME_MS * samples_per_ms;
+        let max_trim = (max_trim_ms as usize * samples_per_ms).min(samples.len() / 2);
+        let is_quiet = |frame: &[i16]| dsp::mean_square(&[frame]).is_none_or(|square| square.sqrt() < threshold_rms);
+
+        let mut start = 0;
+        while start + frame <= max_trim && is_quiet(&samples[start..start + frame]) {
+            start += frame;
+        }
+        let mut end = samples.len();
+        while end > start + frame && samples.len() - end + frame <= max_trim && is_quiet(&samples[end - frame..end]) {
+            end -= frame;
+        }
+        start..end
+    }
+
+    // Estimate each segment's SNR from its level against the non-speech audio around it,
+    // or against all non-speech audio in the file when it has too little of its own
+    fn estimate_snr(segments: &mut [AudioSegment], content: &[i16], sample_rate: f64) {
+        let context = (SNR_CONTEXT_SECONDS * sample_rate) as usize;
+        let min_noise = (SNR_MIN_NOISE_SECONDS * sample_rate) as usize;
+        let bounds: Vec<(usize, usize)> = segments.iter()
+            .map(|segment| {
+                let start = (segment.start_sample.max(0) as usize).min(content.len());
+                (start, (segment.end_sample.max(0) as usize).clamp(start, content.len()))
+            })
+            .collect();
+
+        // Non-speech gaps before the first segment, between segments and after the last
+        let mut gaps = Vec::with_capacity(bounds.len() + 1);
+        let mut previous_end = 0;
+        for &(start, end) in &bounds {
+            gaps.push(previous_end.min(start)..start);
+            previous_end = end;
+        }
+        gaps.push(previous_end..content.len());
+        let all_noise: Vec<&[i16]> = gaps.iter().map(|gap| &content[gap.clone()]).collect();
+        let file_noise = dsp::mean_square(&all_noise);
+
+        for (index, segment) in segments.iter_mut().enumerate() {
+            let (start, end) = bounds[index];
+            let (before, after) = (&gaps[index], &gaps[index + 1]);
+            let surrounding = [
+                &content[before.start.max(start.saturating_sub(context))..start],
+                &content[end..after.end.min(end + context)],
+            ];
+            let surrounding_len = surrounding[0].len() + surrounding[1].len();
+            let noise = if surrounding_len >= min_noise { dsp::mean_square(&surrounding) } else { file_noise };
+
+            segment.snr_db = dsp::mean_square(&[&content[start..end]])
+                .zip(noise)
+                .map(|(speech, noise)| dsp::amplitude_to_dbfs(speech.sqrt()) - dsp::amplitude_to_dbfs(noise.sqrt()));
+        }
+    }
+
+    // Range from the first to the last sample above the silence level, widened by
+    // `margin` samples on each side. Empty when the whole file is silent.
+    fn audible_span(content: &[i16], margin: usize) -> std::ops::Range<usize> {
+        let is_audible = |&sample: &i16| sample.unsigned_abs() > SILENCE_LEVEL;
+        match (content.iter().position(is_audible), content.iter().rposition(is_audible)) {
+            (Some(first), Some(last)) => first.saturating_sub(margin)..(last + 1 + margin).min(content.len()),
+            _ => 0..0,
+        }
+    }
+
+    // VAD threshold for audio with the given noise floor: the configured threshold for
+    // clean audio, rising linearly to MAX_ADAPTIVE_THRESHOLD for very noisy recordings
+    fn threshold_for_noise_floor(&self, noise_floor_dbfs: f64) -> f32 {
+        let noisiness = ((noise_floor_dbfs - CLEAN_NOISE_FLOOR_DBFS) / (NOISY_NOISE_FLOOR_DBFS - CLEAN_NOISE_FLOOR_DBFS)).clamp(0.0, 1.0) as f32;
+        let threshold = self.vad_config.threshold;
+        threshold + (MAX_ADAPTIVE_THRESHOLD - threshold).max(0.0) * noisiness
+    }
+
+    /// Run tracker for `chunk_size`-sample chunks at the VAD rate, using this processor's VAD settings
+    pub fn speech_run_tracker(&self, chunk_size: usize) -> SpeechRunTracker {
+        self.run_tracker(self.vad_config, chunk_size)
+    }
+
+    fn run_tracker(&self, vad_config: VadConfig, chunk_size: usize) -> SpeechRunTracker {
+        let samples_per_ms = usize::from(self.sample_rate) / 1000;
+        SpeechRunTracker {
+            vad_config,
+            adaptive_threshold: self.adaptive_threshold,
+            min_speech_chunks: (vad_config.min_speech_ms as usize * samples_per_ms).div_ceil(chunk_size),
+            noise_floor: 0.0,
+            highest_threshold: vad_config.threshold,
+            current_run: None,
+            chunk_count: 0,
+        }
+    }
+
+    // Group chunk probabilities into speech runs (see SpeechRunTracker), reporting each
+    // as a sample range as soon as no later chunk can extend it. `vad_config` is this
+    // processor's, possibly with a threshold raised for noise. Returns the number of
+    // chunks seen.
+    fn speech_runs<I, R>(&self, probabilities: I, vad_config: VadConfig, chunk_size: usize, total_samples: usize, mut on_run: R) -> usize
+    where
+        I: Iterator<Item = f32>,
+        R: FnMut(usize, usize),
+    {
+        let mut tracker = self.run_tracker(vad_config, chunk_size);
+        for probability in probabilities {
+            if let Some(run) = tracker.push(probability) {
+                on_run(run.start * chunk_size, run.end * chunk_size);
+            }
+        }
+
+        // A run reaching the last chunk also covers the partial chunk VAD never saw
+        if let Some(run) = tracker.finish() {
+            let end_sample = if run.end >= tracker.chunk_count() { total_samples } else { run.end * chunk_size };
+            on_run((run.start * chunk_size).min(total_samples), end_sample);
+        }
+
+        if self.adaptive_threshold.is_some() {
+            debug_log!("Adaptive VAD threshold ranged from {:.2} to {:.2}", vad_config.threshold, tracker.highest_threshold);
+        }
+        tracker.chunk_count()
+    }
+
+    /// Loudness-gate speech detection: sample ranges where `frame_ms` frames reach
+    /// `threshold_dbfs` RMS, each held open for `hang_ms` after the last loud frame
+    pub fn rms_vad(&self, samples: &[i16], frame_ms: u32, threshold_dbfs: f64, hang_ms: u32) -> Vec<std::ops::Range<usize>> {
+        let mut gate = self.rms_gate(frame_ms, threshold_dbfs, hang_ms);
+        gate.push(samples);
+        gate.finish();
+        gate.ranges
+    }
+
+    fn rms_gate(&self, frame_ms: u32, threshold_dbfs: f64, hang_ms: u32) -> RmsGate {
+        let samples_per_ms = usize::from(self.sample_rate) / 1000;
+        RmsGate {
+            frame: (frame_ms as usize * samples_per_ms).max(1),
+            hang: hang_ms as usize * samples_per_ms,
+            threshold: dsp::dbfs_to_amplitude(threshold_dbfs),
+            partial: Vec::new(),
+            position: 0,
+            open: None,
+            ranges: Vec::new(),
+        }
+    }
+
+    // Merge segments that are close together (within max_gap_seconds)
+    fn merge_close_segments(&self, mut segments: Vec<AudioSegment>, content: &[i16], max_gap_seconds: f64) -> Vec<AudioSegment> {
+        let dummy_callback = |_step: &str, _progress: f64, _details: Option<&str>| {};
+        self.merge_close_segments_with_progress(segments, content, max_gap_seconds, 0.0, &dummy_callback)
+    }
+
+    fn merge_close_segments_with_progress<F>(&self, mut segments: Vec<AudioSegment>, content: &[i16], max_gap_seconds: f64, min_duration_seconds: f64, progress_callback: &F) -> Vec<AudioSegment>
+    where
+        F: Fn(&str, f64, Option<&str>),
+    {
+        // Drop blips like coughs first, so they neither become segments of their own
+        // nor split the gap between the longer segments around them
+        let detected = segments.len();
+        segments.retain(|segment| segment.end_time_seconds - segment.start_time_seconds >= min_duration_seconds);
+        if segments.len() < detected {
+            debug_log!("Dropped {} segments shorter than {:.2}s", detected - segments.len(), min_duration_seconds);
+        }
+
+        if segments.is_empty() {
+            return segments;
+        }
+
+        // Sort segments by start time to ensure proper order
+        segments.sort_by(|a, b| a.start_time_seconds.partial_cmp(&b.start_time_seconds).unwrap());
+
+        let mut merged = Vec::new();
+        let mut segments_iter = segments.into_iter();
+        let mut current = segments_iter.next().unwrap();
+        let mut processed = 0;
+        let total_segments = segments_iter.len() + 1;
+
+        for next in segments_iter {
+            processed += 1;
+            
+            // Update progress during merging
+            if processed % 10 == 0 || processed == total_segments - 1 {
+                let merge_progress = 90.0 + (processed as f64 / total_segments as f64) * 5.0;
+                progress_callback("Merging segments", merge_progress, Some(&format!("Processed {}/{} segments", processed, total_segments)));
+            }
+            
+            let gap = next.start_time_seconds - current.end_time_seconds;
+            
+            if gap <= max_gap_seconds {
+                // Merge current and next segments
+                debug_log!("Merging segments: {:.2}s-{:.2}s with {:.2}s-{:.2}s (gap: {:.2}s)", 
+                    current.start_time_seconds, current.end_time_seconds,
+                    next.start_time_seconds, next.end_time_seconds, gap);
+                
+                // A segment inside the current one must not cut it short
+                let merged_start = current.start_sample;
+                let merged_end = current.end_sample.max(next.end_sample);
+                let merged_start_time = current.start_time_seconds;
+                let merged_end_time = current.end_time_seconds.max(next.end_time_seconds);
+                
+                // Audio of both segments with the gap between them filled in
+                let merged_audio = self.stitch_merged_audio(&current, &next, content);
+                
+                debug_log!("Merged segment: {:.2}s-{:.2}s, samples: {}-{}, audio length: {} samples", 
+                    merged_start_time, merged_end_time, merged_start, merged_end, merged_audio.len());
+                
+                current = AudioSegment {
+                    start_sample: merged_start,
+                    end_sample: merged_end,
+                    start_time_seconds: merged_start_time,
+                    end_time_seconds: merged_end_time,
+                    audio_data: merged_audio,
+                    audio_base64: String::new(),
+                    snr_db: None,
+                    overlap_seconds: 0.0,
+                    channel: current.channel,
+                };
+            } else {
+                // Gap is too large, keep current segment and move to next
+                debug_log!("Gap too large ({:.2}s > {:.2}s), not merging segments: {:.2}s-{:.2}s and {:.2}s-{:.2}s", 
+                    gap, max_gap_seconds,
+                    current.start_time_seconds, current.end_time_seconds,
+                    next.start_time_seconds, next.end_time_seconds);
+                merged.push(current);
+                current = next;
+            }
+        }
+        
+        // Don't forget to add the last segment
+        merged.push(current);
+        
+        merged
+    }
+
+    // `current`'s audio followed by the source audio in the gap and whatever of `next`'s
+    // audio lies past `current`. Each segment's audio is taken to start at its
+    // start_sample. With crossfading, the joins blend between the segments' audio and
+    // the source, which changes nothing where the segment audio was cut from `content`.
+    fn stitch_merged_audio(&self, current: &AudioSegment, next: &AudioSegment, content: &[i16]) -> Vec<i16> {
+        let fade_len = if self.crossfade_merges {
+            (u32::from(self.sample_rate) * MERGE_CROSSFADE_MS / 1000) as usize
+        } else {
+            0
+        };
+        let position = |sample: i64| (sample.max(0) as usize).min(content.len());
+
+        // Fade the end of the current audio out into the source that follows it
+        let mut merged = current.audio_data.clone();
+        let current_end = position(current.start_sample + merged.len() as i64);
+        let fade_out = fade_len.min(merged.len()).min(current_end);
+        if fade_out > 0 {
+            let mut tail = content[current_end - fade_out..current_end].to_vec();
+            let tail_start = merged.len() - fade_out;
+            dsp::crossfade(&merged[tail_start..], &mut tail);
+            merged[tail_start..].copy_from_slice(&tail);
+        }
+
+        let next_start = position(next.start_sample);
+        if next_start > current_end {
+            merged.extend_from_slice(&content[current_end..next_start]);
+        }
+
+        // Skip what overlaps the current segment and fade the rest in from the source
+        let skip = current_end.saturating_sub(next_start).min(next.audio_data.len());
+        let mut rest = next.audio_data[skip..].to_vec();
+        let rest_start = next_start + skip;
+        let fade_in = fade_len.min(rest.len()).min(content.len().saturating_sub(rest_start));
+        if fade_in > 0 {
+            dsp::crossfade(&content[rest_start..rest_start + fade_in], &mut rest[..fade_in]);
+        }
+        merged.extend(rest);
+        merged
+    }
+
+    // Check that segments are sorted, non-overlapping and inside the decoded audio.
+    // Segments running past the end are clamped with a warning; ordering problems are errors.
+    fn validate_segments(&self, segments: &mut [AudioSegment], total_samples: usize, sample_rate: f64) -> Result<(), Box<dyn std::error::Error>> {
+        let total_samples = total_samples as i64;
+        let mut previous_end = 0i64;
+
+        for (index, segment) in segments.iter_mut().enumerate() {
+            if segment.end_sample > total_samples {
+                debug_log!("Warning: segment {} ends at sample {} past the end of the audio ({}), clamping",
+                    index, segment.end_sample, total_samples);
+                segment.end_sample = total_samples;
+                segment.end_time_seconds = total_samples as f64 / sample_rate;
+            }
+
+            if segment.start_sample >= segment.end_sample {
+                return Err(format!("Segment {} is empty or inverted: samples {}-{}",
+                    index, segment.start_sample, segment.end_sample).into());
+            }
+
+            if segment.start_sample < previous_end {
+                return Err(format!("Segment {} starts at sample {} before the previous segment ends at {}",
+                    index, segment.start_sample, previous_end).into());
+            }
+
+            previous_end = segment.end_sample;
+        }
+
+        Ok(())
+    }
+
+    // Convert audio samples to base64-encoded WAV for browser playback (always little-endian RIFF)
+    fn samples_to_wav_base64(&self, samples: &[i16], sample_rate: u32) -> Result<String, Box<dyn std::error::Error>> {
+        let wav_data = self.samples_to_wav_bytes(samples, sample_rate)?;
+        Ok(utils::encode_wav_b64(&wav_data))
+    }
+
+    /// Scale samples by a constant gain so the peak lands at `target_peak_dbfs`,
+    /// clamping to the i16 range. Digital silence is left untouched.
+    pub fn normalize_samples(&mut self, samples: &mut [i16], target_peak_dbfs: f32) {
+        let peak = samples.iter().map(|sample| sample.unsigned_abs()).max().unwrap_or(0);
+        if peak == 0 {
+            return;
+        }
+
+        let gain = dsp::dbfs_to_amplitude(target_peak_dbfs as f64) / peak as f64;
+        debug_log!("Normalizing peak from {:.1} dBFS to {:.1} dBFS (gain {:.2}x)",
+            dsp::amplitude_to_dbfs(peak as f64), target_peak_dbfs, gain);
+        for sample in samples.iter_mut() {
+            *sample = (*sample as f64 * gain).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+        }
+    }
+
+    pub fn extract_audio_chunk(&self, content: &[i16], start_sample: i64, end_sample: i64) -> Vec<i16> {
+        let start_idx = start_sample.max(0) as usize;
+        let end_idx = (end_sample as usize).min(content.len());
+        content[start_idx..end_idx].to_vec()
+    }
+
+    /// Simple resampling by linear interpolation, with no anti-aliasing.
+    /// Only used when the rates already match; see `sinc_resample`
     fn simple_resample(&self, input: &[i16], from_rate: u32, to_rate: u32) -> Vec<i16> {
         if from_rate == to_rate {
             return input.to_vec(); // No resampling needed
         }
         
+        if input.is_empty() || from_rate == 0 || to_rate == 0 {
+            return Vec::new();
+        }
+        
+        // Same length rule as sinc_resample, in both directions
         let ratio = from_rate as f64 / to_rate as f64;
-        let output_len = (input.len() as f64 / ratio) as usize;
+        let output_len = (input.len() as f64 * to_rate as f64 / from_rate as f64).round() as usize;
         let mut output = Vec::with_capacity(output_len);
         
         for i in 0..output_len {
             let src_pos = i as f64 * ratio;
-            let src_index = src_pos as usize;
-            
-            if src_index >= input.len() {
-                break;
-            }
+            // Upsampling rounds up to positions just past the last input sample, which hold it
+            let src_index = (src_pos as usize).min(input.len() - 1);
             
             // Linear interpolation between samples
             if src_index + 1 < input.len() {
@@ -462,46 +3068,152 @@ impl AudioProcessor {
         output
     }
     
+    /// Mono sinc resampler taking `chunk_size` input frames per call, with the filter
+    /// settings used for whole files
+    pub fn sinc_resampler(from_rate: u32, to_rate: u32, chunk_size: usize) -> Result<SincFixedIn<f32>, Box<dyn std::error::Error>> {
+        use rubato::{SincInterpolationParameters, SincInterpolationType, WindowFunction};
+
+        let params = SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Cubic,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        Ok(SincFixedIn::<f32>::new(to_rate as f64 / from_rate as f64, 1.0, params, chunk_size, 1)?)
+    }
+
+    /// Band-limited resampling for arbitrary rate ratios
+    fn sinc_resample(&self, input: &[i16], from_rate: u32, to_rate: u32) -> Result<Vec<i16>, Box<dyn std::error::Error>> {
+        const CHUNK_SIZE: usize = 4096;
+        
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let ratio = to_rate as f64 / from_rate as f64;
+        let mut resampler = Self::sinc_resampler(from_rate, to_rate, CHUNK_SIZE)?;
+        
+        let samples: Vec<f32> = input.iter().map(|&s| s as f32 / 32768.0).collect();
+        let expected_len = (input.len() as f64 * ratio).round() as usize;
+        // The filter delays its output, so skip that many leading samples and keep
+        // flushing until the tail of the input has made it through
+        let delay = resampler.output_delay();
+        let mut resampled: Vec<f32> = Vec::with_capacity(expected_len + delay + CHUNK_SIZE);
+        
+        let mut chunks = samples.chunks_exact(CHUNK_SIZE);
+        for chunk in &mut chunks {
+            resampled.extend_from_slice(&resampler.process(&[chunk], None)?[0]);
+        }
+        let remainder = chunks.remainder();
+        if !remainder.is_empty() {
+            resampled.extend_from_slice(&resampler.process_partial(Some(&[remainder]), None)?[0]);
+        }
+        while resampled.len() < expected_len + delay {
+            resampled.extend_from_slice(&resampler.process_partial::<&[f32]>(None, None)?[0]);
+        }
+        
+        Ok(resampled[delay..delay + expected_len]
+            .iter()
+            .map(|&s| (s * 32768.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+            .collect())
+    }
+    
     /// Public wrapper for resampling audio
     pub fn resample_audio(&self, input: &[i16], from_rate: u32, to_rate: u32) -> Result<Vec<i16>, Box<dyn std::error::Error>> {
-        Ok(self.simple_resample(input, from_rate, to_rate))
+        if from_rate == to_rate {
+            return Ok(self.simple_resample(input, from_rate, to_rate));
+        }
+        if from_rate == 0 || to_rate == 0 {
+            return Err(format!("Cannot resample from {} Hz to {} Hz", from_rate, to_rate).into());
+        }
+        self.sinc_resample(input, from_rate, to_rate)
+    }
+    
+    /// Convert audio samples to WAV bytes (without base64 encoding)
+    /// Output is always a little-endian RIFF file, regardless of the source's byte order
+    pub fn samples_to_wav_bytes(&self, samples: &[i16], sample_rate: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut wav_data = Vec::with_capacity(44 + samples.len() * 2);
+        Self::write_wav(&mut wav_data, samples, sample_rate)?;
+        Ok(wav_data)
+    }
+
+    /// Write samples as a 16-bit mono WAV file, streaming them through a buffered
+    /// writer instead of building the whole file in memory first
+    pub fn write_wav_to_path(&self, samples: &[i16], sample_rate: u32, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        Self::write_wav(&mut writer, samples, sample_rate)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_wav<W: Write>(out: &mut W, samples: &[i16], sample_rate: u32) -> std::io::Result<()> {
+        wav::write_header(out, sample_rate, wav::FORMAT_PCM, 16, samples.len())?;
+        for &sample in samples {
+            out.write_all(&sample.to_le_bytes())?;
+        }
+        Ok(())
+    }
+    
+    /// Mono WAV from float samples in [-1, 1]: 16- or 24-bit PCM, or 32-bit IEEE float
+    pub fn samples_f32_to_wav_bytes(&self, samples: &[f32], sample_rate: u32, bit_depth: WavBitDepth) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let format_tag = if bit_depth == WavBitDepth::Float32 { wav::FORMAT_IEEE_FLOAT } else { wav::FORMAT_PCM };
+        let mut wav_data = Vec::with_capacity(44 + samples.len() * usize::from(bit_depth.bits() / 8));
+        wav::write_header(&mut wav_data, sample_rate, format_tag, bit_depth.bits(), samples.len())?;
+
+        for &sample in samples {
+            let sample = sample.clamp(-1.0, 1.0);
+            match bit_depth {
+                WavBitDepth::Pcm16 => wav_data.extend_from_slice(&((sample * i16::MAX as f32).round() as i16).to_le_bytes()),
+                WavBitDepth::Pcm24 => wav_data.extend_from_slice(&((sample * 8_388_607.0).round() as i32).to_le_bytes()[..3]),
+                WavBitDepth::Float32 => wav_data.extend_from_slice(&sample.to_le_bytes()),
+            }
+        }
+
+        Ok(wav_data)
     }
-    
-    /// Convert audio samples to WAV bytes (without base64 encoding)
-    pub fn samples_to_wav_bytes(&self, samples: &[i16], sample_rate: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let mut wav_data = Vec::new();
-        
-        // WAV header
-        let num_samples = samples.len() as u32;
-        let byte_rate = sample_rate * 2; // 16-bit mono
-        let data_size = num_samples * 2;
-        let file_size = 36 + data_size;
-        
-        // RIFF header
-        wav_data.extend_from_slice(b"RIFF");
-        wav_data.extend_from_slice(&file_size.to_le_bytes());
-        wav_data.extend_from_slice(b"WAVE");
+
+    /// Encode mono samples as a constant-bitrate MP3. `bitrate` is in kbps and must be
+    /// one of the standard MPEG rates (8-320); LAME rejects sample rates it can't encode
+    pub fn samples_to_mp3_bytes(&self, samples: &[i16], sample_rate: u32, bitrate: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, MonoPcm, Quality};
         
-        // fmt chunk
-        wav_data.extend_from_slice(b"fmt ");
-        wav_data.extend_from_slice(&16u32.to_le_bytes()); // chunk size
-        wav_data.extend_from_slice(&1u16.to_le_bytes()); // PCM format
-        wav_data.extend_from_slice(&1u16.to_le_bytes()); // mono
-        wav_data.extend_from_slice(&sample_rate.to_le_bytes());
-        wav_data.extend_from_slice(&byte_rate.to_le_bytes());
-        wav_data.extend_from_slice(&2u16.to_le_bytes()); // block align
-        wav_data.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+        let brate = match bitrate {
+            8 => Bitrate::Kbps8,
+            16 => Bitrate::Kbps16,
+            24 => Bitrate::Kbps24,
+            32 => Bitrate::Kbps32,
+            40 => Bitrate::Kbps40,
+            48 => Bitrate::Kbps48,
+            64 => Bitrate::Kbps64,
+            80 => Bitrate::Kbps80,
+            96 => Bitrate::Kbps96,
+            112 => Bitrate::Kbps112,
+            128 => Bitrate::Kbps128,
+            160 => Bitrate::Kbps160,
+            192 => Bitrate::Kbps192,
+            224 => Bitrate::Kbps224,
+            256 => Bitrate::Kbps256,
+            320 => Bitrate::Kbps320,
+            _ => return Err(format!("Unsupported MP3 bitrate: {} kbps", bitrate).into()),
+        };
         
-        // data chunk
-        wav_data.extend_from_slice(b"data");
-        wav_data.extend_from_slice(&data_size.to_le_bytes());
+        let mut builder = Builder::new().ok_or("Failed to create MP3 encoder")?;
+        builder.set_num_channels(1).map_err(|e| format!("Failed to set MP3 channels: {}", e))?;
+        builder.set_sample_rate(sample_rate).map_err(|e| format!("Unsupported MP3 sample rate {} Hz: {}", sample_rate, e))?;
+        builder.set_brate(brate).map_err(|e| format!("Failed to set MP3 bitrate: {}", e))?;
+        builder.set_quality(Quality::Good).map_err(|e| format!("Failed to set MP3 quality: {}", e))?;
+        let mut encoder = builder.build().map_err(|e| format!("Failed to initialize MP3 encoder at {} Hz: {}", sample_rate, e))?;
         
-        // audio data
-        for &sample in samples {
-            wav_data.extend_from_slice(&sample.to_le_bytes());
-        }
+        let mut mp3_data = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(samples.len()));
+        encoder.encode_to_vec(MonoPcm(samples), &mut mp3_data)
+            .map_err(|e| format!("Failed to encode MP3: {}", e))?;
+        // The final flush can emit up to one full frame
+        mp3_data.reserve(7200);
+        encoder.flush_to_vec::<FlushNoGap>(&mut mp3_data)
+            .map_err(|e| format!("Failed to flush MP3 encoder: {}", e))?;
         
-        Ok(wav_data)
+        Ok(mp3_data)
     }
     
     // Extract a segment from an audio file by time range
@@ -511,6 +3223,12 @@ impl AudioProcessor {
         start_time_seconds: f64,
         end_time_seconds: f64,
     ) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>> {
+        // Try to seek straight to the range first, only decoding what we need
+        match self.extract_segment_seeking(file_path, start_time_seconds, end_time_seconds) {
+            Ok(result) => return Ok(result),
+            Err(e) => debug_log!("Seeking extraction unavailable ({}), falling back to full decode", e),
+        }
+
         // Decode the full audio file
         let (audio_samples, sample_rate) = self.decode_audio_symphonia(file_path.to_str().unwrap())?;
         
@@ -531,4 +3249,956 @@ impl AudioProcessor {
         
         Ok((segment_samples, sample_rate))
     }
+
+    /// Float samples of a time range, from a full decode at the file's own rate
+    pub fn extract_segment_from_file_f32(
+        &self,
+        file_path: &std::path::Path,
+        start_time_seconds: f64,
+        end_time_seconds: f64,
+    ) -> Result<(Vec<f32>, u32), Box<dyn std::error::Error>> {
+        let (audio_samples, sample_rate) = self.decode_audio_f32(file_path.to_str().ok_or("Invalid file path")?)?;
+
+        let start_sample = ((start_time_seconds * sample_rate as f64) as usize).min(audio_samples.len());
+        let end_sample = ((end_time_seconds * sample_rate as f64) as usize).min(audio_samples.len());
+        if start_sample >= end_sample {
+            return Err("Invalid time range: start time is after end time".into());
+        }
+
+        Ok((audio_samples[start_sample..end_sample].to_vec(), sample_rate))
+    }
+
+    // Seek close to the requested start and decode only the packets covering the range.
+    // Packet timestamps are used to trim the decoded audio so the result lines up
+    // sample-for-sample with slicing a full decode, including any codec decode delay.
+    fn extract_segment_seeking(
+        &self,
+        file_path: &std::path::Path,
+        start_time_seconds: f64,
+        end_time_seconds: f64,
+    ) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>> {
+        let (mut format, track, _) = Self::open_audio_file(file_path.to_str().ok_or("Invalid file path")?)?;
+
+        let track_id = track.id;
+        let sample_rate = track.codec_params.sample_rate.ok_or("Unknown sample rate")?;
+        let time_base = track.codec_params.time_base.ok_or("Unknown time base")?;
+        let channels = track.codec_params.channels.unwrap_or_default().count();
+
+        let start_frame = (start_time_seconds * sample_rate as f64) as u64;
+        let end_frame = (end_time_seconds * sample_rate as f64) as u64;
+        if start_frame >= end_frame {
+            return Err("Invalid time range: start time is after end time".into());
+        }
+
+        let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+        // Start decoding a little early: codecs like MP3 rebuild the first packets after a
+        // seek from state (overlap, bit reservoir) that only earlier packets provide
+        let seek_seconds = (start_time_seconds - SEEK_PREROLL_SECONDS).max(0.0);
+        format.seek(SeekMode::Accurate, SeekTo::Time { time: Time::from(seek_seconds), track_id: Some(track_id) })?;
+        decoder.reset();
+
+        // Convert a packet timestamp into a frame index at the track's sample rate
+        let ts_to_frame = |ts: u64| {
+            let time = time_base.calc_time(ts);
+            ((time.seconds as f64 + time.frac) * sample_rate as f64).round() as u64
+        };
+
+        let weights = surround_weights(track.codec_params.channels);
+        let mut samples = Vec::new();
+        let mut packet_samples = Vec::new();
+        let mut converter = PcmConverter::new(false);
+        let mut dual_mono = self.dual_mono_detector(i16::MAX as f64);
+        let mut first_packet = true;
+
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let packet_start = ts_to_frame(packet.ts());
+            if packet_start >= end_frame {
+                break;
+            }
+            if first_packet && packet_start > start_frame {
+                return Err("Seek landed after the requested start time".into());
+            }
+            first_packet = false;
+
+            match decoder.decode(&packet) {
+                Ok(audio_buf) => {
+                    packet_samples.clear();
+                    self.downmix_into(converter.convert(audio_buf), channels, weights.as_deref(), &mut dual_mono, &mut packet_samples);
+
+                    // Keep only the part of this packet that falls inside the range
+                    let skip = start_frame.saturating_sub(packet_start) as usize;
+                    let take = (end_frame - packet_start) as usize;
+                    let skip = skip.min(packet_samples.len());
+                    let take = take.min(packet_samples.len());
+                    if skip < take {
+                        samples.extend_from_slice(&packet_samples[skip..take]);
+                    }
+                }
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if samples.is_empty() {
+            return Err("No audio samples decoded in the requested range".into());
+        }
+
+        Ok((samples, sample_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_temp_path;
+
+    fn adaptive_runs(probabilities: &[f32]) -> Vec<std::ops::Range<usize>> {
+        let mut processor = AudioProcessor::new();
+        processor.set_adaptive_threshold(Some(AdaptiveThreshold::default()));
+        let mut tracker = processor.speech_run_tracker(512);
+        let mut runs: Vec<_> = probabilities.iter().filter_map(|&p| tracker.push(p)).collect();
+        runs.extend(tracker.finish());
+        runs
+    }
+
+    #[test]
+    fn adaptive_threshold_keeps_long_speech_run() {
+        // Three minutes of uninterrupted speech after a few seconds of quiet
+        let mut probabilities = vec![0.05; 100];
+        probabilities.extend(std::iter::repeat_n(0.9, 5600));
+        probabilities.extend(std::iter::repeat_n(0.05, 100));
+
+        let runs = adaptive_runs(&probabilities);
+        assert_eq!(runs.len(), 1, "speech run was cut: {:?}", runs);
+        assert!(runs[0].start <= 100 && runs[0].end >= 5700, "run {:?} doesn't cover the speech", runs[0]);
+    }
+
+    #[test]
+    fn adaptive_threshold_follows_rising_noise() {
+        // Quiet room, then noise the model rates well above the fixed threshold, then speech
+        let mut probabilities = vec![0.1; 300];
+        probabilities.extend((0..200).map(|i| 0.1 + 0.5 * i as f32 / 200.0));
+        probabilities.extend(std::iter::repeat_n(0.6, 1000));
+        probabilities.extend(std::iter::repeat_n(0.99, 30));
+        probabilities.extend(std::iter::repeat_n(0.6, 100));
+
+        let runs = adaptive_runs(&probabilities);
+        assert_eq!(runs.len(), 1, "noise was heard as speech: {:?}", runs);
+        assert!(runs[0].start >= 1400 && runs[0].end <= 1600, "run {:?} isn't the speech burst", runs[0]);
+    }
+
+    fn tone(sample_rate: u32, frequency: f64, amplitude: f64, len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| (amplitude * (2.0 * std::f64::consts::PI * frequency * i as f64 / sample_rate as f64).sin()).round() as i16)
+            .collect()
+    }
+
+    fn interleave(left: &[i16], right: &[i16]) -> Vec<i16> {
+        left.iter().zip(right).flat_map(|(&l, &r)| [l, r]).collect()
+    }
+
+    #[test]
+    fn dual_mono_is_decided_once_per_file() {
+        let processor = AudioProcessor::new();
+        let left = tone(48000, 440.0, 8000.0, 2 * DUAL_MONO_PROBE_FRAMES);
+        let right = tone(48000, 660.0, 8000.0, 2 * DUAL_MONO_PROBE_FRAMES);
+
+        // Identical channels: once the probe has seen enough signal, buffers take the first channel as-is
+        let mut dual_mono = processor.dual_mono_detector(i16::MAX as f64);
+        let mut out = Vec::new();
+        processor.downmix_into(&interleave(&left, &left), 2, None, &mut dual_mono, &mut out);
+        assert_eq!(dual_mono.decision, Some(true));
+        out.clear();
+        processor.downmix_into(&interleave(&left, &left), 2, None, &mut dual_mono, &mut out);
+        assert_eq!(out, left);
+
+        // Channels that differ from the start rule it out for the rest of the file, even
+        // if later buffers happen to match
+        let mut dual_mono = processor.dual_mono_detector(i16::MAX as f64);
+        let mut out = Vec::new();
+        processor.downmix_into(&interleave(&left[..1024], &right[..1024]), 2, None, &mut dual_mono, &mut out);
+        assert_eq!(dual_mono.decision, Some(false));
+        let averaged: Vec<i16> = left[..1024].iter().zip(&right[..1024]).map(|(&l, &r)| ((l as i32 + r as i32) / 2) as i16).collect();
+        assert_eq!(out, averaged);
+        processor.downmix_into(&interleave(&left, &left), 2, None, &mut dual_mono, &mut out);
+        assert_eq!(dual_mono.probed_frames, 0);
+    }
+
+    #[test]
+    fn dual_mono_probe_ignores_silence() {
+        let processor = AudioProcessor::new();
+        let mut dual_mono = processor.dual_mono_detector(i16::MAX as f64);
+        let silence = vec![0i16; 2 * DUAL_MONO_PROBE_FRAMES];
+        assert_eq!(dual_mono.identical_frames(&silence, 2), DUAL_MONO_PROBE_FRAMES);
+        assert_eq!(dual_mono.decision, None);
+
+        // Disabled detection never takes the fast path
+        let mut processor = AudioProcessor::new();
+        processor.set_detect_dual_mono(false);
+        let left = tone(48000, 440.0, 8000.0, 2 * DUAL_MONO_PROBE_FRAMES);
+        assert_eq!(processor.dual_mono_detector(i16::MAX as f64).identical_frames(&interleave(&left, &left), 2), 0);
+    }
+
+    fn rms(samples: &[i16]) -> f64 {
+        (samples.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn resample_44k1_to_16k_has_expected_length() {
+        let processor = AudioProcessor::new();
+        let output = processor.resample_audio(&tone(44100, 440.0, 8000.0, 44100), 44100, 16000).unwrap();
+        assert_eq!(output.len(), 16000);
+
+        // Odd ratios and inputs shorter than the resampler's chunk size
+        assert_eq!(processor.resample_audio(&tone(22050, 440.0, 8000.0, 1234), 22050, 16000).unwrap().len(), 895);
+        assert_eq!(processor.resample_audio(&[5, 6, 7], 48000, 16000).unwrap().len(), 1);
+        assert!(processor.resample_audio(&[], 48000, 16000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn resample_removes_tones_above_output_nyquist() {
+        let processor = AudioProcessor::new();
+        // 10 kHz is above the 8 kHz Nyquist of 16 kHz output: linear interpolation folds it
+        // down to 6 kHz, the band-limited resampler should filter it out
+        let input = tone(44100, 10000.0, 16000.0, 44100);
+        let linear = processor.simple_resample(&input, 44100, 16000);
+        let band_limited = processor.resample_audio(&input, 44100, 16000).unwrap();
+
+        // Skip the filter's ramp-up and ramp-down at either end
+        let (aliased, filtered) = (rms(&linear[1000..15000]), rms(&band_limited[1000..15000]));
+        assert!(filtered < rms(&input) / 100.0, "{:.1} rms left of the 10 kHz tone", filtered);
+        assert!(filtered < aliased / 100.0, "band-limited {:.1} rms vs linear {:.1}", filtered, aliased);
+
+        // An in-band tone passes at full level
+        let passed = processor.resample_audio(&tone(44100, 1000.0, 16000.0, 44100), 44100, 16000).unwrap();
+        let level_db = 20.0 * (rms(&passed[1000..15000]) / rms(&tone(16000, 1000.0, 16000.0, 16000))).log10();
+        assert!(level_db.abs() < 0.5, "1 kHz tone changed by {:.2} dB", level_db);
+    }
+
+    #[test]
+    fn simple_resample_length_matches_ratio() {
+        let processor = AudioProcessor::new();
+        let input: Vec<i16> = (0..8001).map(|i| (i % 100) as i16 * 100).collect();
+        for (from_rate, to_rate) in [(8000, 16000), (48000, 16000), (44100, 16000), (16000, 44100)] {
+            let ideal = input.len() as f64 * to_rate as f64 / from_rate as f64;
+            let output = processor.simple_resample(&input, from_rate, to_rate);
+            assert!((output.len() as f64 - ideal).abs() <= 1.0, "{} -> {} Hz gave {} samples, ideal {:.1}", from_rate, to_rate, output.len(), ideal);
+        }
+    }
+
+    #[test]
+    fn simple_resample_upsampling_holds_last_sample() {
+        let processor = AudioProcessor::new();
+        // Past the last input sample there is nothing to interpolate towards, so it is held
+        assert_eq!(processor.simple_resample(&[0, 100, 200], 8000, 16000), vec![0, 50, 100, 150, 200, 200]);
+    }
+
+    #[test]
+    fn normalize_brings_quiet_sine_to_target_peak() {
+        let mut processor = AudioProcessor::new();
+        // -40 dBFS peak
+        let mut samples = tone(16000, 440.0, 327.68, 16000);
+        processor.normalize_samples(&mut samples, -3.0);
+
+        let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        let peak_dbfs = dsp::amplitude_to_dbfs(peak as f64);
+        assert!((peak_dbfs + 3.0).abs() < 0.1, "peak at {:.2} dBFS", peak_dbfs);
+    }
+
+    #[test]
+    fn normalize_never_clips() {
+        let mut processor = AudioProcessor::new();
+        // Already at full scale in both directions: a 0 dBFS target leaves it as is
+        let mut full_scale = vec![i16::MIN, i16::MAX, 0];
+        processor.normalize_samples(&mut full_scale, 0.0);
+        assert_eq!(full_scale, vec![i16::MIN, i16::MAX, 0]);
+
+        let mut silent = vec![0i16; 4];
+        processor.normalize_samples(&mut silent, -3.0);
+        assert_eq!(silent, vec![0; 4]);
+        assert!(processor.set_normalization(Some(1.0)).is_err(), "targets above 0 dBFS would clip");
+    }
+
+    fn segment_at(start_sample: i64, audio_data: Vec<i16>) -> AudioSegment {
+        let end_sample = start_sample + audio_data.len() as i64;
+        AudioSegment {
+            start_sample,
+            end_sample,
+            start_time_seconds: start_sample as f64 / 16000.0,
+            end_time_seconds: end_sample as f64 / 16000.0,
+            audio_data,
+            audio_base64: String::new(),
+            snr_db: None,
+            overlap_seconds: 0.0,
+            channel: None,
+        }
+    }
+
+    #[test]
+    fn trim_silence_stops_at_max_trim() {
+        let processor = AudioProcessor::new();
+        // 100ms of silence, half a second of tone, then a full second of silence
+        let mut samples = vec![0i16; 1600];
+        samples.extend(tone(16000, 440.0, 16000.0, 8000));
+        samples.extend(vec![0i16; 16000]);
+
+        let keep = processor.trim_silence(&samples, TRIM_THRESHOLD_RMS, TRIM_MAX_MS);
+        assert_eq!(keep.start, 1600, "leading silence shorter than the limit goes entirely");
+        let max_trim = TRIM_MAX_MS as usize * 16;
+        assert_eq!(keep.end, samples.len() - max_trim, "trailing silence is only cut up to the limit");
+        assert!(rms(&samples[keep.start..keep.start + 8000]) > 10000.0, "the tone survives");
+    }
+
+    #[test]
+    fn trim_segment_moves_boundaries_with_the_audio() {
+        let processor = AudioProcessor::new();
+        let mut samples = vec![0i16; 1600];
+        samples.extend(tone(16000, 440.0, 16000.0, 8000));
+        let mut segment = segment_at(16000, samples.clone());
+        processor.trim_segment(&mut segment, 16000.0);
+
+        assert_eq!((segment.start_sample, segment.end_sample), (17600, 16000 + samples.len() as i64));
+        assert!((segment.start_time_seconds - 1.1).abs() < 1e-9);
+        assert_eq!(segment.audio_data, samples[1600..]);
+    }
+
+    #[test]
+    fn trim_segment_leaves_segments_without_edge_silence() {
+        let processor = AudioProcessor::new();
+        let samples = tone(16000, 440.0, 16000.0, 8000);
+        let mut segment = segment_at(16000, samples.clone());
+        processor.trim_segment(&mut segment, 16000.0);
+
+        assert_eq!((segment.start_sample, segment.end_sample), (16000, 24000));
+        assert_eq!((segment.start_time_seconds, segment.end_time_seconds), (1.0, 1.5));
+        assert_eq!(segment.audio_data, samples);
+    }
+
+    fn mono_buffer<S: symphonia::core::sample::Sample>(samples: &[S]) -> symphonia::core::audio::AudioBuffer<S> {
+        use symphonia::core::audio::{AudioBuffer, Signal, SignalSpec};
+        let mut buffer = AudioBuffer::new(samples.len() as u64, SignalSpec::new(16000, Channels::FRONT_LEFT));
+        buffer.render_reserved(Some(samples.len()));
+        buffer.chan_mut(0).copy_from_slice(samples);
+        buffer
+    }
+
+    #[test]
+    fn pcm_converter_clamps_and_scales_float() {
+        use symphonia::core::audio::AsAudioBufferRef;
+        let floats = mono_buffer(&[0.0f32, 0.5, -1.0, 1.5, -2.0, 1e-5, f32::NAN]);
+        let mut converter = PcmConverter::new(false);
+        // Out-of-range samples clamp to full scale instead of wrapping, NaN becomes silence
+        assert_eq!(converter.convert(floats.as_audio_buffer_ref()), &[0, 16384, -32767, 32767, -32767, 0, 0]);
+
+        let mut dithered = PcmConverter::new(true);
+        let output = dithered.convert(floats.as_audio_buffer_ref());
+        assert!(output[2] <= -32766 && output[3] >= 32766 && output[6] == 0, "{:?}", output);
+    }
+
+    #[test]
+    fn pcm_converter_rounds_24_and_32_bit() {
+        use symphonia::core::audio::AsAudioBufferRef;
+        use symphonia::core::sample::i24;
+        let mut converter = PcmConverter::new(false);
+
+        // 256 steps of a 24-bit source make one i16 step; halves round away from zero
+        let s24: Vec<i24> = [0, 127, 128, -129, 8_388_607, -8_388_608, 256 * 1000 + 200].into_iter().map(i24::from).collect();
+        assert_eq!(converter.convert(mono_buffer(&s24).as_audio_buffer_ref()), &[0, 0, 1, -1, 32767, -32768, 1001]);
+
+        let s32 = mono_buffer(&[0i32, 0x7FFF, 0x8000, i32::MAX, i32::MIN, 1000 << 16]);
+        assert_eq!(converter.convert(s32.as_audio_buffer_ref()), &[0, 0, 1, 32767, -32768, 1000]);
+
+        let s16 = mono_buffer(&[0i16, -1, i16::MIN, i16::MAX]);
+        assert_eq!(converter.convert(s16.as_audio_buffer_ref()), &[0, -1, i16::MIN, i16::MAX]);
+    }
+
+    // EBML element with an 8-byte size field
+    fn ebml(id: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut element = id.to_vec();
+        element.push(0x01);
+        element.extend_from_slice(&(data.len() as u64).to_be_bytes()[1..]);
+        element.extend_from_slice(data);
+        element
+    }
+
+    // Minimal single-track WebM file holding `packets` of `codec`, one SimpleBlock each
+    fn webm(codec: &str, sample_rate: f64, packets: &[Vec<u8>], ms_per_packet: usize) -> Vec<u8> {
+        let header = [
+            ebml(&[0x42, 0x86], &[1]), ebml(&[0x42, 0xF7], &[1]), ebml(&[0x42, 0xF2], &[4]), ebml(&[0x42, 0xF3], &[8]),
+            ebml(&[0x42, 0x82], b"webm"), ebml(&[0x42, 0x87], &[2]), ebml(&[0x42, 0x85], &[2]),
+        ].concat();
+        let info = ebml(&[0x15, 0x49, 0xA9, 0x66], &ebml(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]));
+        let audio = [ebml(&[0xB5], &sample_rate.to_be_bytes()), ebml(&[0x9F], &[1])].concat();
+        let track = [ebml(&[0xD7], &[1]), ebml(&[0x73, 0xC5], &[1]), ebml(&[0x83], &[2]), ebml(&[0x86], codec.as_bytes()), ebml(&[0xE1], &audio)].concat();
+        let tracks = ebml(&[0x16, 0x54, 0xAE, 0x6B], &ebml(&[0xAE], &track));
+
+        let mut cluster = ebml(&[0xE7], &[0]);
+        for (index, packet) in packets.iter().enumerate() {
+            let mut block = vec![0x81];
+            block.extend_from_slice(&((index * ms_per_packet) as i16).to_be_bytes());
+            block.push(0x80);
+            block.extend_from_slice(packet);
+            cluster.extend(ebml(&[0xA3], &block));
+        }
+        let cluster = ebml(&[0x1F, 0x43, 0xB6, 0x75], &cluster);
+        [ebml(&[0x1A, 0x45, 0xDF, 0xA3], &header), ebml(&[0x18, 0x53, 0x80, 0x67], &[info, tracks, cluster].concat())].concat()
+    }
+
+    // Split an MPEG-1 layer III stream into its frames
+    fn mp3_frames(mut mp3: &[u8]) -> Vec<Vec<u8>> {
+        const BITRATES_KBPS: [usize; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+        let mut frames = Vec::new();
+        while mp3.len() > 4 && mp3[0] == 0xFF {
+            let sample_rate = [44100, 48000, 32000][((mp3[2] >> 2) & 3) as usize];
+            let len = (144 * BITRATES_KBPS[(mp3[2] >> 4) as usize] * 1000 / sample_rate + ((mp3[2] >> 1) & 1) as usize).min(mp3.len());
+            frames.push(mp3[..len].to_vec());
+            mp3 = &mp3[len..];
+        }
+        frames
+    }
+
+    #[test]
+    fn sniff_format_detects_containers() {
+        let wav = AudioProcessor::new().samples_to_wav_bytes(&[0; 16], 16000).unwrap();
+        assert_eq!(sniff_format(&wav), Some("wav"));
+        assert_eq!(sniff_format(&webm("A_OPUS", 48000.0, &[], 20)), Some("webm"));
+        assert_eq!(sniff_format(b"OggS\0\x02"), Some("ogg"));
+        assert_eq!(sniff_format(b"fLaC\0\0\0\x22"), Some("flac"));
+        assert_eq!(sniff_format(&[0xFF, 0xF1, 0, 0]), Some("aac"));
+        assert_eq!(sniff_format(&[0xFF, 0xFB, 0, 0]), Some("mp3"));
+        assert_eq!(sniff_format(b"not audio"), None);
+        assert!(is_supported_format("WEBM") && is_supported_format("opus"));
+    }
+
+    #[test]
+    fn webm_decodes_from_file_and_bytes() {
+        let processor = AudioProcessor::new();
+        let mp3 = processor.samples_to_mp3_bytes(&tone(44100, 440.0, 16000.0, 44100), 44100, 128).unwrap();
+        let data = webm("A_MPEG/L3", 44100.0, &mp3_frames(&mp3), 26);
+        let path = test_temp_path("clip.webm");
+        std::fs::write(&path, &data).unwrap();
+
+        let decoded = processor.decode_audio_symphonia(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        let (samples, sample_rate) = decoded.unwrap();
+        assert_eq!(sample_rate, 44100);
+        assert!(samples.len() >= 44100 && samples.iter().any(|&s| s.abs() > 10000), "decoded {} samples", samples.len());
+
+        let (from_bytes, _) = processor.decode_audio_bytes(&data, None).unwrap();
+        assert_eq!(from_bytes.len(), samples.len());
+    }
+
+    #[test]
+    fn webm_opus_without_decoder_says_so() {
+        let opus = webm("A_OPUS", 48000.0, &[vec![0xFC, 0xFF, 0xFE]], 20);
+        let error = AudioProcessor::new().decode_audio_bytes(&opus, Some("webm")).unwrap_err().to_string();
+        assert!(error.contains("Opus") && error.contains("no decoder"), "{}", error);
+    }
+
+    #[test]
+    fn late_extraction_seeks_and_matches_full_decode() {
+        let processor = AudioProcessor::new();
+        // A minute of a sweep, so every position in the file has distinct samples
+        let samples: Vec<i16> = (0..16000 * 60).map(|i| ((i as f64 * (0.01 + i as f64 * 1e-8)).sin() * 12000.0) as i16).collect();
+
+        for (name, bytes) in [
+            ("late.wav", processor.samples_to_wav_bytes(&samples, 16000).unwrap()),
+            ("late.mp3", processor.samples_to_mp3_bytes(&samples, 16000, 64).unwrap()),
+        ] {
+            let path = test_temp_path(name);
+            std::fs::write(&path, bytes).unwrap();
+
+            let started = Instant::now();
+            let seeked = processor.extract_segment_seeking(&path, 55.0, 57.0);
+            let seek_time = started.elapsed();
+            let started = Instant::now();
+            let (full, sample_rate) = processor.decode_audio_symphonia(path.to_str().unwrap()).unwrap();
+            let full_decode_time = started.elapsed();
+            std::fs::remove_file(&path).ok();
+
+            let (segment, segment_rate) = seeked.unwrap_or_else(|e| panic!("{}: seeking extraction failed: {}", name, e));
+            assert_eq!(segment_rate, sample_rate);
+            assert_eq!(segment, full[55 * 16000..57 * 16000], "{}: extracted samples differ from the full decode", name);
+            assert!(seek_time < full_decode_time, "{}: seeking took {:?}, full decode {:?}", name, seek_time, full_decode_time);
+        }
+    }
+
+    #[test]
+    fn validate_segments_clamps_overruns_and_rejects_bad_order() {
+        let processor = AudioProcessor::new();
+
+        let mut overrun = vec![segment_at(0, vec![0; 100]), segment_at(200, vec![0; 900])];
+        processor.validate_segments(&mut overrun, 1000, 16000.0).unwrap();
+        assert_eq!(overrun[1].end_sample, 1000);
+        assert_eq!(overrun[1].end_time_seconds, 1000.0 / 16000.0);
+
+        let mut overlapping = vec![segment_at(0, vec![0; 300]), segment_at(200, vec![0; 300])];
+        let error = processor.validate_segments(&mut overlapping, 1000, 16000.0).unwrap_err().to_string();
+        assert!(error.contains("Segment 1 starts at sample 200"), "{}", error);
+
+        let mut unsorted = vec![segment_at(500, vec![0; 100]), segment_at(0, vec![0; 100])];
+        assert!(processor.validate_segments(&mut unsorted, 1000, 16000.0).is_err());
+
+        let mut past_the_end = vec![segment_at(1000, vec![0; 100])];
+        let error = processor.validate_segments(&mut past_the_end, 1000, 16000.0).unwrap_err().to_string();
+        assert!(error.contains("empty or inverted"), "{}", error);
+    }
+
+    #[test]
+    fn headroom_finds_inter_sample_peaks() {
+        let processor = AudioProcessor::new();
+        // A quarter-rate sine sampled 45 degrees off its crests: every sample sits at
+        // -3 dB of the waveform's real peak, which lies between samples
+        let amplitude = 30000.0;
+        let samples: Vec<i16> = (0..16000)
+            .map(|i| (amplitude * (std::f64::consts::FRAC_PI_2 * i as f64 + std::f64::consts::FRAC_PI_4).sin()).round() as i16)
+            .collect();
+        let path = test_temp_path("headroom.wav");
+        std::fs::write(&path, processor.samples_to_wav_bytes(&samples, 16000).unwrap()).unwrap();
+        let headroom = processor.measure_headroom(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let headroom = headroom.unwrap();
+
+        assert!((headroom.sample_peak_dbfs - dsp::amplitude_to_dbfs(amplitude / 2f64.sqrt())).abs() < 0.01, "{:?}", headroom);
+        assert!((headroom.true_peak_dbtp - dsp::amplitude_to_dbfs(amplitude)).abs() < 0.3, "{:?}", headroom);
+        assert!(!headroom.at_full_scale);
+    }
+
+    // 16kHz WAV of silence with a loud tone over each (start, end) range in seconds
+    fn write_bursts(path: &std::path::Path, duration_seconds: f64, bursts: &[(f64, f64)]) {
+        let mut samples = vec![0i16; (duration_seconds * 16000.0) as usize];
+        let loud = tone(16000, 440.0, 12000.0, samples.len());
+        for &(start, end) in bursts {
+            let range = (start * 16000.0) as usize..(end * 16000.0) as usize;
+            samples[range.clone()].copy_from_slice(&loud[range]);
+        }
+        std::fs::write(path, AudioProcessor::new().samples_to_wav_bytes(&samples, 16000).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn segment_cache_serves_identical_runs_and_misses_on_changes() {
+        let path = test_temp_path("segment-cache.wav");
+        let cache_dir = test_temp_path("segment-cache");
+        write_bursts(&path, 8.0, &[(1.0, 3.0), (5.0, 6.0)]);
+
+        let mut processor = AudioProcessor::new();
+        processor.set_vad_backend(VadBackend::Rms);
+        processor.set_segment_cache(Some(SegmentCache::new(cache_dir.clone())));
+        // Segments of a run, and whether they came from the cache
+        let run = |processor: &mut AudioProcessor| {
+            let cached = std::cell::Cell::new(false);
+            let segments = processor
+                .process_audio_file_with_progress(path.to_str().unwrap(), "", |step, _, _| cached.set(cached.get() || step == "Loaded cached segments"))
+                .unwrap();
+            (segments, cached.get())
+        };
+
+        let (first, cached) = run(&mut processor);
+        assert!(!cached);
+        assert_eq!(first.len(), 2);
+        let (second, cached) = run(&mut processor);
+        assert!(cached);
+        assert_eq!(second.len(), first.len());
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!((a.start_sample, a.end_sample), (b.start_sample, b.end_sample));
+            assert_eq!(a.audio_data, b.audio_data);
+        }
+
+        processor.set_merge_gap(3.0);
+        assert!(!run(&mut processor).1);
+        assert!(run(&mut processor).1);
+
+        write_bursts(&path, 8.0, &[(1.0, 2.0)]);
+        let (edited, cached) = run(&mut processor);
+        assert!(!cached);
+        assert_eq!(edited.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    // ID3v2.3 tag holding one APIC frame per (mime, picture type, data)
+    fn id3_with_pictures(pictures: &[(&str, u8, &[u8])]) -> Vec<u8> {
+        let mut frames = Vec::new();
+        for &(mime, picture_type, data) in pictures {
+            let body = [&[0u8][..], mime.as_bytes(), &[0, picture_type, 0], data].concat();
+            frames.extend_from_slice(b"APIC");
+            frames.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            frames.extend_from_slice(&[0, 0]);
+            frames.extend(body);
+        }
+        // The tag size is synchsafe: seven bits per byte
+        let size = frames.len() as u32;
+        let size = [(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F];
+        [b"ID3\x03\x00\x00".as_slice(), &size, &frames].concat()
+    }
+
+    #[test]
+    fn cover_art_prefers_the_front_cover() {
+        let processor = AudioProcessor::new();
+        let mp3 = processor.samples_to_mp3_bytes(&tone(44100, 440.0, 8000.0, 44100), 44100, 128).unwrap();
+        let tag = id3_with_pictures(&[("image/jpeg", 0x00, b"other picture"), ("image/png", 0x03, b"\x89PNG front cover")]);
+        let tagged = test_temp_path("cover.mp3");
+        let untagged = test_temp_path("no-cover.mp3");
+        std::fs::write(&tagged, [tag, mp3.clone()].concat()).unwrap();
+        std::fs::write(&untagged, mp3).unwrap();
+
+        let cover = processor.read_cover_art(tagged.to_str().unwrap());
+        let no_cover = processor.read_cover_art(untagged.to_str().unwrap());
+        std::fs::remove_file(&tagged).unwrap();
+        std::fs::remove_file(&untagged).unwrap();
+
+        let cover = cover.unwrap().expect("cover art");
+        assert_eq!(cover.mime, "image/png");
+        assert_eq!(cover.bytes, b"\x89PNG front cover");
+        assert!(no_cover.unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_progress_rises_steadily_to_the_end() {
+        let processor = AudioProcessor::new();
+        let samples = tone(16000, 440.0, 8000.0, 16000 * 60);
+        // A container with an exact length and a frame stream that may only have an estimate
+        for (name, bytes) in [
+            ("progress.wav", processor.samples_to_wav_bytes(&samples, 16000).unwrap()),
+            ("progress.mp3", processor.samples_to_mp3_bytes(&samples, 16000, 64).unwrap()),
+        ] {
+            let path = test_temp_path(name);
+            std::fs::write(&path, bytes).unwrap();
+            let progress = std::cell::RefCell::new(Vec::new());
+            let decoded = processor.decode_audio_symphonia_with_progress(path.to_str().unwrap(), &|step: &str, value: f64, _: Option<&str>| {
+                if step == "Decoding audio packets" {
+                    progress.borrow_mut().push(value);
+                }
+            });
+            std::fs::remove_file(&path).unwrap();
+            decoded.unwrap();
+
+            let progress = progress.into_inner();
+            assert!(progress.len() >= 10, "{}: only {} progress updates", name, progress.len());
+            assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]), "{}: progress went backwards: {:?}", name, progress);
+            assert!(progress[0] < 12.0, "{}: progress started at {:.1}", name, progress[0]);
+            assert!(*progress.last().unwrap() >= 23.0, "{}: progress stopped at {:.1}", name, progress.last().unwrap());
+        }
+    }
+
+    // Deterministic white noise of the given peak amplitude
+    fn noise(amplitude: f64, len: usize) -> Vec<i16> {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                ((state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0) * amplitude
+            })
+            .map(|sample| sample.round() as i16)
+            .collect()
+    }
+
+    #[test]
+    fn snr_tells_clean_segments_from_buried_ones() {
+        // A loud tone in a quiet room for the first four seconds, a faint one in a noisy room for the next four
+        let mut content = noise(100.0, 16000 * 4);
+        content.extend(noise(3000.0, 16000 * 4));
+        for (start, amplitude) in [(16000, 10000.0), (16000 * 6, 1000.0)] {
+            for (sample, tone) in content[start..start + 16000].iter_mut().zip(tone(16000, 440.0, amplitude, 16000)) {
+                *sample = sample.saturating_add(tone);
+            }
+        }
+        let mut segments = vec![
+            segment_at(16000, content[16000..16000 * 2].to_vec()),
+            segment_at(16000 * 6, content[16000 * 6..16000 * 7].to_vec()),
+        ];
+        AudioProcessor::estimate_snr(&mut segments, &content, 16000.0);
+
+        let (clean, buried) = (segments[0].snr_db.unwrap(), segments[1].snr_db.unwrap());
+        assert!(clean > 30.0, "clean segment has an SNR of {:.1} dB", clean);
+        assert!(buried < 3.0, "buried segment has an SNR of {:.1} dB", buried);
+    }
+
+    #[test]
+    fn mp3_encoding_produces_decodable_frames() {
+        let processor = AudioProcessor::new();
+        let samples = tone(16000, 440.0, 8000.0, 32000);
+        let mp3 = processor.samples_to_mp3_bytes(&samples, 16000, 64).unwrap();
+        // Frame sync: eleven set bits
+        assert!(mp3.len() > 1000 && mp3[0] == 0xFF && mp3[1] & 0xE0 == 0xE0, "no MP3 frame header in {:02X?}", &mp3[..4.min(mp3.len())]);
+
+        let (decoded, sample_rate) = processor.decode_audio_bytes(&mp3, Some("mp3")).unwrap();
+        assert_eq!(sample_rate, 16000);
+        assert!(decoded.len() >= samples.len(), "decoded {} of {} samples", decoded.len(), samples.len());
+    }
+
+    #[test]
+    fn mp3_encoding_rejects_unsupported_settings() {
+        let processor = AudioProcessor::new();
+        let samples = tone(16000, 440.0, 8000.0, 1600);
+        assert!(processor.samples_to_mp3_bytes(&samples, 16000, 65).is_err());
+        assert!(processor.samples_to_mp3_bytes(&samples, 7, 64).is_err());
+    }
+
+    fn downmix(mode: ChannelMixMode, interleaved: &[i16]) -> Vec<i16> {
+        let mut processor = AudioProcessor::new();
+        processor.set_channel_mix_mode(mode);
+        let mut out = Vec::new();
+        processor.downmix_into(interleaved, 2, None, &mut processor.dual_mono_detector(i16::MAX as f64), &mut out);
+        out
+    }
+
+    #[test]
+    fn channel_mix_modes_pick_or_blend_channels() {
+        // Silent left channel, signal on the right only
+        let right = tone(16000, 440.0, 8000.0, 1600);
+        let interleaved = interleave(&vec![0; right.len()], &right);
+
+        assert_eq!(downmix(ChannelMixMode::Right, &interleaved), right);
+        assert_eq!(downmix(ChannelMixMode::Max, &interleaved), right);
+        assert!(downmix(ChannelMixMode::Left, &interleaved).iter().all(|&sample| sample == 0));
+        let average = downmix(ChannelMixMode::Average, &interleaved);
+        assert_eq!(average.len(), right.len());
+        for (&mixed, &sample) in average.iter().zip(&right) {
+            assert!((mixed as i32 - sample as i32 / 2).abs() <= 1, "{} is not half of {}", mixed, sample);
+        }
+    }
+
+    #[test]
+    fn channel_mix_mode_applies_to_decoded_files() {
+        let right = tone(16000, 440.0, 8000.0, 16000);
+        let path = test_temp_path("right-only.wav");
+        let spec = hound::WavSpec { channels: 2, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for &sample in &interleave(&vec![0; right.len()], &right) {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut processor = AudioProcessor::new();
+        let (average, _) = processor.decode_audio_symphonia(path.to_str().unwrap()).unwrap();
+        processor.set_channel_mix_mode(ChannelMixMode::Right);
+        let (right_only, _) = processor.decode_audio_symphonia(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(right_only, right);
+        assert!((rms(&average) / rms(&right) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn supported_formats_ignore_case() {
+        assert!(is_supported_format("WAV") && is_supported_format("flac"));
+        assert!(!is_supported_format("dat") && !is_supported_format(""));
+    }
+
+    #[test]
+    fn renamed_wav_is_recognized_by_content() {
+        let renamed = test_temp_path("renamed.dat");
+        let junk = test_temp_path("junk.dat");
+        write_bursts(&renamed, 4.0, &[(1.0, 3.0)]);
+        std::fs::write(&junk, b"not audio at all, definitely not").unwrap();
+
+        let mut processor = AudioProcessor::new();
+        processor.set_vad_backend(VadBackend::Rms);
+        let segments = processor.process_audio_file(renamed.to_str().unwrap(), "");
+        let error = processor.process_audio_file(junk.to_str().unwrap(), "").unwrap_err().to_string();
+        std::fs::remove_file(&renamed).unwrap();
+        std::fs::remove_file(&junk).unwrap();
+
+        assert_eq!(segments.unwrap().len(), 1);
+        assert!(error.starts_with("Unsupported audio format: 'dat'. Supported formats: WAV, MP3"), "{}", error);
+    }
+
+    #[test]
+    fn wav_base64_header_carries_the_given_rate() {
+        let processor = AudioProcessor::new();
+        for sample_rate in [8000u32, 16000, 44100] {
+            let wav = utils::decode_b64(&processor.samples_to_wav_base64(&[0, 1, -1], sample_rate).unwrap()).unwrap();
+            assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), sample_rate);
+            // Byte rate of 16-bit mono
+            assert_eq!(u32::from_le_bytes(wav[28..32].try_into().unwrap()), sample_rate * 2);
+        }
+    }
+
+    #[test]
+    fn segment_audio_is_encoded_at_the_vad_rate() {
+        let path = test_temp_path("eight-khz-segments.wav");
+        write_bursts(&path, 4.0, &[(1.0, 3.0)]);
+        let mut processor = AudioProcessor::new();
+        processor.set_vad_backend(VadBackend::Rms);
+        processor.set_sample_rate(8000).unwrap();
+        let segments = processor.process_audio_file(path.to_str().unwrap(), "");
+        std::fs::remove_file(&path).unwrap();
+
+        let segments = segments.unwrap();
+        assert_eq!(segments.len(), 1);
+        let wav = utils::decode_b64(&segments[0].audio_base64).unwrap();
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 8000);
+    }
+
+    #[test]
+    fn silent_and_empty_files_are_reported_not_failed() {
+        let silent = test_temp_path("silent.wav");
+        let no_samples = test_temp_path("no-samples.wav");
+        let zero_bytes = test_temp_path("zero-bytes.wav");
+        write_bursts(&silent, 2.0, &[]);
+        std::fs::write(&no_samples, AudioProcessor::new().samples_to_wav_bytes(&[], 16000).unwrap()).unwrap();
+        std::fs::write(&zero_bytes, b"").unwrap();
+
+        // Silero is never loaded for audio that is silent throughout
+        let mut processor = AudioProcessor::new();
+        let mut report = |path: &std::path::Path| {
+            let report = ProcessingReport::from_result(processor.process_audio_file(path.to_str().unwrap(), ""));
+            std::fs::remove_file(path).unwrap();
+            report.unwrap().status
+        };
+        assert_eq!(report(&silent), ProcessingStatus::NoSpeech);
+        assert_eq!(report(&no_samples), ProcessingStatus::NoAudio);
+        assert_eq!(report(&zero_bytes), ProcessingStatus::NoAudio);
+
+        // A file that isn't there is still an error
+        let missing = test_temp_path("missing.wav");
+        assert!(ProcessingReport::from_result(processor.process_audio_file(missing.to_str().unwrap(), "")).is_err());
+    }
+
+    #[test]
+    fn clipped_recordings_are_measured_and_warned_about() {
+        // A sine driven to almost twice full scale spends about two thirds of its time pinned
+        let clipped: Vec<i16> = (0..16000)
+            .map(|i| (60000.0 * (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 16000.0).sin()).clamp(i16::MIN as f64, i16::MAX as f64) as i16)
+            .collect();
+        let clipped_path = test_temp_path("clipped.wav");
+        let clean_path = test_temp_path("not-clipped.wav");
+        let mut processor = AudioProcessor::new();
+        std::fs::write(&clipped_path, processor.samples_to_wav_bytes(&clipped, 16000).unwrap()).unwrap();
+        std::fs::write(&clean_path, processor.samples_to_wav_bytes(&tone(16000, 440.0, 30000.0, 16000), 16000).unwrap()).unwrap();
+
+        let unmeasured = processor.probe_metadata(clipped_path.to_str().unwrap(), false).unwrap();
+        let measured = processor.probe_metadata(clipped_path.to_str().unwrap(), true).unwrap();
+        let clean = processor.probe_metadata(clean_path.to_str().unwrap(), true).unwrap();
+        let steps = std::cell::RefCell::new(Vec::new());
+        let decoded = processor.decode_audio_symphonia_with_progress(clipped_path.to_str().unwrap(), &|step: &str, _, _| steps.borrow_mut().push(step.to_string()));
+        std::fs::remove_file(&clipped_path).unwrap();
+        std::fs::remove_file(&clean_path).unwrap();
+        decoded.unwrap();
+
+        assert!(unmeasured.clipping_ratio.is_none());
+        let ratio = measured.clipping_ratio.unwrap();
+        assert!(ratio > 0.5 && ratio < 0.8, "clipping ratio {:.3}", ratio);
+        assert_eq!(clean.clipping_ratio, Some(0.0));
+        assert!(steps.borrow().iter().any(|step| step == "Clipping detected"), "{:?}", steps.borrow());
+        let warnings = processor.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::Clipping);
+    }
+
+    #[test]
+    fn power_downmix_keeps_the_level_of_uncorrelated_channels() {
+        let level_db = |samples: &[i16], reference: &[i16]| 20.0 * (rms(samples) / rms(reference)).log10();
+        // Two unrelated channels at the same level, like a wide stereo mix
+        let interleaved = noise(10000.0, 40000);
+        let left: Vec<i16> = interleaved.iter().step_by(2).copied().collect();
+
+        let average = downmix(ChannelMixMode::Average, &interleaved);
+        let power = downmix(ChannelMixMode::Power, &interleaved);
+        assert!((level_db(&average, &left) + 3.0).abs() < 0.3, "average is {:.2} dB off the source", level_db(&average, &left));
+        assert!(level_db(&power, &left).abs() < 0.3, "power mix is {:.2} dB off the source", level_db(&power, &left));
+    }
+
+    #[test]
+    fn power_downmix_of_dual_mono_keeps_its_level_across_the_probe() {
+        let mut processor = AudioProcessor::new();
+        processor.set_channel_mix_mode(ChannelMixMode::Power);
+        let left = tone(48000, 440.0, 8000.0, 3 * DUAL_MONO_PROBE_FRAMES);
+        let interleaved = interleave(&left, &left);
+
+        // Decoder-sized buffers, so the decision falls in the middle of the file
+        let mut dual_mono = processor.dual_mono_detector(i16::MAX as f64);
+        let mut out = Vec::new();
+        for buffer in interleaved.chunks(2 * 4096) {
+            processor.downmix_into(buffer, 2, None, &mut dual_mono, &mut out);
+        }
+        assert_eq!(dual_mono.decision, Some(true));
+        let (probed, decided) = out.split_at(DUAL_MONO_PROBE_FRAMES / 2);
+        assert!((rms(probed) / rms(decided) - 1.0).abs() < 0.01, "level jumps from {:.0} to {:.0}", rms(probed), rms(decided));
+        assert_eq!(out, left);
+    }
+
+    #[test]
+    fn power_downmix_clamps_at_full_scale() {
+        let loud = interleave(&[30000, -30000], &[29000, -29000]);
+        assert_eq!(downmix(ChannelMixMode::Power, &loud), vec![i16::MAX, i16::MIN]);
+    }
+
+    #[test]
+    fn merged_segments_cover_the_source_audio_exactly() {
+        let content = tone(16000, 127.0, 8000.0, 32000);
+        let cut = |start: usize, end: usize| segment_at(start as i64, content[start..end].to_vec());
+        for crossfade in [false, true] {
+            let mut processor = AudioProcessor::new();
+            processor.set_crossfade_merges(crossfade);
+
+            // Apart, containing and overlapping: always the contiguous source range
+            for (first, second, expected) in [((1000, 5000), (8000, 12000), 1000..12000), ((1000, 12000), (3000, 5000), 1000..12000), ((1000, 6000), (5000, 9000), 1000..9000)] {
+                let merged = processor.merge_close_segments(vec![cut(first.0, first.1), cut(second.0, second.1)], &content, 1.0);
+                assert_eq!(merged.len(), 1);
+                assert_eq!((merged[0].start_sample, merged[0].end_sample), (expected.start as i64, expected.end as i64));
+                assert!(merged[0].audio_data == content[expected], "crossfade {}: merged audio differs from the source", crossfade);
+            }
+        }
+    }
+
+    #[test]
+    fn crossfaded_merges_have_no_jumps() {
+        // Segment audio that no longer matches the source, e.g. after processing, jumps where it meets the gap
+        let content = tone(16000, 127.0, 8000.0, 32000);
+        let offset = |start: usize, end: usize| segment_at(start as i64, content[start..end].iter().map(|&sample| sample.saturating_add(12000)).collect());
+        let largest_step = |samples: &[i16]| samples.windows(2).map(|pair| (pair[1] as i32 - pair[0] as i32).abs()).max().unwrap();
+
+        let mut processor = AudioProcessor::new();
+        let hard = processor.merge_close_segments(vec![offset(1000, 5000), offset(8000, 12000)], &content, 1.0);
+        processor.set_crossfade_merges(true);
+        let faded = processor.merge_close_segments(vec![offset(1000, 5000), offset(8000, 12000)], &content, 1.0);
+
+        assert_eq!(hard[0].audio_data.len(), faded[0].audio_data.len());
+        assert!(largest_step(&hard[0].audio_data) > 10000);
+        // A 127 Hz tone at this level moves by at most ~400 per sample; a 5 ms fade adds ~150
+        assert!(largest_step(&faded[0].audio_data) < 1000, "largest step {}", largest_step(&faded[0].audio_data));
+    }
+
+    #[test]
+    fn wav_data_length_mismatch_warns_and_placeholders_decode_fully() {
+        let mut wav = AudioProcessor::new().samples_to_wav_bytes(&tone(16000, 300.0, 8000.0, 16000), 16000).unwrap();
+        let mut processor = AudioProcessor::new();
+        let (samples, _) = processor.decode_audio_bytes(&wav, Some("wav")).unwrap();
+        assert_eq!(samples.len(), 16000);
+        assert!(processor.take_warnings().is_empty());
+
+        // Half the data declared: decoding stops there, with a warning saying so
+        wav[40..44].copy_from_slice(&16000u32.to_le_bytes());
+        let (samples, _) = processor.decode_audio_bytes(&wav, Some("wav")).unwrap();
+        assert_eq!(samples.len(), 8000);
+        let warnings = processor.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::WavDataLength);
+
+        // A streamed file's zero size still decodes in full, from a file or from bytes
+        wav[40..44].copy_from_slice(&0u32.to_le_bytes());
+        let path = test_temp_path("streamed.wav");
+        std::fs::write(&path, &wav).unwrap();
+        let from_file = processor.decode_audio_symphonia(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_file.unwrap().0.len(), 16000);
+        assert_eq!(processor.decode_audio_bytes(&wav, None).unwrap().0.len(), 16000);
+        assert_eq!(processor.take_warnings().len(), 2);
+    }
 }
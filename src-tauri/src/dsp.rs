@@ -0,0 +1,344 @@
+// Small signal-processing building blocks shared by the audio pipeline
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// Lowest level reported in dB, used instead of -inf for digital silence
+pub const DB_FLOOR: f64 = -120.0;
+
+/// Convert a linear amplitude relative to i16 full scale into dBFS
+pub fn amplitude_to_dbfs(amplitude: f64) -> f64 {
+    if amplitude <= 0.0 {
+        return DB_FLOOR;
+    }
+    (20.0 * (amplitude / 32768.0).log10()).max(DB_FLOOR)
+}
+
+/// Linear amplitude relative to i16 full scale for a level in dBFS
+pub fn dbfs_to_amplitude(dbfs: f64) -> f64 {
+    32768.0 * 10f64.powf(dbfs / 20.0)
+}
+
+// 4x oversampling as recommended by ITU-R BS.1770 for true-peak measurement
+const OVERSAMPLING: usize = 4;
+const HALF_TAPS: usize = 8;
+const TAPS: usize = HALF_TAPS * 2;
+
+/// Streaming sample-peak and true-peak (inter-sample peak) meter for interleaved audio.
+/// True peak is estimated by 4x oversampling with a Hann-windowed sinc interpolator.
+pub struct TruePeakMeter {
+    history: Vec<VecDeque<f64>>,
+    kernels: Vec<[f64; TAPS]>,
+    sample_peak: f64,
+    true_peak: f64,
+}
+
+impl TruePeakMeter {
+    pub fn new(channels: usize) -> Self {
+        // One interpolation kernel per fractional position between two input samples
+        let kernels = (1..OVERSAMPLING)
+            .map(|phase| {
+                let frac = phase as f64 / OVERSAMPLING as f64;
+                let mut kernel = [0.0; TAPS];
+                for (tap, coefficient) in kernel.iter_mut().enumerate() {
+                    let x = (HALF_TAPS - 1) as f64 + frac - tap as f64;
+                    let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                    let window = 0.5 * (1.0 + (PI * x / HALF_TAPS as f64).cos());
+                    *coefficient = sinc * window;
+                }
+                kernel
+            })
+            .collect();
+
+        Self {
+            history: vec![VecDeque::with_capacity(TAPS); channels.max(1)],
+            kernels,
+            sample_peak: 0.0,
+            true_peak: 0.0,
+        }
+    }
+
+    pub fn process_interleaved(&mut self, samples: &[i16]) {
+        let channels = self.history.len();
+        for frame in samples.chunks(channels) {
+            for (channel, &sample) in frame.iter().enumerate() {
+                let value = sample as f64;
+                self.sample_peak = self.sample_peak.max(value.abs());
+                self.true_peak = self.true_peak.max(value.abs());
+
+                let history = &mut self.history[channel];
+                if history.len() == TAPS {
+                    history.pop_front();
+                }
+                history.push_back(value);
+
+                if history.len() == TAPS {
+                    for kernel in &self.kernels {
+                        let interpolated: f64 = history.iter().zip(kernel).map(|(s, c)| s * c).sum();
+                        self.true_peak = self.true_peak.max(interpolated.abs());
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn sample_peak(&self) -> f64 {
+        self.sample_peak
+    }
+
+    pub fn true_peak(&self) -> f64 {
+        self.true_peak
+    }
+}
+
+/// One band of a parametric EQ: a peaking filter centred on `freq` (Hz)
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EqBand {
+    pub freq: f64,
+    pub gain_db: f64,
+    pub q: f64,
+}
+
+// RBJ audio-EQ-cookbook peaking filter, run as transposed direct form II
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn peaking(band: &EqBand, sample_rate: f64) -> Self {
+        let a = 10f64.powf(band.gain_db / 40.0);
+        let w0 = 2.0 * PI * band.freq / sample_rate;
+        let alpha = w0.sin() / (2.0 * band.q);
+        let cos_w0 = w0.cos();
+        let a0 = 1.0 + alpha / a;
+
+        Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: (-2.0 * cos_w0) / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: (-2.0 * cos_w0) / a0,
+            a2: (1.0 - alpha / a) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// Apply a cascade of peaking filters to mono samples. Bands must sit below Nyquist
+/// and have a positive Q; an empty band list returns the input unchanged.
+pub fn apply_eq(samples: &[i16], bands: &[EqBand], sample_rate: u32) -> Result<Vec<i16>, String> {
+    let nyquist = sample_rate as f64 / 2.0;
+    for band in bands {
+        if !band.freq.is_finite() || band.freq <= 0.0 || band.freq >= nyquist {
+            return Err(format!("EQ band frequency {} Hz must be between 0 and {} Hz", band.freq, nyquist));
+        }
+        if !band.q.is_finite() || band.q <= 0.0 || !band.gain_db.is_finite() {
+            return Err(format!("EQ band at {} Hz needs a positive Q and a finite gain", band.freq));
+        }
+    }
+
+    let mut filters: Vec<Biquad> = bands.iter().map(|band| Biquad::peaking(band, sample_rate as f64)).collect();
+    Ok(samples.iter()
+        .map(|&sample| {
+            let filtered = filters.iter_mut().fold(sample as f64, |x, filter| filter.process(x));
+            filtered.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+        })
+        .collect())
+}
+
+/// Scale samples by `gain_db`, hard-clipping at the i16 limits. Returns how many
+/// samples had to be clipped.
+pub fn apply_gain(samples: &mut [i16], gain_db: f64) -> usize {
+    let gain = 10f64.powf(gain_db / 20.0);
+    let mut clipped = 0;
+    for sample in samples.iter_mut() {
+        let scaled = (*sample as f64 * gain).round();
+        if scaled > i16::MAX as f64 || scaled < i16::MIN as f64 {
+            clipped += 1;
+        }
+        *sample = scaled.clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+    }
+    clipped
+}
+
+/// Smallest and largest sample in each of `buckets` equal stretches of `samples`, for
+/// drawing a waveform. With fewer samples than buckets, each sample is a bucket of its own.
+pub fn min_max_peaks(samples: &[i16], buckets: usize) -> Vec<(i16, i16)> {
+    let buckets = buckets.min(samples.len());
+    (0..buckets)
+        .map(|bucket| {
+            let stretch = &samples[bucket * samples.len() / buckets..(bucket + 1) * samples.len() / buckets];
+            stretch.iter().fold((i16::MAX, i16::MIN), |(min, max), &sample| (min.min(sample), max.max(sample)))
+        })
+        .collect()
+}
+
+/// Mean square of all samples across several slices, or None if they are all empty
+pub fn mean_square(slices: &[&[i16]]) -> Option<f64> {
+    let count: usize = slices.iter().map(|slice| slice.len()).sum();
+    if count == 0 {
+        return None;
+    }
+    let sum: f64 = slices.iter()
+        .flat_map(|slice| slice.iter())
+        .map(|&sample| (sample as f64) * (sample as f64))
+        .sum();
+    Some(sum / count as f64)
+}
+
+/// Level in dBFS of the quietest `fraction` of `frame_len`-sample frames, an estimate of
+/// the background noise under any speech. Frames of digital silence say nothing about a
+/// recording's noise and are skipped; None when nothing else is left.
+pub fn noise_floor_dbfs(samples: &[i16], frame_len: usize, fraction: f64) -> Option<f64> {
+    let mut powers: Vec<f64> = samples.chunks_exact(frame_len.max(1))
+        .filter(|frame| frame.iter().any(|sample| sample.unsigned_abs() > 1))
+        .filter_map(|frame| mean_square(&[frame]))
+        .collect();
+    if powers.is_empty() {
+        return None;
+    }
+    powers.sort_by(f64::total_cmp);
+    let count = ((powers.len() as f64 * fraction).ceil() as usize).clamp(1, powers.len());
+    let power = powers[..count].iter().sum::<f64>() / count as f64;
+    Some(amplitude_to_dbfs(power.sqrt()))
+}
+
+/// Blend `to` in from `from` over their common length: the first sample is nearly all
+/// `from`, the last nearly all `to`
+pub fn crossfade(from: &[i16], to: &mut [i16]) {
+    let len = from.len().min(to.len());
+    for (index, (target, &source)) in to.iter_mut().zip(from).enumerate() {
+        let weight = (index + 1) as f64 / (len + 1) as f64;
+        *target = (source as f64 * (1.0 - weight) + *target as f64 * weight).round() as i16;
+    }
+}
+
+/// Number of samples pinned at full scale, a sign of input that was overloaded.
+/// Both -32767 and -32768 count, as float sources convert to the symmetric range.
+pub fn count_clipped(samples: &[i16]) -> usize {
+    samples.iter().filter(|&&sample| sample.unsigned_abs() >= i16::MAX as u16).count()
+}
+
+/// Convert a float sample to i16: values past full scale are clamped to [-1.0, 1.0],
+/// then scaled by i16::MAX and rounded. NaN becomes silence.
+pub fn float_to_i16(sample: f32) -> i16 {
+    if sample.is_nan() {
+        return 0;
+    }
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+/// Convert a full-range i32 sample to i16, rounding to the nearest step instead of
+/// truncating. 24-bit sources arrive here shifted up by 8 bits.
+pub fn i32_to_i16(sample: i32) -> i16 {
+    ((sample as i64 + 0x8000) >> 16).min(i16::MAX as i64) as i16
+}
+
+/// Converts float samples to i16 with triangular-PDF dither of +-1 LSB, which turns
+/// quantization error into a constant noise floor instead of distortion that follows
+/// the signal. Uses a small xorshift generator, so output is repeatable.
+pub struct TpdfDither {
+    state: u32,
+}
+
+impl Default for TpdfDither {
+    fn default() -> Self {
+        Self { state: 0x9E37_79B9 }
+    }
+}
+
+impl TpdfDither {
+    // Uniform in [0, 1)
+    fn next_uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Quantize a sample to i16 on the same scale as `float_to_i16`
+    pub fn quantize(&mut self, sample: f32) -> i16 {
+        if sample.is_nan() {
+            return 0;
+        }
+        let noise = self.next_uniform() - self.next_uniform();
+        (sample.clamp(-1.0, 1.0) * i16::MAX as f32 + noise).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Magnitude of the `frequency` component of `samples`, skipping the filters' settling time
+    fn energy_at(samples: &[i16], frequency: f64, sample_rate: f64) -> f64 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, &sample) in samples.iter().enumerate().skip(1000) {
+            let phase = 2.0 * PI * frequency * i as f64 / sample_rate;
+            re += sample as f64 * phase.cos();
+            im += sample as f64 * phase.sin();
+        }
+        (re * re + im * im).sqrt()
+    }
+
+    #[test]
+    fn eq_boosts_only_its_band() {
+        let sample_rate = 16000.0;
+        let samples: Vec<i16> = (0..16000)
+            .map(|i| {
+                let t = i as f64 / sample_rate;
+                (3000.0 * (2.0 * PI * 300.0 * t).sin() + 3000.0 * (2.0 * PI * 3000.0 * t).sin()) as i16
+            })
+            .collect();
+        let boosted = apply_eq(&samples, &[EqBand { freq: 3000.0, gain_db: 6.0, q: 2.0 }], 16000).unwrap();
+
+        let high_gain = energy_at(&boosted, 3000.0, sample_rate) / energy_at(&samples, 3000.0, sample_rate);
+        let low_gain = energy_at(&boosted, 300.0, sample_rate) / energy_at(&samples, 300.0, sample_rate);
+        assert!((high_gain - dbfs_to_amplitude(6.0) / 32768.0).abs() < 0.05, "3 kHz gained {:.3}x", high_gain);
+        assert!((low_gain - 1.0).abs() < 0.02, "300 Hz gained {:.3}x", low_gain);
+    }
+
+    #[test]
+    fn eq_rejects_bands_above_nyquist() {
+        assert!(apply_eq(&[0; 16], &[EqBand { freq: 9000.0, gain_db: 6.0, q: 2.0 }], 16000).is_err());
+    }
+
+    #[test]
+    fn dither_preserves_levels_below_one_lsb() {
+        let mut dither = TpdfDither::default();
+        for level in [0.3f32, 1.6, -2.25] {
+            let sample = level / i16::MAX as f32;
+            let mean = (0..200_000).map(|_| dither.quantize(sample) as f64).sum::<f64>() / 200_000.0;
+            assert!((mean - level as f64).abs() < 0.02, "{} LSB averages to {:.3} LSB", level, mean);
+        }
+    }
+
+    #[test]
+    fn dither_turns_distortion_into_noise() {
+        // A sine of 1.3 LSB: plain rounding turns it into a stepped wave rich in odd harmonics
+        let sine: Vec<f32> = (0..16000).map(|i| 1.3 * (2.0 * PI * 100.0 * i as f64 / 16000.0).sin() as f32 / i16::MAX as f32).collect();
+        let rounded: Vec<i16> = sine.iter().map(|&sample| (sample * i16::MAX as f32).round() as i16).collect();
+        let mut dither = TpdfDither::default();
+        let dithered: Vec<i16> = sine.iter().map(|&sample| dither.quantize(sample)).collect();
+
+        let third_harmonic = |samples: &[i16]| energy_at(samples, 300.0, 16000.0);
+        assert!(third_harmonic(&dithered) * 4.0 < third_harmonic(&rounded),
+            "third harmonic {:.0} dithered, {:.0} rounded", third_harmonic(&dithered), third_harmonic(&rounded));
+        // The tone itself survives
+        let fundamental = energy_at(&dithered, 100.0, 16000.0) / (1.3 * 15000.0 / 2.0);
+        assert!((fundamental - 1.0).abs() < 0.1, "fundamental at {:.2} of its level", fundamental);
+    }
+}
@@ -0,0 +1,116 @@
+// Self-contained HTML transcript: every segment's audio is embedded as a WAV data
+// URI, so the file can be shared and played offline without any other assets.
+
+use crate::utils;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TranscriptSegment {
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
+    #[serde(alias = "transcription", default)]
+    pub text: String,
+    pub audio_base64: Option<String>, // WAV, standard or URL-safe base64
+}
+
+pub fn render_html(title: &str, segments: &[TranscriptSegment]) -> Result<String, String> {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(title)));
+    html.push_str(concat!(
+        "<style>\n",
+        "body { font-family: -apple-system, 'Segoe UI', Roboto, sans-serif; max-width: 48rem; margin: 2rem auto; padding: 0 1rem; color: #222; }\n",
+        ".segment { margin: 1.5rem 0; }\n",
+        ".time { font: inherit; color: #0366d6; background: none; border: none; padding: 0; cursor: pointer; }\n",
+        "audio { display: block; width: 100%; margin-top: 0.5rem; }\n",
+        "</style>\n",
+        "</head>\n<body>\n",
+    ));
+    html.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+
+    for (index, segment) in segments.iter().enumerate() {
+        html.push_str(&format!("<div class=\"segment\" id=\"segment-{}\">\n", index + 1));
+        html.push_str(&format!(
+            "<button class=\"time\" onclick=\"document.getElementById('audio-{}').play()\">{} &ndash; {}</button>\n",
+            index + 1,
+            format_timestamp(segment.start_time_seconds),
+            format_timestamp(segment.end_time_seconds),
+        ));
+        html.push_str(&format!("<p>{}</p>\n", escape(&segment.text)));
+
+        if let Some(audio_base64) = segment.audio_base64.as_deref().filter(|audio| !audio.is_empty()) {
+            // Data URIs need the standard alphabet, so normalize URL-safe input
+            let audio_bytes = utils::decode_b64(audio_base64)
+                .map_err(|e| format!("Segment {} has invalid audio: {}", index + 1, e))?;
+            html.push_str(&format!(
+                "<audio id=\"audio-{}\" controls preload=\"none\" src=\"data:audio/wav;base64,{}\"></audio>\n",
+                index + 1,
+                utils::encode_wav_b64(&audio_bytes),
+            ));
+        }
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+fn format_timestamp(seconds: f64) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, minutes, secs) = (total_ms / 3_600_000, total_ms / 60_000 % 60, total_ms / 1000 % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}", minutes, secs)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base64::Engine;
+
+    #[test]
+    fn every_segment_gets_playable_audio() {
+        // Bytes whose URL-safe encoding differs from the standard one
+        let wav = [0xfb, 0xff, 1, 2, 3];
+        let url_safe = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(wav);
+        let segments = vec![
+            TranscriptSegment { start_time_seconds: 1.0, end_time_seconds: 2.5, text: "Hi <b>".to_string(), audio_base64: Some(url_safe.clone()) },
+            TranscriptSegment { start_time_seconds: 3661.0, end_time_seconds: 3662.0, text: "Bye".to_string(), audio_base64: Some(url_safe) },
+            TranscriptSegment { start_time_seconds: 4000.0, end_time_seconds: 4001.0, text: "No audio".to_string(), audio_base64: None },
+        ];
+        let html = render_html("A & B", &segments).unwrap();
+
+        assert_eq!(html.matches("<audio ").count(), 2);
+        for data in html.split("src=\"data:audio/wav;base64,").skip(1) {
+            let encoded = &data[..data.find('"').unwrap()];
+            assert_eq!(base64::engine::general_purpose::STANDARD.decode(encoded).unwrap(), wav);
+        }
+        assert!(html.contains("<title>A &amp; B</title>"));
+        assert!(html.contains("<p>Hi &lt;b&gt;</p>"));
+        assert!(html.contains("00:01 &ndash; 00:02"));
+        assert!(html.contains("1:01:01 &ndash; 1:01:02"));
+    }
+
+    #[test]
+    fn invalid_audio_is_an_error() {
+        let segments = vec![TranscriptSegment { start_time_seconds: 0.0, end_time_seconds: 1.0, text: "x".to_string(), audio_base64: Some("not base64!".to_string()) }];
+        let error = render_html("t", &segments).unwrap_err();
+        assert!(error.starts_with("Segment 1 has invalid audio"), "{}", error);
+    }
+
+    #[test]
+    fn segments_accept_the_transcription_field() {
+        let segment: TranscriptSegment = serde_json::from_str(r#"{"start_time_seconds":0,"end_time_seconds":1,"transcription":"x","audio_base64":null}"#).unwrap();
+        assert_eq!(segment.text, "x");
+    }
+}
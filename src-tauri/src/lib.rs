@@ -1,10 +1,40 @@
 // Modules
-mod audio_processing;
+mod audio_cache;
+pub mod audio_processing;
+mod dsp;
+mod html_export;
+mod live;
+mod segment_cache;
+mod segment_snapshot;
+mod spectrogram;
+mod transcript;
+mod transcription;
+mod upload;
 mod utils;
+mod wav;
 
-use audio_processing::{AudioProcessor, AudioSegment};
+use audio_cache::DecodedAudioCache;
+use audio_processing::debug_log;
+use audio_processing::{AdaptiveThreshold, AudioMetadata, AudioProcessor, AudioSegment, ChannelMixMode, CoverArt, DecodeError, Headroom, PipelineTimings, ProcessingCancelled, ProcessingReport, ProcessingWarning, ProcessingStatus, SegmentInfo, SegmentUpdate, VadBackend, VadConfig, WavBitDepth, SUPPORTED_FORMATS};
+use transcript::{Cue, HallucinationFilter, WordTiming};
+use transcription::{Backend, ClientConfig, FormLayout, HttpClient, RequestOptions, RetryPolicy, Transcription};
+use dsp::EqBand;
+use html_export::TranscriptSegment;
+use live::{LiveSegment, LiveSession};
+use segment_cache::SegmentCache;
+use segment_snapshot::SnapshotSegment;
+use spectrogram::{ColorMap, FrequencyScale};
+use upload::ChunkedUpload;
+use utils::Base64Variant;
 use serde::{Serialize, Deserialize};
-use tauri::Emitter;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ProgressUpdate {
@@ -13,104 +43,263 @@ pub struct ProgressUpdate {
     pub details: Option<String>,
 }
 
+// Segment boundaries streamed to the UI while processing is still running
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SegmentFound {
+    pub index: usize,
+    pub start_sample: i64,
+    pub end_sample: i64,
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
+    pub channel: Option<u8>,
+}
+
+impl SegmentFound {
+    fn new(index: usize, segment: &AudioSegment) -> Self {
+        Self {
+            index,
+            start_sample: segment.start_sample,
+            end_sample: segment.end_sample,
+            start_time_seconds: segment.start_time_seconds,
+            end_time_seconds: segment.end_time_seconds,
+            channel: segment.channel,
+        }
+    }
+}
+
+// Segments of several files processed as one timeline, with that timeline's length
+#[derive(Serialize)]
+pub struct ConcatenatedReport {
+    #[serde(flatten)]
+    pub report: ProcessingReport,
+    pub duration_seconds: f64,
+}
+
+// Segment audio made louder for playback by apply_gain_to_segment
+#[derive(Serialize)]
+pub struct BoostedAudio {
+    pub audio_base64: String,
+    // Samples that hit the i16 limits; more than a few means the gain is too high
+    pub clipped_samples: usize,
+}
+
+// Audio of one segment to transcribe in a batch
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SegmentAudio {
+    pub index: usize,
+    pub audio_base64: String,
+    // Where the segment starts in the source file, so word times can be made absolute
+    #[serde(default)]
+    pub start_time_seconds: f64,
+    // Context the audio carries before start_time_seconds, if it was padded
+    #[serde(default)]
+    pub padding_seconds: f64,
+    // Estimated SNR from process_audio_vad, checked against transcribe_segments' min_snr_db
+    #[serde(default)]
+    pub snr_db: Option<f64>,
+}
+
+impl SegmentAudio {
+    // Length of the segment's WAV audio, 0 if it isn't a WAV
+    fn audio_seconds(&self) -> f64 {
+        utils::decode_b64(&self.audio_base64).ok()
+            .and_then(|bytes| hound::WavReader::new(std::io::Cursor::new(bytes)).ok())
+            .map(|reader| reader.duration() as f64 / reader.spec().sample_rate as f64)
+            .unwrap_or(0.0)
+    }
+}
+
+// Throughput of a transcribe_segments run, returned with its transcriptions and emitted
+// as "transcription-stats" once the run ends
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchStats {
+    pub audio_seconds: f64, // Audio of the segments transcribed successfully
+    pub wall_seconds: f64,
+    pub requests: usize, // Retries and fallback requests included
+    pub retries: usize,  // Requests beyond the first for each segment
+    pub average_latency_seconds: f64, // Per segment, from its first request to its result
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TranscribedBatch {
+    pub transcriptions: Vec<Transcription>,
+    pub stats: BatchStats,
+}
+
+// Emitted by transcribe_segments after each segment, successful or not
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TranscriptionProgress {
+    pub segment_index: usize,
+    pub progress: f64, // 0.0 to 100.0
+    pub transcription: Option<Transcription>,
+    pub error: Option<String>,
+    pub skipped: Option<String>, // Why the segment wasn't sent, e.g. too low an SNR
+}
+
+// Identifies this app to transcription gateways unless the caller overrides it
+const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+// Transcription backend chosen for the current session via set_active_model
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ActiveModel {
+    pub model_name: String,
+    pub base_url: String,
+    pub form_layout: FormLayout,
+}
+
+#[derive(Default)]
+pub struct BackendState {
+    active_model: Mutex<Option<ActiveModel>>,
+    // Cancel flags of running process_audio_vad calls, by frontend session id
+    processing_sessions: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    // Live capture sessions fed by append_pcm_frames, by frontend session id
+    live_sessions: Mutex<HashMap<String, LiveSession>>,
+    // Decoded audio shared by process_audio_vad runs, see audio_cache
+    decoded_audio: Arc<Mutex<DecodedAudioCache>>,
+    // Transcription clients by user agent and timeouts
+    http_clients: Mutex<HashMap<ClientConfig, HttpClient>>,
+    // Directory chosen with set_temp_dir, None for the default
+    temp_dir: Mutex<Option<std::path::PathBuf>>,
+}
+
+impl BackendState {
+    // Backend used by later transcriptions that don't name a model or base URL
+    fn set_active_model(&self, model_name: String, base_url: &str, form_layout: FormLayout) -> Result<(), String> {
+        // Reject unusable layouts up front rather than on the first transcription
+        form_layout.fields()?;
+        let base_url = transcription::normalize_base_url(base_url)?;
+
+        let mut active_model = self.active_model.lock().map_err(|e| format!("Failed to lock backend state: {}", e))?;
+        *active_model = Some(ActiveModel { model_name, base_url, form_layout });
+        Ok(())
+    }
+
+    // Temp files written for uploads; only these may be deleted when processing is cancelled
+    fn temp_audio_dir(&self) -> std::path::PathBuf {
+        self.temp_dir.lock().unwrap().clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("transcriber_audio"))
+    }
+}
+
+// Create `dir` if needed and make sure files can be written to it
+fn check_writable_dir(dir: &std::path::Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create temp directory {}: {}", dir.display(), e))?;
+    let probe = dir.join(format!(".write_test_{}", uuid::Uuid::new_v4()));
+    std::fs::write(&probe, b"")
+        .map_err(|e| format!("Temp directory {} is not writable: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(probe);
+    Ok(())
+}
+
+// Error text for the frontend: DecodeError messages explain themselves, anything
+// else gets `context` in front
+fn describe_error(context: &str, e: Box<dyn std::error::Error>) -> String {
+    match e.downcast_ref::<DecodeError>() {
+        Some(decode_error) => decode_error.to_string(),
+        None => format!("{}: {}", context, e),
+    }
+}
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+// An uploaded file converted for the app, with anything found wrong while decoding it
+#[derive(Debug, Serialize)]
+pub struct SavedAudio {
+    pub path: String,
+    pub warnings: Vec<ProcessingWarning>,
+}
+
 #[tauri::command]
-async fn save_audio_file_chunked(chunk_data: Vec<u8>, chunk_index: usize, total_chunks: usize, filename: String, session_id: String) -> Result<String, String> {
+async fn save_audio_file_chunked(chunk_data: Vec<u8>, chunk_index: usize, total_chunks: usize, filename: String, session_id: String, state: tauri::State<'_, BackendState>) -> Result<Option<SavedAudio>, String> {
+    store_upload_chunk(&state.temp_audio_dir(), &chunk_data, chunk_index, total_chunks, &filename, &session_id)
+}
+
+// Store one chunk of an upload. The chunk that completes the set, whichever index it
+// has, assembles the file and converts it to a 16kHz WAV; None while chunks are missing.
+fn store_upload_chunk(temp_dir: &std::path::Path, chunk_data: &[u8], chunk_index: usize, total_chunks: usize, filename: &str, session_id: &str) -> Result<Option<SavedAudio>, String> {
     use std::fs;
-    use std::env;
-    use std::fs::OpenOptions;
-    use std::io::Write;
     
-    // Create a temporary directory for audio files
-    let temp_dir = env::temp_dir().join("transcriber_audio");
-    if !temp_dir.exists() {
-        fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    upload::check_session_id(session_id)?;
+    // Chunks are stored separately so retries and out-of-order delivery can't corrupt the file
+    let upload = ChunkedUpload::new(temp_dir.to_path_buf(), session_id.to_string(), total_chunks);
+    upload.store(chunk_index, chunk_data)?;
+    
+    if !upload.missing().is_empty() {
+        return Ok(None);
     }
     
     // Create session-based filename
     let temp_filename = format!("{}_{}", session_id, filename);
     let temp_path = temp_dir.join(temp_filename);
+    upload.assemble(&temp_path)?;
     
-    // Append chunk to file
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&temp_path)
-        .map_err(|e| format!("Failed to open temp file: {}", e))?;
-    
-    file.write_all(&chunk_data).map_err(|e| format!("Failed to write chunk: {}", e))?;
-    file.flush().map_err(|e| format!("Failed to flush file: {}", e))?;
+    // Convert to 16kHz WAV format
+    let mut processor = AudioProcessor::new();
+    let (audio_samples, original_sample_rate) = processor.decode_audio_symphonia(&temp_path.to_string_lossy())
+        .map_err(|e| describe_error("Failed to decode audio", e))?;
+    // Damaged uploads are still converted, but shouldn't go unnoticed
+    let warnings = processor.take_warnings();
+    for warning in &warnings {
+        debug_log!("Warning for {}: {}", filename, warning.message);
+    }
     
-    // If this is the last chunk, process the complete file
-    if chunk_index == total_chunks - 1 {
-        // Convert to 16kHz WAV format
-        let mut processor = AudioProcessor::new();
-        let (audio_samples, original_sample_rate) = processor.decode_audio_symphonia(&temp_path.to_string_lossy())
-            .map_err(|e| format!("Failed to decode audio: {}", e))?;
-        
-        // Resample to 16kHz if needed
-        let target_sample_rate = 16000;
-        let resampled_audio = if original_sample_rate != target_sample_rate {
-            processor.resample_audio(&audio_samples, original_sample_rate, target_sample_rate)
-                .map_err(|e| format!("Failed to resample audio: {}", e))?
-        } else {
-            audio_samples
-        };
-        
-        // Create the final processed filename
-        let uuid = uuid::Uuid::new_v4();
-        let processed_filename = format!("{}_processed.wav", uuid);
-        let processed_path = temp_dir.join(processed_filename);
-        
-        // Save as WAV with 16kHz
-        let wav_data = processor.samples_to_wav_bytes(&resampled_audio, target_sample_rate)
-            .map_err(|e| format!("Failed to create WAV data: {}", e))?;
-        
-        fs::write(&processed_path, wav_data).map_err(|e| format!("Failed to write processed file: {}", e))?;
-        
-        // Clean up the original temporary file
-        let _ = fs::remove_file(temp_path);
-        
-        Ok(processed_path.to_string_lossy().to_string())
+    // Resample to 16kHz if needed
+    let target_sample_rate = 16000;
+    let resampled_audio = if original_sample_rate != target_sample_rate {
+        processor.resample_audio(&audio_samples, original_sample_rate, target_sample_rate)
+            .map_err(|e| format!("Failed to resample audio: {}", e))?
     } else {
-        // Return temporary status for intermediate chunks
-        Ok(format!("chunk_{}_of_{}_received", chunk_index + 1, total_chunks))
-    }
+        audio_samples
+    };
+    
+    // Create the final processed filename
+    let uuid = uuid::Uuid::new_v4();
+    let processed_filename = format!("{}_processed.wav", uuid);
+    let processed_path = temp_dir.join(processed_filename);
+    
+    // Save as WAV with 16kHz
+    let wav_data = processor.samples_to_wav_bytes(&resampled_audio, target_sample_rate)
+        .map_err(|e| format!("Failed to create WAV data: {}", e))?;
+    
+    fs::write(&processed_path, wav_data).map_err(|e| format!("Failed to write processed file: {}", e))?;
+    
+    // Clean up the original temporary file
+    let _ = fs::remove_file(temp_path);
+    
+    Ok(Some(SavedAudio { path: processed_path.to_string_lossy().to_string(), warnings }))
 }
 
 #[tauri::command]
-async fn save_audio_file(file_data: Vec<u8>, filename: String) -> Result<String, String> {
+async fn save_audio_file(file_data: Vec<u8>, filename: String, state: tauri::State<'_, BackendState>) -> Result<SavedAudio, String> {
     use std::fs;
-    use std::env;
     use std::io::Cursor;
     
     // Create a temporary directory for audio files
-    let temp_dir = env::temp_dir().join("transcriber_audio");
+    let temp_dir = state.temp_audio_dir();
     if !temp_dir.exists() {
         fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
     }
     
-    // Create a unique filename for the original file
     let uuid = uuid::Uuid::new_v4();
     let file_extension = std::path::Path::new(&filename)
         .extension()
         .and_then(|ext| ext.to_str())
-        .unwrap_or("wav");
-    let original_temp_filename = format!("{}_original.{}", uuid, file_extension);
-    let original_temp_path = temp_dir.join(original_temp_filename);
-    
-    // Save the original file temporarily
-    fs::write(&original_temp_path, file_data).map_err(|e| format!("Failed to write original file: {}", e))?;
+        .filter(|ext| audio_processing::is_supported_format(ext));
     
-    // Convert to 16kHz MP3 using the audio processor
+    // Convert to 16kHz MP3 using the audio processor, straight from the uploaded bytes
     let mut processor = AudioProcessor::new();
-    let (audio_samples, original_sample_rate) = processor.decode_audio_symphonia(&original_temp_path.to_string_lossy())
-        .map_err(|e| format!("Failed to decode audio: {}", e))?;
+    let (audio_samples, original_sample_rate) = processor.decode_audio_bytes(&file_data, file_extension)
+        .map_err(|e| describe_error("Failed to decode audio", e))?;
+    // Damaged uploads are still converted, but shouldn't go unnoticed
+    let warnings = processor.take_warnings();
+    for warning in &warnings {
+        debug_log!("Warning for {}: {}", filename, warning.message);
+    }
     
     // Resample to 16kHz if needed
     let target_sample_rate = 16000;
@@ -125,28 +314,73 @@ async fn save_audio_file(file_data: Vec<u8>, filename: String) -> Result<String,
     let mp3_filename = format!("{}.mp3", uuid);
     let mp3_path = temp_dir.join(mp3_filename);
     
-    // Save as MP3 (for now we'll save as WAV since we don't have MP3 encoder, but with 16kHz)
-    // TODO: Add proper MP3 encoding library
-    let wav_data = processor.samples_to_wav_bytes(&resampled_audio, target_sample_rate)
-        .map_err(|e| format!("Failed to create WAV data: {}", e))?;
+    // Speech at 16kHz mono stays intelligible at 64 kbps
+    let mp3_data = processor.samples_to_mp3_bytes(&resampled_audio, target_sample_rate, 64)
+        .map_err(|e| format!("Failed to encode MP3: {}", e))?;
     
-    fs::write(&mp3_path, wav_data).map_err(|e| format!("Failed to write processed file: {}", e))?;
+    fs::write(&mp3_path, mp3_data).map_err(|e| format!("Failed to write processed file: {}", e))?;
     
-    // Clean up the original temporary file
-    let _ = fs::remove_file(original_temp_path);
-    
-    Ok(mp3_path.to_string_lossy().to_string())
+    Ok(SavedAudio { path: mp3_path.to_string_lossy().to_string(), warnings })
 }
 
+// Native file picker limited to formats the pipeline can decode; None when cancelled
 #[tauri::command]
-async fn select_audio_file() -> Result<Option<String>, String> {
-    // For now, return None since we need to implement this properly
-    // This is a placeholder that can be expanded later
-    Ok(None)
+async fn select_audio_file(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
+    // pick_file reports through a callback instead of blocking this task
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app_handle.dialog().file()
+        .set_title("Select audio file")
+        .add_filter("Audio", SUPPORTED_FORMATS)
+        .pick_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+
+    let Some(file_path) = rx.await.map_err(|_| "File dialog closed unexpectedly".to_string())? else {
+        return Ok(None);
+    };
+    let path = file_path.into_path()
+        .map_err(|e| format!("Selected file is not a local path: {}", e))?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+// Segment cache lives in the app cache dir so the OS may reclaim it
+fn segment_cache(app_handle: &tauri::AppHandle) -> Result<SegmentCache, String> {
+    let cache_dir = app_handle.path().app_cache_dir()
+        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?;
+    Ok(SegmentCache::new(cache_dir.join("segments")))
 }
 
 #[tauri::command]
-async fn process_audio_vad(file_path: String, app_handle: tauri::AppHandle) -> Result<Vec<AudioSegment>, String> {
+#[allow(clippy::too_many_arguments)] // Each option is a separate named argument on the JS side
+async fn process_audio_vad(
+    file_path: String,
+    detect_dual_mono: Option<bool>,
+    channel_mix: Option<ChannelMixMode>,
+    vad_sample_rate: Option<u32>,
+    vad_config: Option<VadConfig>,
+    vad_backend: Option<VadBackend>,
+    merge_gap_seconds: Option<f64>,
+    crossfade_merges: Option<bool>,
+    min_segment_duration_seconds: Option<f64>,
+    adaptive_threshold: Option<bool>,
+    adaptation_rate: Option<f32>,
+    noise_floor_threshold: Option<bool>,
+    stream_segments: Option<bool>,
+    eq_bands: Option<Vec<EqBand>>,
+    skip_silence: Option<bool>,
+    trim_silence: Option<bool>,
+    normalize_peak_dbfs: Option<f32>,
+    lazy_segment_audio: Option<bool>,
+    preserve_original_rate: Option<bool>,
+    dither: Option<bool>,
+    max_segment_seconds: Option<f64>,
+    split_overlap_seconds: Option<f64>,
+    per_channel: Option<bool>,
+    probability_resolution_ms: Option<u32>,
+    session_id: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<ProcessingReport, String> {
     // Check if file exists
     if !std::path::Path::new(&file_path).exists() {
         return Err(format!("File not found: {}", file_path));
@@ -166,82 +400,1026 @@ async fn process_audio_vad(file_path: String, app_handle: tauri::AppHandle) -> R
         }
     };
 
+    // Optionally stream segments as VAD finds them; "segments-merged" then replaces them
+    // with the final set, which is also what this command returns
+    let segment_callback = |update: SegmentUpdate| {
+        let result = match update {
+            SegmentUpdate::Found(index, segment) => app_handle.emit("segment-found", SegmentFound::new(index, segment)),
+            SegmentUpdate::Merged(segments) => {
+                let merged: Vec<SegmentFound> = segments.iter().enumerate()
+                    .map(|(index, segment)| SegmentFound::new(index, segment))
+                    .collect();
+                app_handle.emit("segments-merged", merged)
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to emit segment event: {}", e);
+        }
+    };
+
     // Process the audio file with progress reporting
     let mut processor = AudioProcessor::new();
+    processor.set_detect_dual_mono(detect_dual_mono.unwrap_or(true));
+    processor.set_channel_mix_mode(channel_mix.unwrap_or_default());
+    processor.set_sample_rate(vad_sample_rate.unwrap_or(16000))?;
+    processor.set_vad_config(vad_config.unwrap_or_default())?;
+    processor.set_vad_backend(vad_backend.unwrap_or_default());
+    if let Some(gap) = merge_gap_seconds {
+        if !gap.is_finite() || gap < 0.0 {
+            return Err(format!("Merge gap must be a non-negative number of seconds, got {}", gap));
+        }
+        processor.set_merge_gap(gap);
+    }
+    processor.set_crossfade_merges(crossfade_merges.unwrap_or(false));
+    if let Some(min_duration) = min_segment_duration_seconds {
+        if !min_duration.is_finite() || min_duration < 0.0 {
+            return Err(format!("Minimum segment duration must be a non-negative number of seconds, got {}", min_duration));
+        }
+        processor.set_min_segment_duration(min_duration);
+    }
+    if adaptive_threshold.unwrap_or(false) {
+        let mut adaptive = AdaptiveThreshold::default();
+        if let Some(rate) = adaptation_rate {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!("Adaptation rate must be between 0.0 and 1.0, got {}", rate));
+            }
+            adaptive.adaptation_rate = rate;
+        }
+        processor.set_adaptive_threshold(Some(adaptive));
+    }
+    processor.set_noise_floor_threshold(noise_floor_threshold.unwrap_or(false));
+    processor.set_eq_bands(eq_bands.unwrap_or_default());
+    processor.set_skip_silence(skip_silence.unwrap_or(false));
+    processor.set_trim_silence(trim_silence.unwrap_or(false));
+    processor.set_normalization(normalize_peak_dbfs)?;
+    processor.set_lazy_segment_audio(lazy_segment_audio.unwrap_or(false));
+    processor.set_preserve_original_rate(preserve_original_rate.unwrap_or(false));
+    processor.set_dither(dither.unwrap_or(false));
+    processor.set_segment_splitting(max_segment_seconds, split_overlap_seconds.unwrap_or(0.5))?;
+    processor.set_probability_resolution(probability_resolution_ms)?;
+    match segment_cache(&app_handle) {
+        Ok(cache) => processor.set_segment_cache(Some(cache)),
+        Err(e) => eprintln!("Processing without segment cache: {}", e),
+    }
+    processor.set_decoded_audio_cache(Some(state.decoded_audio.clone()));
+
+    // Register a cancel flag so cancel_processing can stop this run
+    if let Some(session_id) = &session_id {
+        let flag = Arc::new(AtomicBool::new(false));
+        state.processing_sessions.lock().unwrap().insert(session_id.clone(), flag.clone());
+        processor.set_cancel_flag(Some(flag));
+    }
     
-    match processor.process_audio_file_with_progress(&file_path, "mock_model_path", progress_callback) {
-        Ok(segments) => {
-            // Final progress update
-            progress_callback("Processing complete", 100.0, Some(&format!("Found {} speech segments", segments.len())));
-            Ok(segments)
+    // Per-channel mode keeps one speaker per channel apart instead of mixing them
+    let result = if per_channel.unwrap_or(false) {
+        processor.process_audio_file_stereo(&file_path, progress_callback, segment_callback)
+    } else if stream_segments.unwrap_or(false) {
+        processor.process_audio_file_streaming(&file_path, progress_callback, segment_callback)
+    } else {
+        processor.process_audio_file_with_progress(&file_path, "mock_model_path", progress_callback)
+    };
+
+    if let Some(session_id) = &session_id {
+        state.processing_sessions.lock().unwrap().remove(session_id);
+    }
+
+    match ProcessingReport::from_result(result) {
+        Ok(mut report) => {
+            report.speech_probabilities = processor.take_speech_probabilities();
+            report.warnings = processor.take_warnings();
+            // Final progress update, saying why nothing was found if that's the case
+            let (step, details) = match report.status {
+                ProcessingStatus::Speech => ("Processing complete", format!("Found {} speech segments", report.segments.len())),
+                ProcessingStatus::NoSpeech => ("No speech detected", "The audio decoded fine but contains no speech".to_string()),
+                ProcessingStatus::NoAudio => ("No audio found", "The file contains no decodable audio".to_string()),
+            };
+            progress_callback(step, 100.0, Some(&details));
+            Ok(report)
+        },
+        Err(e) if e.is::<ProcessingCancelled>() => {
+            // The uploaded copy is useless once its run is abandoned; never touch files elsewhere
+            let path = std::path::Path::new(&file_path);
+            if path.starts_with(state.temp_audio_dir()) {
+                let _ = std::fs::remove_file(path);
+            }
+            Err(e.to_string())
         },
-        Err(e) => Err(format!("Error processing audio file: {}", e))
+        Err(e) => Err(describe_error("Error processing audio file", e))
+    }
+}
+
+/// Ask a running process_audio_vad call to stop, and delete any partial upload for the
+/// session. Returns true if a running call was signalled.
+#[tauri::command]
+async fn cancel_processing(session_id: String, state: tauri::State<'_, BackendState>) -> Result<bool, String> {
+    upload::check_session_id(&session_id)?;
+    let signalled = match state.processing_sessions.lock().unwrap().get(&session_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    };
+
+    // Parts save_audio_file_chunked stored before the cancel, if it came between chunks
+    ChunkedUpload::in_progress(state.temp_audio_dir(), session_id).discard();
+
+    Ok(signalled)
+}
+
+fn emit_live_segments(app_handle: &tauri::AppHandle, segments: &[LiveSegment]) {
+    for segment in segments {
+        if let Err(e) = app_handle.emit("live-segment", segment) {
+            eprintln!("Failed to emit live segment: {}", e);
+        }
+    }
+}
+
+// Feed captured PCM to a live session, creating it on first use. Each segment is
+// emitted as "live-segment" as soon as its speech ends; returns how many ended here.
+#[tauri::command]
+async fn append_pcm_frames(
+    session_id: String,
+    samples: Vec<i16>,
+    sample_rate: u32,
+    vad_config: Option<VadConfig>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<usize, String> {
+    let mut sessions = state.live_sessions.lock().map_err(|e| format!("Failed to lock live sessions: {}", e))?;
+    let session = match sessions.entry(session_id.clone()) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            entry.insert(LiveSession::new(session_id.clone(), sample_rate, vad_config.unwrap_or_default())?)
+        }
+    };
+    if session.input_rate() != sample_rate {
+        return Err(format!("Live session {} was started at {} Hz, got frames at {} Hz", session_id, session.input_rate(), sample_rate));
+    }
+
+    let segments = session.append(&samples)?;
+    emit_live_segments(&app_handle, &segments);
+    Ok(segments.len())
+}
+
+// Close a live session, emitting whatever speech was still open
+#[tauri::command]
+async fn finalize_pcm_session(
+    session_id: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<usize, String> {
+    let session = state.live_sessions.lock()
+        .map_err(|e| format!("Failed to lock live sessions: {}", e))?
+        .remove(&session_id)
+        .ok_or_else(|| format!("No live session {}", session_id))?;
+
+    let segments = session.finish()?;
+    emit_live_segments(&app_handle, &segments);
+    Ok(segments.len())
+}
+
+#[tauri::command]
+async fn clear_processing_cache(app_handle: tauri::AppHandle) -> Result<usize, String> {
+    segment_cache(&app_handle)?
+        .clear()
+        .map_err(|e| format!("Failed to clear processing cache: {}", e))
+}
+
+/// Drop all decoded audio kept in memory, returning how many bytes were freed
+#[tauri::command]
+async fn clear_audio_cache(state: tauri::State<'_, BackendState>) -> Result<usize, String> {
+    let freed = state.decoded_audio.lock().unwrap().clear();
+    debug_log!("Cleared decoded audio cache ({} bytes)", freed);
+    Ok(freed)
+}
+
+/// Write uploads and other temporary audio to `path` instead of the system temp
+/// directory, e.g. when that is a small tmpfs; None goes back to the default. The
+/// directory is created if needed and must be writable.
+#[tauri::command]
+async fn set_temp_dir(path: Option<String>, state: tauri::State<'_, BackendState>) -> Result<(), String> {
+    let dir = match path {
+        Some(path) => {
+            let dir = std::path::PathBuf::from(path.trim());
+            if !dir.is_absolute() {
+                return Err(format!("Temp directory must be an absolute path, got '{}'", path));
+            }
+            check_writable_dir(&dir)?;
+            Some(dir)
+        }
+        None => None,
+    };
+    *state.temp_dir.lock().unwrap() = dir;
+    Ok(())
+}
+
+/// Limit the memory kept for decoded audio; 0 disables the cache
+#[tauri::command]
+async fn set_audio_cache_size(max_bytes: usize, state: tauri::State<'_, BackendState>) -> Result<(), String> {
+    state.decoded_audio.lock().unwrap().set_max_bytes(max_bytes);
+    Ok(())
+}
+
+/// Print audio pipeline diagnostics to the console; off by default in release builds
+#[tauri::command]
+async fn set_debug_logging(enabled: bool) -> Result<(), String> {
+    audio_processing::set_debug_logging(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+async fn benchmark_pipeline(file_path: String) -> Result<PipelineTimings, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let mut processor = AudioProcessor::new();
+    processor.benchmark_pipeline(&file_path)
+        .map_err(|e| describe_error("Error benchmarking pipeline", e))
+}
+
+// Preview how many segments a file has and how long they are, without extracting their
+// audio. Shares the decoded-audio and segment caches with process_audio_vad, so a full
+// run with the same settings afterwards skips decoding and VAD.
+#[tauri::command]
+async fn estimate_segments(
+    file_path: String,
+    vad_config: Option<VadConfig>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<Vec<SegmentInfo>, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let mut processor = AudioProcessor::new();
+    processor.set_vad_config(vad_config.unwrap_or_default())?;
+    processor.set_decoded_audio_cache(Some(state.decoded_audio.clone()));
+    if let Ok(cache) = segment_cache(&app_handle) {
+        processor.set_segment_cache(Some(cache));
+    }
+    processor.estimate_segments(&file_path)
+        .map_err(|e| describe_error("Error estimating segments", e))
+}
+
+// Fixed-length chunks on a regular grid, for content where VAD isn't wanted
+#[tauri::command]
+async fn chunk_audio_fixed(
+    file_path: String,
+    chunk_seconds: f64,
+    overlap_seconds: Option<f64>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<Vec<AudioSegment>, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let progress_callback = |step: &str, progress: f64, details: Option<&str>| {
+        let update = ProgressUpdate {
+            step: step.to_string(),
+            progress,
+            details: details.map(|s| s.to_string()),
+        };
+        if let Err(e) = app_handle.emit("audio-processing-progress", &update) {
+            eprintln!("Failed to emit progress event: {}", e);
+        }
+    };
+
+    let mut processor = AudioProcessor::new();
+    processor.set_decoded_audio_cache(Some(state.decoded_audio.clone()));
+    processor.chunk_audio_fixed(&file_path, chunk_seconds, overlap_seconds.unwrap_or(0.0), progress_callback)
+        .map_err(|e| describe_error("Error chunking audio file", e))
+}
+
+// Process a recording split across several files, e.g. a meeting exported in parts, as
+// one file. Segment times run on across file boundaries in the order the files are given.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Each option is a separate named argument on the JS side
+async fn concat_and_process(
+    file_paths: Vec<String>,
+    channel_mix: Option<ChannelMixMode>,
+    vad_sample_rate: Option<u32>,
+    vad_config: Option<VadConfig>,
+    vad_backend: Option<VadBackend>,
+    merge_gap_seconds: Option<f64>,
+    session_id: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<ConcatenatedReport, String> {
+    if let Some(missing) = file_paths.iter().find(|file_path| !std::path::Path::new(file_path).exists()) {
+        return Err(format!("File not found: {}", missing));
+    }
+
+    let progress_callback = |step: &str, progress: f64, details: Option<&str>| {
+        let update = ProgressUpdate {
+            step: step.to_string(),
+            progress,
+            details: details.map(|s| s.to_string()),
+        };
+        if let Err(e) = app_handle.emit("audio-processing-progress", &update) {
+            eprintln!("Failed to emit progress event: {}", e);
+        }
+    };
+
+    let mut processor = AudioProcessor::new();
+    processor.set_channel_mix_mode(channel_mix.unwrap_or_default());
+    processor.set_sample_rate(vad_sample_rate.unwrap_or(16000))?;
+    processor.set_vad_config(vad_config.unwrap_or_default())?;
+    processor.set_vad_backend(vad_backend.unwrap_or_default());
+    if let Some(gap) = merge_gap_seconds {
+        if !gap.is_finite() || gap < 0.0 {
+            return Err(format!("Merge gap must be a non-negative number of seconds, got {}", gap));
+        }
+        processor.set_merge_gap(gap);
+    }
+    processor.set_decoded_audio_cache(Some(state.decoded_audio.clone()));
+
+    if let Some(session_id) = &session_id {
+        let flag = Arc::new(AtomicBool::new(false));
+        state.processing_sessions.lock().unwrap().insert(session_id.clone(), flag.clone());
+        processor.set_cancel_flag(Some(flag));
+    }
+
+    let file_paths: Vec<&str> = file_paths.iter().map(String::as_str).collect();
+    let mut duration_seconds = 0.0;
+    let result = processor.process_audio_files_concatenated(&file_paths, progress_callback)
+        .map(|(segments, duration)| {
+            duration_seconds = duration;
+            segments
+        });
+
+    if let Some(session_id) = &session_id {
+        state.processing_sessions.lock().unwrap().remove(session_id);
+    }
+
+    let mut report = ProcessingReport::from_result(result)
+        .map_err(|e| describe_error("Error processing audio files", e))?;
+    report.warnings = processor.take_warnings();
+    progress_callback("Processing complete", 100.0, Some(&format!("Found {} speech segments in {} files", report.segments.len(), file_paths.len())));
+    Ok(ConcatenatedReport { report, duration_seconds })
+}
+
+#[tauri::command]
+async fn probe_audio(file_path: String, measure_clipping: Option<bool>) -> Result<AudioMetadata, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let processor = AudioProcessor::new();
+    processor.probe_metadata(&file_path, measure_clipping.unwrap_or(false))
+        .map_err(|e| describe_error("Error probing audio file", e))
+}
+
+#[tauri::command]
+async fn get_headroom(file_path: String) -> Result<Headroom, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let processor = AudioProcessor::new();
+    processor.measure_headroom(&file_path)
+        .map_err(|e| describe_error("Error measuring headroom", e))
+}
+
+#[tauri::command]
+async fn get_cover_art(file_path: String) -> Result<Option<CoverArt>, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let processor = AudioProcessor::new();
+    processor.read_cover_art(&file_path)
+        .map_err(|e| describe_error("Error reading cover art", e))
+}
+
+// Save embedded cover art to output_path; returns false when the file has none
+#[tauri::command]
+async fn export_cover_art(file_path: String, output_path: String) -> Result<bool, String> {
+    let Some(cover_art) = get_cover_art(file_path).await? else {
+        return Ok(false);
+    };
+
+    std::fs::write(&output_path, &cover_art.bytes)
+        .map_err(|e| format!("Failed to write cover art: {}", e))?;
+    debug_log!("Saved {} cover art ({} bytes) to {}", cover_art.mime, cover_art.bytes.len(), output_path);
+    Ok(true)
+}
+
+// Full track as 16kHz mono WAV, the same audio the VAD and transcription see
+#[tauri::command]
+async fn export_processed_wav(file_path: String, output_path: String, overwrite: Option<bool>) -> Result<String, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+    if !overwrite.unwrap_or(false) && std::path::Path::new(&output_path).exists() {
+        return Err(format!("Output file already exists: {}", output_path));
+    }
+
+    let processor = AudioProcessor::new();
+    let (audio_samples, original_sample_rate) = processor.decode_audio_symphonia(&file_path)
+        .map_err(|e| describe_error("Failed to decode audio", e))?;
+
+    let target_sample_rate = 16000;
+    let resampled_audio = processor.resample_audio(&audio_samples, original_sample_rate, target_sample_rate)
+        .map_err(|e| format!("Failed to resample audio: {}", e))?;
+
+    processor.write_wav_to_path(&resampled_audio, target_sample_rate, std::path::Path::new(&output_path))
+        .map_err(|e| format!("Failed to write WAV file: {}", e))?;
+
+    debug_log!("Exported {:.1}s of processed audio to {}", resampled_audio.len() as f64 / target_sample_rate as f64, output_path);
+    Ok(output_path)
+}
+
+// Spectrogram of a time range as PNG bytes
+#[tauri::command]
+async fn render_spectrogram(
+    file_path: String,
+    start_time_seconds: f64,
+    end_time_seconds: f64,
+    width: u32,
+    height: u32,
+    frequency_scale: Option<FrequencyScale>,
+    color_map: Option<ColorMap>
+) -> Result<Vec<u8>, String> {
+    let path = std::path::Path::new(&file_path);
+    if !path.exists() {
+        return Err(format!("File not found: {}", file_path));
     }
+
+    let processor = AudioProcessor::new();
+    let (samples, sample_rate) = processor.extract_segment_from_file(path, start_time_seconds, end_time_seconds)
+        .map_err(|e| describe_error("Failed to extract segment", e))?;
+
+    spectrogram::render_png(
+        &samples,
+        sample_rate,
+        width,
+        height,
+        frequency_scale.unwrap_or_default(),
+        color_map.unwrap_or_default(),
+    )
+}
+
+// Most buckets waveform_peaks hands out; far more than any screen is wide
+const MAX_WAVEFORM_BUCKETS: usize = 1_000_000;
+
+// Min/max sample pairs across a file at 16kHz, for drawing its waveform. `buckets` sets
+// the resolution: each pair covers 1/buckets of the file, and the reply (and what it
+// takes to build) grows with it, so a few thousand are plenty for a full-width view.
+// Shares the decoded-audio cache with process_audio_vad, so whichever runs second
+// skips decoding.
+#[tauri::command]
+async fn waveform_peaks(
+    file_path: String,
+    buckets: usize,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<Vec<(i16, i16)>, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+    if buckets == 0 || buckets > MAX_WAVEFORM_BUCKETS {
+        return Err(format!("Bucket count must be between 1 and {}, got {}", MAX_WAVEFORM_BUCKETS, buckets));
+    }
+
+    let progress_callback = |step: &str, progress: f64, details: Option<&str>| {
+        let update = ProgressUpdate {
+            step: step.to_string(),
+            progress,
+            details: details.map(|s| s.to_string()),
+        };
+        if let Err(e) = app_handle.emit("audio-processing-progress", &update) {
+            eprintln!("Failed to emit progress event: {}", e);
+        }
+    };
+
+    let mut processor = AudioProcessor::new();
+    processor.set_decoded_audio_cache(Some(state.decoded_audio.clone()));
+    processor.waveform_peaks(&file_path, buckets, progress_callback)
+        .map_err(|e| describe_error("Error computing waveform", e))
 }
 
 #[tauri::command]
-async fn convert_audio_to_base64(file_path: String) -> Result<String, String> {
+async fn convert_audio_to_base64(file_path: String, variant: Option<Base64Variant>) -> Result<String, String> {
     // Read the entire audio file
     let audio_bytes = std::fs::read(&file_path)
         .map_err(|e| format!("Failed to read audio file: {}", e))?;
     
-    // Encode to base64
-    let base64_string = base64::encode(&audio_bytes);
+    // Encode to base64 (standard alphabet with padding unless asked otherwise)
+    let base64_string = variant.unwrap_or(utils::AUDIO_BASE64).encode(&audio_bytes);
     
     Ok(base64_string)
 }
 
 #[tauri::command]
+fn set_active_model(
+    model: String,
+    base_url: String,
+    form_layout: Option<FormLayout>,
+    state: tauri::State<'_, BackendState>
+) -> Result<(), String> {
+    state.set_active_model(model, &base_url, form_layout.unwrap_or_default())
+}
+
+// Everything needed to transcribe a segment besides its audio
+struct TranscriptionJob {
+    client: HttpClient,
+    requests: Arc<AtomicUsize>, // Sent through `client` so far
+    primary: Backend,
+    fallback: Option<Backend>,
+    hallucination_filter: Option<HallucinationFilter>,
+    options: RequestOptions,
+    retry: RetryPolicy,
+}
+
+impl TranscriptionJob {
+    #[allow(clippy::too_many_arguments)] // Mirrors the shared command arguments
+    fn new(
+        api_key: String,
+        base_url: Option<String>,
+        model_name: Option<String>,
+        client_config: ClientConfig,
+        fallback: Option<Backend>,
+        hallucination_filter: Option<HallucinationFilter>,
+        options: RequestOptions,
+        max_retries: Option<u32>,
+        initial_backoff_ms: Option<u64>,
+        state: &BackendState,
+    ) -> Result<Self, String> {
+        // Explicit parameters win, otherwise fall back to the session's active model
+        let active_model = state.active_model.lock()
+            .map_err(|e| format!("Failed to lock backend state: {}", e))?
+            .clone();
+        let base_url = base_url
+            .or_else(|| active_model.as_ref().map(|m| m.base_url.clone()))
+            .ok_or("No base URL given and no active model set")?;
+        let model_name = model_name
+            .or_else(|| active_model.as_ref().map(|m| m.model_name.clone()))
+            .ok_or("No model name given and no active model set")?;
+        let base_url = transcription::normalize_base_url(&base_url)?;
+        let form_layout = active_model.map(|m| m.form_layout).unwrap_or_default();
+        let primary = Backend { base_url, model_name, api_key, form_layout };
+        let fallback = match fallback {
+            Some(backend) => Some(Backend { base_url: transcription::normalize_base_url(&backend.base_url)?, ..backend }),
+            None => None,
+        };
+
+        // Reuse the client (and its open connections) of earlier calls with the same settings
+        let client = {
+            let mut clients = state.http_clients.lock()
+                .map_err(|e| format!("Failed to lock backend state: {}", e))?;
+            match clients.get(&client_config) {
+                Some(client) => client.clone(),
+                None => {
+                    let client = client_config.build()?;
+                    clients.insert(client_config, client.clone());
+                    client
+                }
+            }
+        };
+
+        let mut retry = RetryPolicy::default();
+        if let Some(max_retries) = max_retries {
+            retry.max_retries = max_retries;
+        }
+        if let Some(initial_backoff_ms) = initial_backoff_ms {
+            retry.initial_backoff_ms = initial_backoff_ms;
+        }
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let client = client.counting_requests(Arc::clone(&requests));
+        Ok(Self { client, requests, primary, fallback, hallucination_filter, options, retry })
+    }
+
+    // `options` overrides the job's own, e.g. to add the previous segment as context
+    async fn transcribe(&self, audio_base64: &str, segment_index: usize, options: Option<&RequestOptions>) -> Result<Transcription, String> {
+        // Decode base64 to bytes (standard or URL-safe)
+        let audio_bytes = utils::decode_b64(audio_base64)
+            .map_err(|e| format!("Failed to decode base64: {}", e))?;
+
+        // "verbose_json" adds timings and the detected language where the API supports it
+        let mut transcription = transcription::transcribe_with_fallback(
+            &self.client,
+            &self.primary,
+            self.fallback.as_ref(),
+            audio_bytes,
+            format!("segment_{}.wav", segment_index),
+            options.unwrap_or(&self.options),
+            &self.retry,
+        ).await?;
+
+        // Flag empty or made-up text so the caller can drop the segment
+        if let Some(filter) = &self.hallucination_filter {
+            transcription.hallucination = filter.check(&transcription.result.text);
+        }
+
+        Ok(transcription)
+    }
+
+    // One segment of a batch, with its word times moved onto the source file's timeline
+    async fn transcribe_segment(&self, segment: &SegmentAudio, options: Option<&RequestOptions>) -> Result<Transcription, String> {
+        let mut transcription = self.transcribe(&segment.audio_base64, segment.index, options).await?;
+        transcription.result.shift(segment.start_time_seconds - segment.padding_seconds);
+        Ok(transcription)
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Each option is a separate named argument on the JS side
 async fn transcribe_audio(
     audio_base64: String, 
     segment_index: usize,
     api_key: String,
-    base_url: String,
-    model_name: String
-) -> Result<String, String> {
-    // Decode base64 to bytes
-    let audio_bytes = base64::decode(&audio_base64)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-    
-    // Create multipart form
-    let form = reqwest::multipart::Form::new()
-        .part("file", reqwest::multipart::Part::bytes(audio_bytes)
-            .file_name(format!("segment_{}.wav", segment_index))
-            .mime_str("audio/wav")
-            .map_err(|e| format!("Failed to set mime type: {}", e))?)
-        .text("model", model_name);
-        //.text("language", "en");
-    
-    // Create HTTP client
-    let client = reqwest::Client::new();
-    
-    // Make the API request
-    let response = client
-        .post(&format!("{}/audio/transcriptions", base_url))
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("API error {}: {}", status, error_text));
+    base_url: Option<String>,
+    model_name: Option<String>,
+    user_agent: Option<String>,
+    fallback_backend: Option<Backend>,
+    hallucination_filter: Option<HallucinationFilter>,
+    response_format: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+    max_prompt_chars: Option<usize>,
+    temperature: Option<f32>,
+    max_retries: Option<u32>,
+    initial_backoff_ms: Option<u64>,
+    timeout_seconds: Option<u64>,
+    connect_timeout_seconds: Option<u64>,
+    max_response_bytes: Option<usize>,
+    state: tauri::State<'_, BackendState>
+) -> Result<Transcription, String> {
+    let options = RequestOptions::new(response_format, language, prompt, max_prompt_chars, temperature)?;
+    let client_config = ClientConfig::new(user_agent.unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+        timeout_seconds, connect_timeout_seconds, max_response_bytes)?;
+    let job = TranscriptionJob::new(api_key, base_url, model_name, client_config, fallback_backend,
+        hallucination_filter, options, max_retries, initial_backoff_ms, &state)?;
+    job.transcribe(&audio_base64, segment_index, None).await
+}
+
+/// Check that the endpoint answers, accepts the API key and serves the model, so wrong
+/// settings show up before a batch instead of failing every segment. Base URL and model
+/// default to the active model, like for transcribe_audio.
+#[tauri::command]
+async fn check_transcription_endpoint(
+    api_key: String,
+    base_url: Option<String>,
+    model_name: Option<String>,
+    user_agent: Option<String>,
+    state: tauri::State<'_, BackendState>
+) -> Result<(), String> {
+    let client_config = ClientConfig::new(user_agent.unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()), None, None, None)?;
+    let job = TranscriptionJob::new(api_key, base_url, model_name, client_config, None,
+        None, RequestOptions::default(), None, None, &state)?;
+    job.primary.check(&job.client).await
+}
+
+/// Transcribe one time range of a file without running VAD, e.g. to preview "1:30 to 1:45".
+/// The range is cut at the file's own rate, mixed down and resampled to 16kHz before
+/// it is sent, like a segment from process_audio_vad would be.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Each option is a separate named argument on the JS side
+async fn transcribe_time_range(
+    file_path: String,
+    start_seconds: f64,
+    end_seconds: f64,
+    api_key: String,
+    base_url: Option<String>,
+    model_name: Option<String>,
+    user_agent: Option<String>,
+    language: Option<String>,
+    state: tauri::State<'_, BackendState>
+) -> Result<Transcription, String> {
+    let path = std::path::Path::new(&file_path);
+    if !path.exists() {
+        return Err(format!("File not found: {}", file_path));
     }
-    
-    // Parse the response
-    let result: serde_json::Value = response.json().await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-    
-    // Extract the transcription text
-    let text = result.get("text")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-    
-    Ok(text)
+
+    let processor = AudioProcessor::new();
+    let metadata = processor.probe_metadata(&file_path, false)
+        .map_err(|e| describe_error("Failed to read audio file", e))?;
+    if !start_seconds.is_finite() || !end_seconds.is_finite() || start_seconds < 0.0 || start_seconds >= end_seconds {
+        return Err(format!("Invalid time range: {}s to {}s", start_seconds, end_seconds));
+    }
+    if end_seconds > metadata.duration_seconds {
+        return Err(format!("Time range ends at {:.2}s, but the file is only {:.2}s long", end_seconds, metadata.duration_seconds));
+    }
+
+    let (samples, sample_rate) = processor.extract_segment_from_file(path, start_seconds, end_seconds)
+        .map_err(|e| describe_error("Failed to extract time range", e))?;
+    let samples = processor.resample_audio(&samples, sample_rate, 16000)
+        .map_err(|e| describe_error("Failed to resample time range", e))?;
+    let wav_bytes = processor.samples_to_wav_bytes(&samples, 16000)
+        .map_err(|e| describe_error("Failed to encode time range", e))?;
+
+    let options = RequestOptions::new(None, language, None, None, None)?;
+    let client_config = ClientConfig::new(user_agent.unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()), None, None, None)?;
+    let job = TranscriptionJob::new(api_key, base_url, model_name, client_config, None,
+        None, options, None, None, &state)?;
+    let mut transcription = job.transcribe(&utils::encode_wav_b64(&wav_bytes), 0, None).await?;
+    // Word times count from the start of the range, not of the file
+    transcription.result.shift(start_seconds);
+    Ok(transcription)
+}
+
+// Segments transcribed at once by transcribe_segments unless the caller says otherwise
+const DEFAULT_TRANSCRIPTION_CONCURRENCY: usize = 4;
+
+// Results of a transcribe_segments run, kept in the order the segments were given.
+// Events go out through `emit` as (event name, payload).
+struct SegmentBatch<'a> {
+    emit: &'a (dyn Fn(&str, serde_json::Value) + Sync),
+    results: Vec<Option<Transcription>>,
+    failures: Vec<(usize, String)>,
+    done: usize,
+    skipped: usize,
+    max_failure_percent: Option<f64>,
+    started: Instant,
+    requests: Arc<AtomicUsize>,
+    audio_seconds: f64,
+    total_latency: Duration,
+}
+
+impl<'a> SegmentBatch<'a> {
+    fn new(emit: &'a (dyn Fn(&str, serde_json::Value) + Sync), job: &TranscriptionJob, len: usize, max_failure_percent: Option<f64>) -> Self {
+        Self {
+            emit,
+            results: vec![None; len],
+            failures: Vec::new(),
+            done: 0,
+            skipped: 0,
+            max_failure_percent,
+            started: Instant::now(),
+            requests: Arc::clone(&job.requests),
+            audio_seconds: 0.0,
+            total_latency: Duration::ZERO,
+        }
+    }
+
+    fn send<T: Serialize>(&self, event: &str, payload: &T) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => (self.emit)(event, payload),
+            Err(e) => eprintln!("Failed to serialize {}: {}", event, e),
+        }
+    }
+
+    // Emit progress for a finished segment; errors once too many segments have failed
+    fn record(&mut self, position: usize, segment: &SegmentAudio, latency: Duration, result: Result<Transcription, String>) -> Result<(), String> {
+        self.done += 1;
+        self.total_latency += latency;
+        let update = TranscriptionProgress {
+            segment_index: segment.index,
+            progress: self.done as f64 / self.results.len() as f64 * 100.0,
+            transcription: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().cloned(),
+            skipped: None,
+        };
+        self.send("transcription-progress", &update);
+
+        match result {
+            Ok(transcription) => {
+                self.audio_seconds += segment.audio_seconds();
+                self.results[position] = Some(transcription);
+            }
+            Err(e) => self.failures.push((position, format!("segment {}: {}", segment.index + 1, e))),
+        }
+
+        if let Some(max_failure_percent) = self.max_failure_percent {
+            if self.failures.len() as f64 > self.results.len() as f64 * max_failure_percent / 100.0 {
+                self.send("transcription-stats", &self.stats());
+                return Err(format!("Stopped after {} of {} segments failed to transcribe (limit {}%): {}",
+                    self.failures.len(), self.results.len(), max_failure_percent, self.failure_summary()));
+            }
+        }
+        Ok(())
+    }
+
+    // Report a segment that won't be sent at all
+    fn skip(&mut self, segment: &SegmentAudio, reason: String) {
+        self.done += 1;
+        self.skipped += 1;
+        let update = TranscriptionProgress {
+            segment_index: segment.index,
+            progress: self.done as f64 / self.results.len() as f64 * 100.0,
+            transcription: None,
+            error: None,
+            skipped: Some(reason),
+        };
+        self.send("transcription-progress", &update);
+    }
+
+    fn stats(&self) -> BatchStats {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let sent = self.done - self.skipped;
+        BatchStats {
+            audio_seconds: self.audio_seconds,
+            wall_seconds: self.started.elapsed().as_secs_f64(),
+            requests,
+            retries: requests.saturating_sub(sent),
+            average_latency_seconds: if sent == 0 { 0.0 } else { self.total_latency.as_secs_f64() / sent as f64 },
+        }
+    }
+
+    fn failure_summary(&mut self) -> String {
+        self.failures.sort_by_key(|(position, _)| *position);
+        self.failures.iter().map(|(_, e)| e.as_str()).collect::<Vec<_>>().join("; ")
+    }
+
+    fn finish(mut self) -> Result<TranscribedBatch, String> {
+        let stats = self.stats();
+        self.send("transcription-stats", &stats);
+        if !self.failures.is_empty() {
+            return Err(format!("{} of {} segments failed to transcribe: {}",
+                self.failures.len(), self.results.len(), self.failure_summary()));
+        }
+        Ok(TranscribedBatch { transcriptions: self.results.into_iter().flatten().collect(), stats })
+    }
+}
+
+// Transcribe segments, up to max_concurrency (4 by default) at a time, emitting
+// "transcription-progress" as each finishes and "transcription-stats" at the end. Failed
+// segments don't stop the batch unless more than max_failure_percent of all segments fail;
+// the rest are summarized in the error at the end. With chain_prompts each segment's text
+// is sent as context for the next one, so segments are transcribed one after another.
+// Segments with an SNR below min_snr_db are reported as skipped and never sent.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Each option is a separate named argument on the JS side
+async fn transcribe_segments(
+    segments: Vec<SegmentAudio>,
+    api_key: String,
+    base_url: Option<String>,
+    model_name: Option<String>,
+    user_agent: Option<String>,
+    fallback_backend: Option<Backend>,
+    hallucination_filter: Option<HallucinationFilter>,
+    response_format: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+    max_prompt_chars: Option<usize>,
+    temperature: Option<f32>,
+    chain_prompts: Option<bool>,
+    max_retries: Option<u32>,
+    initial_backoff_ms: Option<u64>,
+    max_concurrency: Option<usize>,
+    max_failure_percent: Option<f64>,
+    min_snr_db: Option<f64>,
+    timeout_seconds: Option<u64>,
+    connect_timeout_seconds: Option<u64>,
+    max_response_bytes: Option<usize>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, BackendState>
+) -> Result<TranscribedBatch, String> {
+    let max_concurrency = max_concurrency.unwrap_or(DEFAULT_TRANSCRIPTION_CONCURRENCY);
+    if max_concurrency == 0 {
+        return Err("Concurrency must be at least 1".to_string());
+    }
+    if max_failure_percent.is_some_and(|percent| !(0.0..=100.0).contains(&percent)) {
+        return Err("Failure threshold must be between 0 and 100 percent".to_string());
+    }
+    if min_snr_db.is_some_and(|min_snr_db| !min_snr_db.is_finite()) {
+        return Err("Minimum SNR must be a finite number of dB".to_string());
+    }
+    let options = RequestOptions::new(response_format, language, prompt, max_prompt_chars, temperature)?;
+    let client_config = ClientConfig::new(user_agent.unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+        timeout_seconds, connect_timeout_seconds, max_response_bytes)?;
+    let job = TranscriptionJob::new(api_key, base_url, model_name, client_config, fallback_backend,
+        hallucination_filter, options, max_retries, initial_backoff_ms, &state)?;
+
+    let emit = |event: &str, payload: serde_json::Value| {
+        if let Err(e) = app_handle.emit(event, payload) {
+            eprintln!("Failed to emit {}: {}", event, e);
+        }
+    };
+    let batch = SegmentBatch::new(&emit, &job, segments.len(), max_failure_percent);
+    run_segment_batch(job, segments, chain_prompts.unwrap_or(false), max_concurrency, min_snr_db, batch).await
+}
+
+async fn run_segment_batch(job: TranscriptionJob, segments: Vec<SegmentAudio>, chain_prompts: bool, max_concurrency: usize, min_snr_db: Option<f64>, mut batch: SegmentBatch<'_>) -> Result<TranscribedBatch, String> {
+    // Likely-garbage audio isn't worth an API request
+    let mut queued = Vec::with_capacity(segments.len());
+    for (position, segment) in segments.into_iter().enumerate() {
+        match (segment.snr_db, min_snr_db) {
+            (Some(snr_db), Some(min_snr_db)) if snr_db < min_snr_db => {
+                batch.skip(&segment, format!("SNR {:.1} dB is below {} dB", snr_db, min_snr_db));
+            }
+            _ => queued.push((position, segment)),
+        }
+    }
+
+    if chain_prompts {
+        // Options carrying the last usable transcription as context
+        let mut chained: Option<RequestOptions> = None;
+        for (position, segment) in &queued {
+            let started = Instant::now();
+            let result = job.transcribe_segment(segment, chained.as_ref()).await;
+            // Hallucinated or failed text would only mislead the next segment
+            chained = result.as_ref().ok()
+                .filter(|transcription| transcription.hallucination.is_none())
+                .map(|transcription| job.options.with_context(&transcription.result.text));
+            batch.record(*position, segment, started.elapsed(), result)?;
+        }
+        return batch.finish();
+    }
+
+    let job = Arc::new(job);
+    let semaphore = Arc::new(Semaphore::new(max_concurrency));
+    // Dropping the set on an early return aborts the segments still running
+    let mut tasks = JoinSet::new();
+    for (position, segment) in queued {
+        let job = Arc::clone(&job);
+        let semaphore = Arc::clone(&semaphore);
+        tasks.spawn(async move {
+            let (result, latency) = match semaphore.acquire_owned().await {
+                Ok(_permit) => {
+                    let started = Instant::now();
+                    (job.transcribe_segment(&segment, None).await, started.elapsed())
+                }
+                Err(e) => (Err(format!("Failed to schedule transcription: {}", e)), Duration::ZERO),
+            };
+            (position, segment, latency, result)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let (position, segment, latency, result) = joined
+            .map_err(|e| format!("Transcription task failed: {}", e))?;
+        batch.record(position, &segment, latency, result)?;
+    }
+    batch.finish()
+}
+
+// Sentence-level segments from VAD-level ones, see transcript::group_sentences
+#[tauri::command]
+fn group_transcript(segments: Vec<Cue>, max_gap_seconds: f64) -> Result<Vec<Cue>, String> {
+    if !max_gap_seconds.is_finite() || max_gap_seconds < 0.0 {
+        return Err(format!("Maximum gap must be a non-negative number of seconds, got {}", max_gap_seconds));
+    }
+    Ok(transcript::group_sentences(&segments, max_gap_seconds))
+}
+
+#[tauri::command]
+fn align_transcript_words(text: String, start_time_seconds: f64, end_time_seconds: f64) -> Vec<WordTiming> {
+    // Approximate timings only, see transcript::align_words
+    transcript::align_words(&text, start_time_seconds, end_time_seconds)
+}
+
+// Subtitle cues for transcribed segments, optionally split into one cue per sentence
+// and wrapped to a line length (at most max_lines lines per cue, 2 by default)
+#[tauri::command]
+fn build_subtitle_cues(
+    segments: Vec<Cue>,
+    split_sentences: Option<bool>,
+    max_chars_per_line: Option<usize>,
+    max_lines: Option<usize>
+) -> Result<Vec<Cue>, String> {
+    if max_chars_per_line == Some(0) || max_lines == Some(0) {
+        return Err("Subtitle line and character limits must be at least 1".to_string());
+    }
+
+    let mut cues = if split_sentences.unwrap_or(false) {
+        segments.iter()
+            .flat_map(|segment| transcript::sentence_cues(&segment.text, segment.start_time_seconds, segment.end_time_seconds))
+            .collect()
+    } else {
+        segments
+    };
+
+    if let Some(max_chars_per_line) = max_chars_per_line {
+        let max_lines = max_lines.unwrap_or(2);
+        cues = cues.iter()
+            .flat_map(|cue| transcript::wrap_cue(cue, max_chars_per_line, max_lines))
+            .collect();
+    }
+
+    Ok(cues)
+}
+
+// Write a self-contained HTML transcript with playable segment audio
+#[tauri::command]
+async fn export_html(segments: Vec<TranscriptSegment>, out_path: String, title: Option<String>) -> Result<(), String> {
+    let html = html_export::render_html(title.as_deref().unwrap_or("Transcript"), &segments)?;
+    std::fs::write(&out_path, html).map_err(|e| format!("Failed to write HTML transcript: {}", e))?;
+    debug_log!("Exported {} segments to {}", segments.len(), out_path);
+    Ok(())
+}
+
+// Save segments of a file so they can be reloaded without running VAD again
+#[tauri::command]
+async fn export_segments_json(
+    file_path: String,
+    output_path: String,
+    segments: Vec<SnapshotSegment>,
+    include_audio: Option<bool>
+) -> Result<String, String> {
+    segment_snapshot::export(std::path::Path::new(&file_path), std::path::Path::new(&output_path), &segments, include_audio.unwrap_or(false))?;
+    debug_log!("Exported {} segments of {} to {}", segments.len(), file_path, output_path);
+    Ok(output_path)
+}
+
+// Segments saved by export_segments_json, if the audio file is unchanged
+#[tauri::command]
+async fn import_segments_json(file_path: String, json_path: String) -> Result<Vec<AudioSegment>, String> {
+    segment_snapshot::import(std::path::Path::new(&file_path), std::path::Path::new(&json_path))
 }
 
 #[tauri::command]
@@ -256,49 +1434,23 @@ async fn check_file_exists(file_path: String) -> Result<bool, String> {
 async fn extract_segment_audio(
     original_audio_base64: String,
     start_time_seconds: f64,
-    end_time_seconds: f64
+    end_time_seconds: f64,
+    channel_mix: Option<ChannelMixMode>,
+    bit_depth: Option<WavBitDepth>,
+    state: tauri::State<'_, BackendState>
 ) -> Result<String, String> {
-    use base64;
-    use std::env;
     use std::fs;
     
     // Decode the base64 audio data (this is the compressed MP3/etc file)
-    let audio_bytes = base64::decode(&original_audio_base64)
+    let audio_bytes = utils::decode_b64(&original_audio_base64)
         .map_err(|e| format!("Failed to decode base64 audio: {}", e))?;
     
-    // Determine the file extension from the audio data
-    let file_extension = if audio_bytes.len() > 4 {
-        // Check for MP3 header (ID3 tag or frame sync)
-        if audio_bytes.starts_with(b"ID3") || 
-           (audio_bytes.len() > 2 && audio_bytes[0] == 0xFF && (audio_bytes[1] & 0xE0) == 0xE0) {
-            "mp3"
-        }
-        // Check for WAV header
-        else if audio_bytes.starts_with(b"RIFF") && audio_bytes.len() > 8 && &audio_bytes[8..12] == b"WAVE" {
-            "wav"
-        }
-        // Check for M4A/AAC header
-        else if audio_bytes.len() > 8 && &audio_bytes[4..8] == b"ftyp" {
-            "m4a"
-        }
-        // Check for FLAC header
-        else if audio_bytes.starts_with(b"fLaC") {
-            "flac"
-        }
-        // Check for OGG header
-        else if audio_bytes.starts_with(b"OggS") {
-            "ogg"
-        }
-        else {
-            // Default to mp3 since that's most common for compressed audio
-            "mp3"
-        }
-    } else {
-        "mp3"
-    };
+    // Determine the file extension from the audio data, defaulting to mp3 since that's
+    // most common for compressed audio
+    let file_extension = audio_processing::sniff_format(&audio_bytes).unwrap_or("mp3");
     
     // Create a temporary file for the original compressed audio
-    let temp_dir = env::temp_dir().join("transcriber_audio");
+    let temp_dir = state.temp_audio_dir();
     if !temp_dir.exists() {
         fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
     }
@@ -309,25 +1461,112 @@ async fn extract_segment_audio(
     fs::write(&temp_original_path, &audio_bytes)
         .map_err(|e| format!("Failed to write original audio file: {}", e))?;
     
-    // Use audio processor to extract the segment
-    let processor = AudioProcessor::new();
-    let (segment_samples, sample_rate) = processor.extract_segment_from_file(
-        &temp_original_path,
-        start_time_seconds,
-        end_time_seconds
-    ).map_err(|e| format!("Failed to extract segment: {}", e))?;
-    
-    // Convert segment samples to WAV bytes using the original sample rate
-    let segment_wav_bytes = processor.samples_to_wav_bytes(&segment_samples, sample_rate)
-        .map_err(|e| format!("Failed to convert segment to WAV: {}", e))?;
-    
-    // Encode to base64
-    let segment_base64 = base64::encode(&segment_wav_bytes);
+    // Use audio processor to extract the segment, mixed down the same way as for VAD
+    let mut processor = AudioProcessor::new();
+    processor.set_channel_mix_mode(channel_mix.unwrap_or_default());
+    let segment_wav_bytes = segment_wav(&processor, &temp_original_path, start_time_seconds, end_time_seconds, bit_depth.unwrap_or_default());
     
-    // Clean up temporary file
+    // Clean up temporary file, whether or not extraction worked
     let _ = fs::remove_file(&temp_original_path);
+    let segment_wav_bytes = segment_wav_bytes?;
     
-    Ok(segment_base64)
+    Ok(utils::encode_wav_b64(&segment_wav_bytes))
+}
+
+// WAV of a time range of a file at its own sample rate. 16-bit comes from the seeking
+// i16 extraction; deeper formats decode the whole file as float.
+fn segment_wav(processor: &AudioProcessor, path: &std::path::Path, start_time_seconds: f64, end_time_seconds: f64, bit_depth: WavBitDepth) -> Result<Vec<u8>, String> {
+    let wav_bytes = if bit_depth == WavBitDepth::Pcm16 {
+        let (segment_samples, sample_rate) = processor.extract_segment_from_file(path, start_time_seconds, end_time_seconds)
+            .map_err(|e| describe_error("Failed to extract segment", e))?;
+        processor.samples_to_wav_bytes(&segment_samples, sample_rate)
+    } else {
+        let (segment_samples, sample_rate) = processor.extract_segment_from_file_f32(path, start_time_seconds, end_time_seconds)
+            .map_err(|e| describe_error("Failed to extract segment", e))?;
+        processor.samples_f32_to_wav_bytes(&segment_samples, sample_rate, bit_depth)
+    };
+    wav_bytes.map_err(|e| format!("Failed to convert segment to WAV: {}", e))
+}
+
+// Base64 WAV of one segment of a file on disk, for segments returned without their audio
+#[tauri::command]
+async fn get_segment_base64(
+    file_path: String,
+    start_time_seconds: f64,
+    end_time_seconds: f64,
+    channel_mix: Option<ChannelMixMode>,
+    bit_depth: Option<WavBitDepth>
+) -> Result<String, String> {
+    let path = std::path::Path::new(&file_path);
+    if !path.exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let mut processor = AudioProcessor::new();
+    processor.set_channel_mix_mode(channel_mix.unwrap_or_default());
+    let segment_wav_bytes = segment_wav(&processor, path, start_time_seconds, end_time_seconds, bit_depth.unwrap_or_default())?;
+    Ok(utils::encode_wav_b64(&segment_wav_bytes))
+}
+
+// Largest boost or cut apply_gain_to_segment accepts
+const MAX_PREVIEW_GAIN_DB: f64 = 48.0;
+
+// Make a segment's WAV louder (or quieter) for review playback. Works on the base64
+// alone, so the stored segment and the file stay as they are; the result is 16-bit mono
+// at the segment's rate.
+#[tauri::command]
+async fn apply_gain_to_segment(audio_base64: String, gain_db: f64) -> Result<BoostedAudio, String> {
+    if !gain_db.is_finite() || gain_db.abs() > MAX_PREVIEW_GAIN_DB {
+        return Err(format!("Gain must be between -{0} and {0} dB, got {1}", MAX_PREVIEW_GAIN_DB, gain_db));
+    }
+    let wav_bytes = utils::decode_b64(&audio_base64)
+        .map_err(|e| format!("Failed to decode base64 audio: {}", e))?;
+
+    let processor = AudioProcessor::new();
+    let (mut samples, sample_rate) = processor.decode_audio_bytes(&wav_bytes, Some("wav"))
+        .map_err(|e| describe_error("Failed to decode segment audio", e))?;
+    let clipped_samples = dsp::apply_gain(&mut samples, gain_db);
+    let wav_bytes = processor.samples_to_wav_bytes(&samples, sample_rate)
+        .map_err(|e| describe_error("Failed to encode segment audio", e))?;
+    Ok(BoostedAudio { audio_base64: utils::encode_wav_b64(&wav_bytes), clipped_samples })
+}
+
+// Base64 WAV of a segment by its sample indices, cut from the same audio VAD and
+// transcription saw. The settings that shape that audio must match the run that found
+// the segment; indices count at its VAD rate (16kHz unless 8kHz was used) and so does
+// the returned WAV.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Each option is a separate named argument on the JS side
+async fn extract_vad_segment_audio(
+    file_path: String,
+    start_sample: i64,
+    end_sample: i64,
+    vad_sample_rate: Option<u32>,
+    detect_dual_mono: Option<bool>,
+    channel_mix: Option<ChannelMixMode>,
+    dither: Option<bool>,
+    normalize_peak_dbfs: Option<f32>,
+    eq_bands: Option<Vec<EqBand>>,
+    state: tauri::State<'_, BackendState>
+) -> Result<String, String> {
+    if !std::path::Path::new(&file_path).exists() {
+        return Err(format!("File not found: {}", file_path));
+    }
+
+    let mut processor = AudioProcessor::new();
+    processor.set_sample_rate(vad_sample_rate.unwrap_or(16000))?;
+    processor.set_detect_dual_mono(detect_dual_mono.unwrap_or(true));
+    processor.set_channel_mix_mode(channel_mix.unwrap_or_default());
+    processor.set_dither(dither.unwrap_or(false));
+    processor.set_normalization(normalize_peak_dbfs)?;
+    processor.set_eq_bands(eq_bands.unwrap_or_default());
+    processor.set_decoded_audio_cache(Some(state.decoded_audio.clone()));
+
+    let samples = processor.extract_vad_segment(&file_path, start_sample, end_sample)
+        .map_err(|e| describe_error("Failed to extract segment", e))?;
+    let wav_bytes = processor.samples_to_wav_bytes(&samples, vad_sample_rate.unwrap_or(16000))
+        .map_err(|e| format!("Failed to convert segment to WAV: {}", e))?;
+    Ok(utils::encode_wav_b64(&wav_bytes))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -336,7 +1575,148 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .invoke_handler(tauri::generate_handler![greet, process_audio_vad, select_audio_file, save_audio_file, save_audio_file_chunked, transcribe_audio, convert_audio_to_base64, check_file_exists, extract_segment_audio])
+        .manage(BackendState::default())
+        .invoke_handler(tauri::generate_handler![greet, process_audio_vad, cancel_processing, append_pcm_frames, finalize_pcm_session, clear_processing_cache, clear_audio_cache, set_temp_dir, set_audio_cache_size, set_debug_logging, benchmark_pipeline, estimate_segments, chunk_audio_fixed, concat_and_process, probe_audio, get_headroom, get_cover_art, export_cover_art, export_processed_wav, render_spectrogram, waveform_peaks, select_audio_file, save_audio_file, save_audio_file_chunked, check_transcription_endpoint, transcribe_audio, transcribe_time_range, transcribe_segments, set_active_model, group_transcript, align_transcript_words, build_subtitle_cues, export_html, export_segments_json, import_segments_json, convert_audio_to_base64, check_file_exists, extract_segment_audio, get_segment_base64, apply_gain_to_segment, extract_vad_segment_audio])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(state: &BackendState, base_url: Option<&str>, model_name: Option<&str>) -> Result<TranscriptionJob, String> {
+        let client_config = ClientConfig::new(DEFAULT_USER_AGENT.to_string(), None, None, None)?;
+        TranscriptionJob::new("key".to_string(), base_url.map(String::from), model_name.map(String::from), client_config,
+            None, None, RequestOptions::default(), None, None, state)
+    }
+
+    #[test]
+    fn active_model_switches_mid_session() {
+        let state = BackendState::default();
+        assert!(job(&state, None, None).is_err(), "no model should be configured yet");
+
+        state.set_active_model("whisper-1".to_string(), "https://api.openai.com/v1/", FormLayout::default()).unwrap();
+        let first = job(&state, None, None).unwrap();
+        assert_eq!(first.primary.model_name, "whisper-1");
+        assert_eq!(first.primary.base_url, "https://api.openai.com/v1");
+
+        state.set_active_model("large-v3".to_string(), "http://localhost:8000/v1", FormLayout::default()).unwrap();
+        let second = job(&state, None, None).unwrap();
+        assert_eq!(second.primary.model_name, "large-v3");
+        assert_eq!(second.primary.base_url, "http://localhost:8000/v1");
+
+        // Explicit parameters still win over the session's model
+        let explicit = job(&state, Some("https://api.groq.com/openai/v1"), Some("distil-whisper")).unwrap();
+        assert_eq!(explicit.primary.model_name, "distil-whisper");
+        assert_eq!(explicit.primary.base_url, "https://api.groq.com/openai/v1");
+    }
+
+    #[test]
+    fn upload_completes_when_the_last_chunk_arrives_first() {
+        let dir = crate::utils::test_temp_path("upload-last-first");
+        let samples: Vec<i16> = (0..16000).map(|i| ((i as f64 * 0.05).sin() * 16000.0) as i16).collect();
+        let wav = AudioProcessor::new().samples_to_wav_bytes(&samples, 16000).unwrap();
+        let chunks: Vec<&[u8]> = wav.chunks(wav.len().div_ceil(3)).collect();
+        assert_eq!(chunks.len(), 3);
+
+        assert!(store_upload_chunk(&dir, chunks[2], 2, 3, "clip.wav", "session").unwrap().is_none());
+        assert!(store_upload_chunk(&dir, chunks[0], 0, 3, "clip.wav", "session").unwrap().is_none());
+        let processed = store_upload_chunk(&dir, chunks[1], 1, 3, "clip.wav", "session").unwrap().unwrap();
+        assert!(processed.warnings.is_empty());
+
+        let decoded = AudioProcessor::new().decode_audio_symphonia(&processed.path);
+        std::fs::remove_dir_all(&dir).ok();
+        let (decoded, sample_rate) = decoded.unwrap();
+        assert_eq!((decoded.len(), sample_rate), (samples.len(), 16000));
+    }
+
+    #[test]
+    fn upload_warnings_are_returned_with_the_path() {
+        let dir = crate::utils::test_temp_path("upload-warnings");
+        let samples: Vec<i16> = (0..16000).map(|i| ((i as f64 * 0.05).sin() * 16000.0) as i16).collect();
+        let mut wav = AudioProcessor::new().samples_to_wav_bytes(&samples, 16000).unwrap();
+        // Declares only half of the audio, like a file cut off while being written
+        wav[40..44].copy_from_slice(&16000u32.to_le_bytes());
+
+        let saved = store_upload_chunk(&dir, &wav, 0, 1, "cut.wav", "session");
+        std::fs::remove_dir_all(&dir).ok();
+        let warnings = saved.unwrap().unwrap().warnings;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, audio_processing::WarningKind::WavDataLength);
+    }
+
+    #[tokio::test]
+    async fn words_from_a_late_segment_carry_absolute_times() {
+        let (base_url, _) = transcription::test_server(vec![("200 OK",
+            r#"{"text":"hello there","words":[{"word":"hello","start":0.5,"end":1.0},{"word":"there","start":1.25,"end":1.75}]}"#)]).await;
+        let state = BackendState::default();
+        let job = job(&state, Some(&base_url), Some("whisper-1")).unwrap();
+
+        // An hour into the file, with a quarter second of context before the segment
+        let segment = SegmentAudio { index: 41, audio_base64: utils::encode_wav_b64(&[1, 2, 3]), start_time_seconds: 3600.0, padding_seconds: 0.25, snr_db: None };
+        let transcription = job.transcribe_segment(&segment, None).await.unwrap();
+        let times: Vec<(f64, f64)> = transcription.result.segments.iter()
+            .map(|word| (word.start_time_seconds, word.end_time_seconds))
+            .collect();
+        assert_eq!(times, vec![(3600.25, 3600.75), (3601.0, 3601.5)]);
+    }
+
+    #[tokio::test]
+    async fn batch_stats_match_a_backend_of_known_latency() {
+        // Every answer takes 100ms, and the first one asks for a retry
+        let (base_url, _) = transcription::test_server_with_latency(vec![
+            ("503 Service Unavailable", r#"{"error":{"message":"busy"}}"#),
+            ("200 OK", r#"{"text":"hello"}"#),
+        ], Duration::from_millis(100)).await;
+        let state = BackendState::default();
+        let client_config = ClientConfig::new(DEFAULT_USER_AGENT.to_string(), None, None, None).unwrap();
+        let job = TranscriptionJob::new("key".to_string(), Some(base_url), Some("whisper-1".to_string()), client_config,
+            None, None, RequestOptions::default(), Some(1), Some(1), &state).unwrap();
+
+        let wav = |seconds: f64| utils::encode_wav_b64(&AudioProcessor::new().samples_to_wav_bytes(&vec![0; (seconds * 16000.0) as usize], 16000).unwrap());
+        let segments = vec![
+            SegmentAudio { index: 0, audio_base64: wav(1.5), start_time_seconds: 0.0, padding_seconds: 0.0, snr_db: None },
+            SegmentAudio { index: 1, audio_base64: wav(0.5), start_time_seconds: 2.0, padding_seconds: 0.0, snr_db: None },
+        ];
+        let events = Mutex::new(Vec::new());
+        let emit = |event: &str, payload: serde_json::Value| events.lock().unwrap().push((event.to_string(), payload));
+        let batch = SegmentBatch::new(&emit, &job, segments.len(), None);
+        let stats = run_segment_batch(job, segments, false, 1, None, batch).await.unwrap().stats;
+
+        assert_eq!((stats.audio_seconds, stats.requests, stats.retries), (2.0, 3, 1));
+        // One segment took two answers, the other one
+        assert!((stats.average_latency_seconds - 0.15).abs() < 0.04, "average latency {:.3}s", stats.average_latency_seconds);
+        assert!(stats.wall_seconds >= 0.3 && stats.wall_seconds < 0.5, "wall time {:.3}s", stats.wall_seconds);
+
+        let events = events.into_inner().unwrap();
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["transcription-progress", "transcription-progress", "transcription-stats"]);
+        assert_eq!(events[2].1["requests"], 3);
+    }
+
+    #[tokio::test]
+    async fn segments_below_the_minimum_snr_are_skipped() {
+        let (base_url, requests) = transcription::test_server(vec![("200 OK", r#"{"text":"hello"}"#)]).await;
+        let state = BackendState::default();
+        let job = job(&state, Some(&base_url), Some("whisper-1")).unwrap();
+
+        let audio_base64 = utils::encode_wav_b64(&AudioProcessor::new().samples_to_wav_bytes(&[0; 1600], 16000).unwrap());
+        let segment = |index: usize, snr_db: f64| SegmentAudio { index, audio_base64: audio_base64.clone(), start_time_seconds: index as f64, padding_seconds: 0.0, snr_db: Some(snr_db) };
+        let segments = vec![segment(0, 24.0), segment(1, 2.5)];
+        let events = Mutex::new(Vec::new());
+        let emit = |event: &str, payload: serde_json::Value| events.lock().unwrap().push((event.to_string(), payload));
+        let batch = SegmentBatch::new(&emit, &job, segments.len(), None);
+        let transcribed = run_segment_batch(job, segments, false, 4, Some(10.0), batch).await.unwrap();
+
+        // Only the clean segment reaches the API
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert_eq!(transcribed.transcriptions.len(), 1);
+        assert_eq!(transcribed.stats.retries, 0);
+        let events = events.into_inner().unwrap();
+        let skipped = events.iter().find(|(name, update)| name == "transcription-progress" && update["segment_index"] == 1).unwrap();
+        assert_eq!(skipped.1["skipped"], "SNR 2.5 dB is below 10 dB");
+        let clean = events.iter().find(|(name, update)| name == "transcription-progress" && update["segment_index"] == 0).unwrap();
+        assert_eq!(clean.1["transcription"]["text"], "hello");
+    }
+}
@@ -0,0 +1,133 @@
+// Live capture: raw PCM frames pushed by the frontend as they are recorded. Each
+// session resamples to the VAD rate as frames arrive, runs VAD one chunk at a time
+// and hands back speech segments as soon as they end. Only the audio a pending
+// segment can still need is kept: the padding before the next possible speech
+// onset while idle, and everything since the current run started while speaking.
+
+use crate::audio_processing::{AudioProcessor, SpeechRunTracker, StreamResampler, VadConfig};
+use crate::utils;
+use serde::Serialize;
+use std::collections::VecDeque;
+use voice_activity_detector::VoiceActivityDetector;
+
+/// A speech segment that ended during live capture. Times are from the start of the session.
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveSegment {
+    pub session_id: String,
+    pub index: usize,
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
+    pub audio_base64: String, // Base64-encoded WAV at the VAD rate
+}
+
+pub struct LiveSession {
+    session_id: String,
+    processor: AudioProcessor,
+    vad: VoiceActivityDetector,
+    chunk_size: usize,
+    tracker: SpeechRunTracker,
+    input_rate: u32,
+    resampler: Option<StreamResampler>, // None when frames already arrive at the VAD rate
+    // Audio at the VAD rate from sample `audio_start` (counted from the session start) on
+    audio: VecDeque<i16>,
+    audio_start: usize,
+    segments_emitted: usize,
+}
+
+impl LiveSession {
+    pub fn new(session_id: String, input_rate: u32, vad_config: VadConfig) -> Result<Self, String> {
+        if input_rate == 0 {
+            return Err("Sample rate must be above 0 Hz".to_string());
+        }
+        let mut processor = AudioProcessor::new();
+        processor.set_vad_config(vad_config)?;
+        let (vad, chunk_size) = processor.voice_activity_detector().map_err(|e| e.to_string())?;
+        let vad_rate = processor.vad_sample_rate();
+        let resampler = if input_rate == vad_rate { None } else { Some(StreamResampler::new(input_rate, vad_rate)?) };
+
+        Ok(Self {
+            session_id,
+            tracker: processor.speech_run_tracker(chunk_size),
+            processor,
+            vad,
+            chunk_size,
+            input_rate,
+            resampler,
+            audio: VecDeque::new(),
+            audio_start: 0,
+            segments_emitted: 0,
+        })
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    /// Add captured frames and return the segments that ended within them
+    pub fn append(&mut self, samples: &[i16]) -> Result<Vec<LiveSegment>, String> {
+        let mut resampled = Vec::new();
+        match &mut self.resampler {
+            Some(resampler) => resampler.push(samples, &mut resampled)?,
+            None => resampled.extend_from_slice(samples),
+        }
+        self.audio.extend(resampled);
+
+        let mut segments = Vec::new();
+        while self.audio_end() - self.vad_position() >= self.chunk_size {
+            let offset = self.vad_position() - self.audio_start;
+            let probability = self.vad.predict(self.audio.range(offset..offset + self.chunk_size).copied());
+            if let Some(run) = self.tracker.push(probability) {
+                segments.push(self.segment(run.start * self.chunk_size, run.end * self.chunk_size)?);
+            }
+        }
+
+        // Drop audio no later segment can include
+        let keep_from = (self.tracker.earliest_pending_chunk() * self.chunk_size).max(self.audio_start);
+        self.audio.drain(..keep_from - self.audio_start);
+        self.audio_start = keep_from;
+        Ok(segments)
+    }
+
+    /// End the session, returning any segments still open, including speech that ran
+    /// up to the last frame
+    pub fn finish(mut self) -> Result<Vec<LiveSegment>, String> {
+        let mut tail = Vec::new();
+        if let Some(resampler) = &mut self.resampler {
+            resampler.finish(&mut tail)?;
+        }
+        let mut segments = self.append(&tail)?;
+
+        // As for files, a run reaching the last chunk also covers the partial chunk VAD never saw
+        if let Some(run) = self.tracker.finish() {
+            let end = if run.end >= self.tracker.chunk_count() { self.audio_end() } else { run.end * self.chunk_size };
+            segments.push(self.segment(run.start * self.chunk_size, end)?);
+        }
+        Ok(segments)
+    }
+
+    fn vad_position(&self) -> usize {
+        self.tracker.chunk_count() * self.chunk_size
+    }
+
+    fn audio_end(&self) -> usize {
+        self.audio_start + self.audio.len()
+    }
+
+    fn segment(&mut self, start: usize, end: usize) -> Result<LiveSegment, String> {
+        let (start, end) = (start.max(self.audio_start), end.min(self.audio_end()));
+        let samples: Vec<i16> = self.audio.range(start - self.audio_start..end - self.audio_start).copied().collect();
+        let sample_rate = self.processor.vad_sample_rate();
+        let wav = self.processor.samples_to_wav_bytes(&samples, sample_rate)
+            .map_err(|e| format!("Failed to create WAV data: {}", e))?;
+
+        let segment = LiveSegment {
+            session_id: self.session_id.clone(),
+            index: self.segments_emitted,
+            start_time_seconds: start as f64 / sample_rate as f64,
+            end_time_seconds: end as f64 / sample_rate as f64,
+            audio_base64: utils::encode_wav_b64(&wav),
+        };
+        self.segments_emitted += 1;
+        Ok(segment)
+    }
+}
@@ -0,0 +1,155 @@
+// On-disk cache of segment boundaries so re-opening a file skips VAD and merging.
+// Entries are keyed by a hash of the file's content plus every setting that
+// affects segmentation, so edits to either simply miss the cache.
+
+use crate::audio_processing::AudioSegment;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+// Bump when the cached format or segmentation algorithm changes
+const CACHE_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CachedSegment {
+    pub start_sample: i64,
+    pub end_sample: i64,
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
+}
+
+impl CachedSegment {
+    /// Rebuild a full segment by slicing the 16kHz audio it was detected in
+    pub fn to_audio_segment(&self, content: &[i16]) -> AudioSegment {
+        let start = (self.start_sample.max(0) as usize).min(content.len());
+        let end = (self.end_sample.max(0) as usize).clamp(start, content.len());
+        AudioSegment {
+            start_sample: self.start_sample,
+            end_sample: self.end_sample,
+            start_time_seconds: self.start_time_seconds,
+            end_time_seconds: self.end_time_seconds,
+            audio_data: content[start..end].to_vec(),
+            audio_base64: String::new(),
+            snr_db: None,
+            overlap_seconds: 0.0,
+            channel: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SegmentCache {
+    dir: PathBuf,
+}
+
+impl SegmentCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Hash the file content together with the processing configuration
+    pub fn key(&self, file_path: &str, fingerprint: &str) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(CACHE_VERSION.to_le_bytes());
+        hasher.update(fingerprint.as_bytes());
+
+        let mut file = File::open(file_path)?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+
+        Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    // A missing or unreadable entry is just a cache miss
+    pub fn load(&self, key: &str) -> Option<Vec<CachedSegment>> {
+        let data = fs::read(self.entry_path(key)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn store(&self, key: &str, segments: &[AudioSegment]) -> io::Result<()> {
+        let cached: Vec<CachedSegment> = segments.iter()
+            .map(|segment| CachedSegment {
+                start_sample: segment.start_sample,
+                end_sample: segment.end_sample,
+                start_time_seconds: segment.start_time_seconds,
+                end_time_seconds: segment.end_time_seconds,
+            })
+            .collect();
+
+        fs::create_dir_all(&self.dir)?;
+        let data = serde_json::to_vec(&cached)?;
+        fs::write(self.entry_path(key), data)
+    }
+
+    /// Delete every cached entry, returning how many were removed
+    pub fn clear(&self) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_and_clear() {
+        let dir = crate::utils::test_temp_path("segment-cache-entries");
+        let file = dir.with_extension("bin");
+        fs::write(&file, b"audio").unwrap();
+        let cache = SegmentCache::new(dir.clone());
+        let file_path = file.to_str().unwrap();
+
+        let key = cache.key(file_path, "vad=a").unwrap();
+        assert_ne!(key, cache.key(file_path, "vad=b").unwrap());
+        assert!(cache.load(&key).is_none());
+
+        let segment = AudioSegment {
+            start_sample: 1,
+            end_sample: 3,
+            start_time_seconds: 1.0 / 16000.0,
+            end_time_seconds: 3.0 / 16000.0,
+            audio_data: vec![6, 7],
+            audio_base64: String::new(),
+            snr_db: None,
+            overlap_seconds: 0.0,
+            channel: None,
+        };
+        cache.store(&key, &[segment]).unwrap();
+        let loaded = cache.load(&key).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].to_audio_segment(&[5, 6, 7, 8]).audio_data, vec![6, 7]);
+
+        fs::write(&file, b"audiO").unwrap();
+        assert_ne!(key, cache.key(file_path, "vad=a").unwrap());
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(cache.load(&key).is_none());
+        fs::remove_file(&file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+// Segments saved to a JSON file of the user's choosing, so a segmentation can be
+// reloaded after a restart without running VAD again. The snapshot records the size
+// and modification time of the audio file it belongs to and refuses to load against
+// a file that has changed since.
+
+use crate::audio_processing::AudioSegment;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// Bump when the snapshot format changes incompatibly
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// One segment as stored in a snapshot. Extra fields the frontend keeps on its
+/// segments (transcriptions and the like) are ignored when reading them in.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotSegment {
+    pub start_sample: i64,
+    pub end_sample: i64,
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
+    #[serde(default)]
+    pub snr_db: Option<f64>,
+    #[serde(default)]
+    pub overlap_seconds: f64,
+    #[serde(default)]
+    pub channel: Option<u8>,
+    // Base64 WAV, only kept when the snapshot was exported with audio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_base64: Option<String>,
+}
+
+impl SnapshotSegment {
+    fn to_audio_segment(&self) -> AudioSegment {
+        AudioSegment {
+            start_sample: self.start_sample,
+            end_sample: self.end_sample,
+            start_time_seconds: self.start_time_seconds,
+            end_time_seconds: self.end_time_seconds,
+            audio_data: Vec::new(),
+            audio_base64: self.audio_base64.clone().unwrap_or_default(),
+            snr_db: self.snr_db,
+            overlap_seconds: self.overlap_seconds,
+            channel: self.channel,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SegmentSnapshot {
+    version: u32,
+    file_size: u64,
+    modified_unix_ms: u64,
+    segments: Vec<SnapshotSegment>,
+}
+
+// Size and modification time (ms since the epoch) identifying a version of a file
+fn file_identity(file_path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(file_path)?;
+    let modified_unix_ms = metadata.modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or(0);
+    Ok((metadata.len(), modified_unix_ms))
+}
+
+/// Write `segments` of `file_path` to `output_path`, with their audio only if `include_audio`
+pub fn export(file_path: &Path, output_path: &Path, segments: &[SnapshotSegment], include_audio: bool) -> Result<(), String> {
+    let (file_size, modified_unix_ms) = file_identity(file_path)
+        .map_err(|e| format!("Failed to read audio file metadata: {}", e))?;
+    let segments = segments.iter()
+        .map(|segment| SnapshotSegment {
+            audio_base64: segment.audio_base64.clone().filter(|audio| include_audio && !audio.is_empty()),
+            ..segment.clone()
+        })
+        .collect();
+
+    let snapshot = SegmentSnapshot { version: SNAPSHOT_VERSION, file_size, modified_unix_ms, segments };
+    let data = serde_json::to_vec_pretty(&snapshot).map_err(|e| format!("Failed to serialize segments: {}", e))?;
+    fs::write(output_path, data).map_err(|e| format!("Failed to write segments file: {}", e))
+}
+
+/// Segments saved by `export` for `file_path`. Fails if the audio file has changed since,
+/// in which case it needs to be processed again. Segments exported without audio come
+/// back with none, to be extracted on demand.
+pub fn import(file_path: &Path, json_path: &Path) -> Result<Vec<AudioSegment>, String> {
+    let data = fs::read(json_path).map_err(|e| format!("Failed to read segments file: {}", e))?;
+    let snapshot: SegmentSnapshot = serde_json::from_slice(&data)
+        .map_err(|e| format!("Not a valid segments file: {}", e))?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(format!("Segments file has version {}, expected {}; process the audio again", snapshot.version, SNAPSHOT_VERSION));
+    }
+
+    let (file_size, modified_unix_ms) = file_identity(file_path)
+        .map_err(|e| format!("Failed to read audio file metadata: {}", e))?;
+    if file_size != snapshot.file_size || modified_unix_ms != snapshot.modified_unix_ms {
+        return Err(format!("{} has changed since its segments were exported; process it again", file_path.display()));
+    }
+
+    Ok(snapshot.segments.iter().map(SnapshotSegment::to_audio_segment).collect())
+}
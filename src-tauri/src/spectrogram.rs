@@ -0,0 +1,167 @@
+// STFT magnitude spectrograms rendered to PNG for visual inspection of audio
+
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+const FFT_SIZE: usize = 1024;
+// Lowest frequency shown on a log scale
+const LOG_MIN_FREQ: f64 = 20.0;
+// Levels this far below the loudest bin render as the bottom of the color map
+const DYNAMIC_RANGE_DB: f64 = 80.0;
+const MAX_DIMENSION: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrequencyScale {
+    #[default]
+    Linear,
+    Log,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMap {
+    #[default]
+    Viridis,
+    Magma,
+    Grayscale,
+}
+
+impl ColorMap {
+    // Evenly spaced color stops from quiet to loud
+    fn stops(&self) -> &'static [[u8; 3]] {
+        match self {
+            ColorMap::Viridis => &[[68, 1, 84], [59, 82, 139], [33, 145, 140], [94, 201, 98], [253, 231, 37]],
+            ColorMap::Magma => &[[0, 0, 4], [81, 18, 124], [183, 55, 121], [252, 137, 97], [252, 253, 191]],
+            ColorMap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+        }
+    }
+
+    fn color(&self, level: f64) -> [u8; 3] {
+        let stops = self.stops();
+        let position = level.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let index = (position.floor() as usize).min(stops.len() - 2);
+        let t = position - index as f64;
+        let (from, to) = (stops[index], stops[index + 1]);
+        [0, 1, 2].map(|c| (from[c] as f64 + (to[c] as f64 - from[c] as f64) * t).round() as u8)
+    }
+}
+
+/// Render mono samples as a `width` x `height` PNG, time left to right and
+/// frequency bottom to top. Levels are relative to the loudest bin in the range.
+pub fn render_png(
+    samples: &[i16],
+    sample_rate: u32,
+    width: u32,
+    height: u32,
+    scale: FrequencyScale,
+    color_map: ColorMap,
+) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!("Spectrogram size must be between 1x1 and {0}x{0}, got {1}x{2}", MAX_DIMENSION, width, height));
+    }
+    if samples.is_empty() {
+        return Err("No audio in the requested range".to_string());
+    }
+
+    let columns = magnitude_columns(samples, width as usize);
+    let nyquist = sample_rate as f64 / 2.0;
+    let bins = FFT_SIZE / 2;
+
+    // Range of frequency bins covered by each row, top row first
+    let bin_at = |position: f64| {
+        let freq = match scale {
+            FrequencyScale::Linear => position * nyquist,
+            FrequencyScale::Log => LOG_MIN_FREQ * (nyquist / LOG_MIN_FREQ).powf(position),
+        };
+        ((freq / nyquist * bins as f64).round() as usize).min(bins)
+    };
+    let row_bins: Vec<(usize, usize)> = (0..height)
+        .map(|y| {
+            let low = bin_at(1.0 - (y + 1) as f64 / height as f64);
+            let high = bin_at(1.0 - y as f64 / height as f64);
+            (low, high.max(low))
+        })
+        .collect();
+
+    let loudest = columns.iter().flatten().cloned().fold(f64::MIN_POSITIVE, f64::max);
+    let mut pixels = Vec::with_capacity((width * height * 3) as usize);
+    for &(low, high) in &row_bins {
+        for column in &columns {
+            let magnitude = column[low..=high].iter().cloned().fold(f64::MIN_POSITIVE, f64::max);
+            let db = 20.0 * (magnitude / loudest).log10();
+            pixels.extend_from_slice(&color_map.color(1.0 + db / DYNAMIC_RANGE_DB));
+        }
+    }
+
+    let mut png_bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png_bytes, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("Failed to write PNG header: {}", e))?;
+    writer.write_image_data(&pixels).map_err(|e| format!("Failed to write PNG data: {}", e))?;
+    writer.finish().map_err(|e| format!("Failed to finish PNG: {}", e))?;
+
+    Ok(png_bytes)
+}
+
+// One Hann-windowed FFT magnitude spectrum per output column, centred on that
+// column's position in the audio; frames running off either end are zero-padded
+fn magnitude_columns(samples: &[i16], width: usize) -> Vec<Vec<f64>> {
+    let fft = FftPlanner::<f64>::new().plan_fft_forward(FFT_SIZE);
+    let window: Vec<f64> = (0..FFT_SIZE)
+        .map(|i| 0.5 * (1.0 - (2.0 * PI * i as f64 / FFT_SIZE as f64).cos()))
+        .collect();
+
+    let mut buffer = vec![Complex::new(0.0, 0.0); FFT_SIZE];
+    (0..width)
+        .map(|x| {
+            let center = ((x as f64 + 0.5) / width as f64 * samples.len() as f64) as isize;
+            let frame_start = center - (FFT_SIZE / 2) as isize;
+            for (i, value) in buffer.iter_mut().enumerate() {
+                let sample = usize::try_from(frame_start + i as isize).ok()
+                    .and_then(|index| samples.get(index))
+                    .map_or(0.0, |&s| s as f64 / 32768.0);
+                *value = Complex::new(sample * window[i], 0.0);
+            }
+            fft.process(&mut buffer);
+            buffer[..=FFT_SIZE / 2].iter().map(|c| c.norm()).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, len: usize) -> Vec<i16> {
+        (0..len).map(|i| ((i as f64 / 16000.0 * 2.0 * PI * frequency).sin() * 8000.0) as i16).collect()
+    }
+
+    fn decode(png_bytes: Vec<u8>) -> (png::OutputInfo, Vec<u8>) {
+        let mut reader = png::Decoder::new(std::io::Cursor::new(png_bytes)).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        (info, pixels)
+    }
+
+    #[test]
+    fn renders_a_png_of_the_requested_size() {
+        let samples = sine(1000.0, 16000);
+        for scale in [FrequencyScale::Linear, FrequencyScale::Log] {
+            let (info, _) = decode(render_png(&samples, 16000, 320, 120, scale, ColorMap::Magma).unwrap());
+            assert_eq!((info.width, info.height), (320, 120));
+        }
+        assert!(render_png(&samples, 16000, 0, 10, FrequencyScale::Linear, ColorMap::Viridis).is_err());
+    }
+
+    #[test]
+    fn tone_is_drawn_at_its_frequency() {
+        // On a linear scale up to 8 kHz, 1 kHz lies 1/8 of the way up from the bottom row
+        let (info, pixels) = decode(render_png(&sine(1000.0, 16000), 16000, 10, 80, FrequencyScale::Linear, ColorMap::Grayscale).unwrap());
+        let brightness = |row: usize| pixels[row * info.line_size + 5 * 3];
+        assert!(brightness(70) > 240, "tone row has brightness {}", brightness(70));
+        assert!(brightness(10) < 60, "empty row has brightness {}", brightness(10));
+    }
+}
@@ -0,0 +1,352 @@
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
+}
+
+/// Approximate per-word timings for a transcribed segment.
+///
+/// This is a heuristic, not real forced alignment: the segment's time span is
+/// split across its words in proportion to their character length, which is
+/// close enough for karaoke-style highlighting but will drift on long pauses
+/// or very uneven speaking rates.
+pub fn align_words(text: &str, start_time_seconds: f64, end_time_seconds: f64) -> Vec<WordTiming> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() || end_time_seconds <= start_time_seconds {
+        return Vec::new();
+    }
+
+    distribute_span(&words, start_time_seconds, end_time_seconds)
+        .into_iter()
+        .zip(&words)
+        .map(|((word_start, word_end), word)| WordTiming {
+            word: word.to_string(),
+            start_time_seconds: word_start,
+            end_time_seconds: word_end,
+        })
+        .collect()
+}
+
+/// A timed block of subtitle text
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Cue {
+    pub text: String,
+    pub start_time_seconds: f64,
+    pub end_time_seconds: f64,
+}
+
+// Lower-case abbreviations (without their final period) that don't end a sentence
+const ABBREVIATIONS: &[&str] = &["mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc"];
+
+/// Split text into sentences on terminal punctuation (`.`, `!`, `?`, `…`),
+/// keeping common abbreviations, single-letter initials and dotted forms like
+/// `e.g.` or `p.m.` attached to the words that follow. Whitespace inside a
+/// sentence is normalized.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    for word in text.split_whitespace() {
+        current.push(word);
+        if ends_sentence(word) {
+            sentences.push(current.join(" "));
+            current.clear();
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current.join(" "));
+    }
+
+    sentences
+}
+
+fn ends_sentence(word: &str) -> bool {
+    // Look past closing quotes and brackets, as in `"Stop!"` or `(see above.)`
+    let word = word.trim_end_matches(['"', '\'', ')', ']', '»', '\u{201D}', '\u{2019}']);
+    if word.ends_with(['!', '?', '…']) || word.ends_with("...") {
+        return true;
+    }
+    if !word.ends_with('.') {
+        return false;
+    }
+
+    let stem = word.trim_end_matches('.').to_lowercase();
+    let is_initial = stem.chars().count() == 1 && stem.chars().all(char::is_alphabetic);
+    let is_dotted = stem.contains('.');
+    !is_initial && !is_dotted && !ABBREVIATIONS.contains(&stem.as_str())
+}
+
+/// One cue per sentence. The segment's time span is split across sentences in
+/// proportion to their character length, the same heuristic as `align_words`.
+pub fn sentence_cues(text: &str, start_time_seconds: f64, end_time_seconds: f64) -> Vec<Cue> {
+    let sentences = split_sentences(text);
+    if sentences.is_empty() || end_time_seconds <= start_time_seconds {
+        return Vec::new();
+    }
+
+    distribute_span(&sentences, start_time_seconds, end_time_seconds)
+        .into_iter()
+        .zip(sentences)
+        .map(|((start, end), text)| Cue {
+            text,
+            start_time_seconds: start,
+            end_time_seconds: end,
+        })
+        .collect()
+}
+
+/// Join consecutive segments into sentence-level ones: a segment whose text doesn't end
+/// a sentence is merged with the next one when the pause between them is at most
+/// `max_gap_seconds`. Merged segments span from the first start to the last end.
+pub fn group_sentences(segments: &[Cue], max_gap_seconds: f64) -> Vec<Cue> {
+    let mut grouped: Vec<Cue> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        if let Some(previous) = grouped.last_mut() {
+            let ends = previous.text.split_whitespace().last().is_some_and(ends_sentence);
+            if !ends && segment.start_time_seconds - previous.end_time_seconds <= max_gap_seconds {
+                previous.text = previous.text.split_whitespace().chain(segment.text.split_whitespace()).collect::<Vec<_>>().join(" ");
+                previous.end_time_seconds = previous.end_time_seconds.max(segment.end_time_seconds);
+                continue;
+            }
+        }
+        grouped.push(segment.clone());
+    }
+    grouped
+}
+
+// Split a time span across pieces of text in proportion to their character count
+fn distribute_span<S: AsRef<str>>(pieces: &[S], start_time_seconds: f64, end_time_seconds: f64) -> Vec<(f64, f64)> {
+    let total_chars: usize = pieces.iter().map(|p| p.as_ref().chars().count()).sum();
+    let duration = end_time_seconds - start_time_seconds;
+
+    let mut spans = Vec::with_capacity(pieces.len());
+    let mut chars_so_far = 0usize;
+
+    for (i, piece) in pieces.iter().enumerate() {
+        let piece_start = start_time_seconds + duration * chars_so_far as f64 / total_chars as f64;
+        chars_so_far += piece.as_ref().chars().count();
+
+        // Pin the last piece to the span end so rounding never leaves a gap
+        let piece_end = if i == pieces.len() - 1 {
+            end_time_seconds
+        } else {
+            start_time_seconds + duration * chars_so_far as f64 / total_chars as f64
+        };
+
+        spans.push((piece_start, piece_end));
+    }
+
+    spans
+}
+
+/// Wrap a cue to at most `max_chars_per_line` characters per line, splitting it
+/// into consecutive cues of at most `max_lines` lines when it doesn't fit. Lines
+/// only break between words, so a word longer than the limit gets a line of its
+/// own. The cue's time span is shared between the pieces by character count.
+pub fn wrap_cue(cue: &Cue, max_chars_per_line: usize, max_lines: usize) -> Vec<Cue> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in cue.text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= max_chars_per_line => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    if lines.is_empty() {
+        return vec![cue.clone()];
+    }
+
+    let blocks: Vec<String> = lines.chunks(max_lines.max(1)).map(|block| block.join("\n")).collect();
+    distribute_span(&blocks, cue.start_time_seconds, cue.end_time_seconds)
+        .into_iter()
+        .zip(blocks)
+        .map(|((start, end), text)| Cue {
+            text,
+            start_time_seconds: start,
+            end_time_seconds: end,
+        })
+        .collect()
+}
+
+/// Post-transcription check for text Whisper-style models tend to invent on
+/// non-speech audio: nothing at all, a stock phrase, or a phrase on repeat.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct HallucinationFilter {
+    pub phrases: Vec<String>, // Matched against the whole text, ignoring case and punctuation
+    pub min_repeats: usize,   // Consecutive repeats of a phrase that count as a loop
+}
+
+impl Default for HallucinationFilter {
+    fn default() -> Self {
+        Self {
+            phrases: [
+                "thank you",
+                "thanks for watching",
+                "thank you for watching",
+                "thank you so much for watching",
+                "please subscribe",
+                "like and subscribe",
+                "subtitles by the amara org community",
+                "you",
+            ].iter().map(|phrase| phrase.to_string()).collect(),
+            min_repeats: 4,
+        }
+    }
+}
+
+impl HallucinationFilter {
+    /// Why the text looks hallucinated, or None if it looks like real speech
+    pub fn check(&self, text: &str) -> Option<String> {
+        let normalized = Self::normalize(text);
+        if normalized.is_empty() {
+            return Some("empty transcription".to_string());
+        }
+
+        if self.phrases.iter().any(|phrase| Self::normalize(phrase) == normalized) {
+            return Some(format!("common hallucination \"{}\"", normalized));
+        }
+
+        let words: Vec<&str> = normalized.split(' ').collect();
+        Self::find_repetition(&words, self.min_repeats.max(2))
+            .map(|(phrase, repeats)| format!("\"{}\" repeated {} times", phrase, repeats))
+    }
+
+    fn normalize(phrase: &str) -> String {
+        phrase.split_whitespace()
+            .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // First run of a 1-8 word phrase repeated back to back at least `min_repeats` times
+    fn find_repetition(words: &[&str], min_repeats: usize) -> Option<(String, usize)> {
+        for length in 1..=8.min(words.len() / min_repeats) {
+            for start in 0..words.len() {
+                let phrase = match words.get(start..start + length) {
+                    Some(phrase) => phrase,
+                    None => break,
+                };
+                let repeats = words[start..].chunks_exact(length).take_while(|chunk| *chunk == phrase).count();
+                if repeats >= min_repeats {
+                    return Some((phrase.join(" "), repeats));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_words_are_monotonic_and_bounded() {
+        let words = align_words("so the quick brown fox jumped", 10.0, 12.5);
+        assert_eq!(words.len(), 6);
+        assert_eq!(words[0].start_time_seconds, 10.0);
+        assert_eq!(words[5].end_time_seconds, 12.5);
+        for word in &words {
+            assert!(word.start_time_seconds < word.end_time_seconds, "{:?}", word);
+        }
+        for pair in words.windows(2) {
+            assert!(pair[0].end_time_seconds <= pair[1].start_time_seconds + 1e-9, "{:?} overlaps {:?}", pair[0], pair[1]);
+        }
+
+        // Longer words get a longer share of the span
+        let duration = |word: &WordTiming| word.end_time_seconds - word.start_time_seconds;
+        assert!(duration(&words[3]) > duration(&words[0]));
+    }
+
+    #[test]
+    fn aligning_nothing_gives_no_words() {
+        assert!(align_words("   ", 0.0, 1.0).is_empty());
+        assert!(align_words("hello", 2.0, 2.0).is_empty());
+    }
+
+    #[test]
+    fn sentences_split_on_terminal_punctuation_only() {
+        let sentences = split_sentences("Dr. Smith arrived at 3.5 p.m. today e.g. now. He said \"Hello!\" Then J. R. left... Did it work? Yes");
+        assert_eq!(sentences, vec![
+            "Dr. Smith arrived at 3.5 p.m. today e.g. now.",
+            "He said \"Hello!\"",
+            "Then J. R. left...",
+            "Did it work?",
+            "Yes",
+        ]);
+    }
+
+    #[test]
+    fn sentence_cues_share_the_span_by_length() {
+        let cues = sentence_cues("One two. Three four five six.", 10.0, 13.0);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start_time_seconds, 10.0);
+        assert_eq!(cues[0].end_time_seconds, cues[1].start_time_seconds);
+        assert_eq!(cues[1].end_time_seconds, 13.0);
+        // 8 of the 28 characters are in the first sentence
+        assert!((cues[0].end_time_seconds - (10.0 + 3.0 * 8.0 / 28.0)).abs() < 1e-9);
+
+        assert!(sentence_cues("", 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn wrapped_cues_respect_line_limits_and_keep_timing() {
+        let text = "The quick brown fox jumps over the lazy dog and keeps running through the forest until nightfall when it finally rests beside a supercalifragilisticexpialidocious river";
+        let cue = Cue { text: text.to_string(), start_time_seconds: 5.0, end_time_seconds: 20.0 };
+        let wrapped = wrap_cue(&cue, 20, 2);
+        assert!(wrapped.len() > 1);
+
+        let mut previous_end = cue.start_time_seconds;
+        for piece in &wrapped {
+            let lines: Vec<&str> = piece.text.split('\n').collect();
+            assert!(lines.len() <= 2, "{:?}", piece);
+            for line in lines {
+                // Only a single word too long for any line may exceed the limit
+                assert!(line.chars().count() <= 20 || !line.contains(' '), "{:?} is too long", line);
+            }
+            assert_eq!(piece.start_time_seconds, previous_end);
+            previous_end = piece.end_time_seconds;
+        }
+        assert_eq!(previous_end, cue.end_time_seconds);
+
+        let words: Vec<&str> = wrapped.iter().flat_map(|piece| piece.text.split_whitespace()).collect();
+        assert_eq!(words.join(" "), text);
+    }
+
+    #[test]
+    fn short_cues_are_left_on_one_line() {
+        let cue = Cue { text: "Hello there".to_string(), start_time_seconds: 1.0, end_time_seconds: 2.0 };
+        let wrapped = wrap_cue(&cue, 42, 2);
+        assert_eq!(wrapped.len(), 1);
+        assert_eq!(wrapped[0].text, "Hello there");
+        assert_eq!((wrapped[0].start_time_seconds, wrapped[0].end_time_seconds), (1.0, 2.0));
+    }
+
+    #[test]
+    fn repeated_phrases_are_flagged_as_hallucinations() {
+        let filter = HallucinationFilter::default();
+        let looped = filter.check("I'm going to the store. I'm going to the store. I'm going to the store. I'm going to the store.");
+        assert_eq!(looped.as_deref(), Some("\"im going to the store\" repeated 4 times"));
+        assert!(filter.check("Okay. Hello hello hello hello hello").is_some());
+        // Three repeats are within what people actually say
+        assert!(filter.check("no no no").is_none());
+    }
+
+    #[test]
+    fn stock_phrases_and_empty_text_are_flagged() {
+        let filter = HallucinationFilter::default();
+        assert_eq!(filter.check("  ").as_deref(), Some("empty transcription"));
+        assert!(filter.check("Thank you for watching!").is_some());
+        // Stock phrases only count when they are the whole text
+        assert!(filter.check("So we went to the store and bought bread, and then we thanked everyone for watching the kids.").is_none());
+
+        let custom = HallucinationFilter { phrases: vec!["Untertitel im Auftrag des ZDF".to_string()], min_repeats: 4 };
+        assert!(custom.check("untertitel im auftrag des zdf.").is_some());
+        assert!(custom.check("Thank you for watching!").is_none());
+    }
+}
@@ -0,0 +1,752 @@
+// Request shaping for OpenAI-compatible transcription endpoints
+
+use crate::audio_processing::debug_log;
+use crate::transcript::WordTiming;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+// Longest single wait between attempts, whatever the backoff or Retry-After says
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// A stalled API connection fails after this long instead of hanging the command
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+// Keep idle connections around between segments and between batches a user starts
+// by hand, so most requests skip the TCP and TLS handshakes
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+// Far above what verbose JSON with word timings needs for a long segment
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 32 * 1024 * 1024;
+
+// Whisper only reads the last 224 tokens of a prompt; about 800 characters of English
+const DEFAULT_MAX_PROMPT_CHARS: usize = 800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormField {
+    File,
+    Model,
+}
+
+/// Names and order of the multipart fields sent to `/audio/transcriptions`.
+/// Defaults to the OpenAI convention (`file`, then `model`); gateways that want
+/// another order, another name, or no model field at all can override it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormLayout {
+    pub file_field: String,
+    pub model_field: String,
+    pub order: Vec<FormField>, // Fields left out are not sent; the file is required
+}
+
+impl Default for FormLayout {
+    fn default() -> Self {
+        Self {
+            file_field: "file".to_string(),
+            model_field: "model".to_string(),
+            order: vec![FormField::File, FormField::Model],
+        }
+    }
+}
+
+impl FormLayout {
+    /// Field names in the order they are sent
+    pub fn fields(&self) -> Result<Vec<(&str, FormField)>, String> {
+        let file_count = self.order.iter().filter(|&&field| field == FormField::File).count();
+        if file_count != 1 {
+            return Err("Form layout must contain the file field exactly once".to_string());
+        }
+        if self.order.iter().filter(|&&field| field == FormField::Model).count() > 1 {
+            return Err("Form layout lists the model field more than once".to_string());
+        }
+
+        Ok(self.order.iter()
+            .map(|&field| match field {
+                FormField::File => (self.file_field.as_str(), field),
+                FormField::Model => (self.model_field.as_str(), field),
+            })
+            .collect())
+    }
+
+    pub fn build_form(&self, audio_bytes: Vec<u8>, file_name: String, model_name: &str, options: &RequestOptions) -> Result<reqwest::multipart::Form, String> {
+        let mut form = reqwest::multipart::Form::new();
+        let mut audio_bytes = Some(audio_bytes);
+
+        for (name, field) in self.fields()? {
+            form = match field {
+                FormField::File => {
+                    let part = reqwest::multipart::Part::bytes(audio_bytes.take().unwrap_or_default())
+                        .file_name(file_name.clone())
+                        .mime_str("audio/wav")
+                        .map_err(|e| format!("Failed to set mime type: {}", e))?;
+                    form.part(name.to_string(), part)
+                }
+                FormField::Model => form.text(name.to_string(), model_name.to_string()),
+            };
+        }
+        if let Some(response_format) = &options.response_format {
+            form = form.text("response_format", response_format.clone());
+        }
+        // Without a language the API auto-detects it
+        if let Some(language) = &options.language {
+            form = form.text("language", language.clone());
+        }
+        if let Some(prompt) = &options.prompt {
+            form = form.text("prompt", prompt.clone());
+        }
+        // Without a temperature the server picks its own
+        if let Some(temperature) = options.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+
+        Ok(form)
+    }
+}
+
+/// Optional form fields sent with every transcription request
+#[derive(Debug, Clone)]
+pub struct RequestOptions {
+    pub response_format: Option<String>,
+    pub language: Option<String>,
+    /// Names, jargon or preceding text to bias recognition, at most `max_prompt_chars` long
+    pub prompt: Option<String>,
+    pub max_prompt_chars: usize,
+    /// Sampling temperature; 0 is deterministic. None leaves it to the server's default.
+    pub temperature: Option<f32>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            response_format: None,
+            language: None,
+            prompt: None,
+            max_prompt_chars: DEFAULT_MAX_PROMPT_CHARS,
+            temperature: None,
+        }
+    }
+}
+
+// Last `max_chars` characters of `text`, without the partial word the cut lands in
+fn prompt_tail(text: &str, max_chars: usize) -> &str {
+    let text = text.trim();
+    let char_count = text.chars().count();
+    if char_count <= max_chars {
+        return text;
+    }
+
+    let cut = text.char_indices().nth(char_count - max_chars).map_or(text.len(), |(index, _)| index);
+    let tail = &text[cut..];
+    if text[..cut].ends_with(char::is_whitespace) {
+        return tail;
+    }
+    // Text without spaces (e.g. Chinese or Japanese) is kept as cut
+    tail.find(char::is_whitespace).map_or(tail, |space| tail[space..].trim_start())
+}
+
+impl RequestOptions {
+    /// Checks that `language` looks like an ISO-639-1 code such as "en" or "de" and that
+    /// `temperature` is between 0 and 1, and cuts `prompt` to its last `max_prompt_chars`
+    /// characters (800 by default)
+    pub fn new(response_format: Option<String>, language: Option<String>, prompt: Option<String>, max_prompt_chars: Option<usize>, temperature: Option<f32>) -> Result<Self, String> {
+        let language = match language {
+            Some(code) => {
+                let normalized = code.trim().to_ascii_lowercase();
+                if normalized.len() != 2 || !normalized.bytes().all(|b| b.is_ascii_lowercase()) {
+                    return Err(format!("Language must be a two-letter ISO-639-1 code, got '{}'", code));
+                }
+                Some(normalized)
+            }
+            None => None,
+        };
+        let max_prompt_chars = max_prompt_chars.unwrap_or(DEFAULT_MAX_PROMPT_CHARS);
+        let prompt = prompt
+            .map(|prompt| prompt_tail(&prompt, max_prompt_chars).to_string())
+            .filter(|prompt| !prompt.is_empty());
+        if let Some(temperature) = temperature {
+            if !(0.0..=1.0).contains(&temperature) {
+                return Err(format!("Temperature must be between 0.0 and 1.0, got {}", temperature));
+            }
+        }
+        Ok(Self { response_format, language, prompt, max_prompt_chars, temperature })
+    }
+
+    /// These options with the previous segment's text appended to the prompt for
+    /// continuity. The fixed prompt keeps priority; the context gets what is left of
+    /// the character cap, keeping its most recent words.
+    pub fn with_context(&self, previous_text: &str) -> Self {
+        let fixed = self.prompt.as_deref().unwrap_or("");
+        let budget = self.max_prompt_chars.saturating_sub(fixed.chars().count() + 1);
+        let context = prompt_tail(previous_text, budget);
+        let prompt = match (fixed.is_empty(), context.is_empty()) {
+            (_, true) => self.prompt.clone(),
+            (true, false) => Some(context.to_string()),
+            (false, false) => Some(format!("{} {}", fixed, context)),
+        };
+        Self { prompt, ..self.clone() }
+    }
+}
+
+/// Settings an HTTP client is built with. Clients are built once per distinct config
+/// and kept in the backend state, so every transcription call with the same settings
+/// shares one connection pool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientConfig {
+    pub user_agent: String,
+    pub timeout: Duration,         // Whole request, upload and response included
+    pub connect_timeout: Duration,
+    pub max_response_bytes: usize, // After decompression
+}
+
+impl ClientConfig {
+    /// Timeouts default to 120s per request and 10s to connect, responses are limited to 32 MiB
+    pub fn new(user_agent: String, timeout_seconds: Option<u64>, connect_timeout_seconds: Option<u64>, max_response_bytes: Option<usize>) -> Result<Self, String> {
+        let timeout_seconds = timeout_seconds.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+        let connect_timeout_seconds = connect_timeout_seconds.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS);
+        if timeout_seconds == 0 || connect_timeout_seconds == 0 {
+            return Err("Timeouts must be at least 1 second".to_string());
+        }
+        let max_response_bytes = max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+        if max_response_bytes == 0 {
+            return Err("Maximum response size must be at least 1 byte".to_string());
+        }
+        Ok(Self {
+            user_agent,
+            timeout: Duration::from_secs(timeout_seconds),
+            connect_timeout: Duration::from_secs(connect_timeout_seconds),
+            max_response_bytes,
+        })
+    }
+
+    pub fn build(&self) -> Result<HttpClient, String> {
+        let client = reqwest::Client::builder()
+            .user_agent(&self.user_agent)
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        debug_log!("Created HTTP client ({}, timeout {}s, connect timeout {}s)",
+            self.user_agent, self.timeout.as_secs(), self.connect_timeout.as_secs());
+        Ok(HttpClient { client, timeout: self.timeout, max_response_bytes: self.max_response_bytes, request_count: None })
+    }
+}
+
+/// A reqwest client with the limits it was built with, so timeouts can be reported
+/// as such. Clones share one connection pool.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    timeout: Duration,
+    max_response_bytes: usize,
+    request_count: Option<Arc<AtomicUsize>>, // Requests sent, when counted
+}
+
+impl HttpClient {
+    /// A clone on the same connection pool that adds every request it sends to `count`
+    pub fn counting_requests(&self, count: Arc<AtomicUsize>) -> Self {
+        Self { request_count: Some(count), ..self.clone() }
+    }
+
+    // Tell timeouts apart from other network failures
+    fn describe_error(&self, context: &str, e: &reqwest::Error) -> String {
+        if e.is_timeout() {
+            format!("Transcription timed out after {}s", self.timeout.as_secs())
+        } else {
+            format!("{}: {}", context, e)
+        }
+    }
+
+    // Read a response body, gunzipping it if the server compressed it. Gives up once the
+    // body, compressed or not, passes max_response_bytes instead of buffering all of it.
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<String, RequestError> {
+        let too_large = || RequestError::fatal(format!("Transcription response exceeded the {} byte limit", self.max_response_bytes));
+        if response.content_length().is_some_and(|len| len > self.max_response_bytes as u64) {
+            return Err(too_large());
+        }
+        let gzipped = response.headers().get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("gzip"));
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await
+            .map_err(|e| RequestError::fatal(self.describe_error("Failed to read response", &e)))?
+        {
+            if body.len() + chunk.len() > self.max_response_bytes {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        if gzipped {
+            let mut decoded = Vec::new();
+            GzDecoder::new(body.as_slice())
+                .take(self.max_response_bytes as u64 + 1)
+                .read_to_end(&mut decoded)
+                .map_err(|e| RequestError::fatal(format!("Failed to decompress response: {}", e)))?;
+            if decoded.len() > self.max_response_bytes {
+                return Err(too_large());
+            }
+            body = decoded;
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Check that `base_url` is an absolute http(s) URL that `/audio/transcriptions` can be
+/// appended to, and return it without surrounding whitespace or trailing slashes
+pub fn normalize_base_url(base_url: &str) -> Result<String, String> {
+    let trimmed = base_url.trim();
+    let url = Url::parse(trimmed).map_err(|e| match e {
+        url::ParseError::RelativeUrlWithoutBase => format!("Base URL must start with http:// or https://, got '{}'", trimmed),
+        e => format!("Invalid base URL '{}': {}", trimmed, e),
+    })?;
+    // "localhost:8080" parses with "localhost" as its scheme
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(format!("Base URL must start with http:// or https://, got '{}'", trimmed));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("Base URL must not contain a query or fragment, got '{}'", trimmed));
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// Endpoint, model and credentials of one transcription provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backend {
+    pub base_url: String,
+    pub model_name: String,
+    pub api_key: String,
+    #[serde(default)]
+    pub form_layout: FormLayout,
+}
+
+/// How often to retry rate-limited (429), 500/502/503 and connection failures.
+/// Waits double from `initial_backoff_ms` unless the server sends Retry-After.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff_ms: 500,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(1u64 << retry.min(32))).min(MAX_RETRY_DELAY)
+    }
+}
+
+// Outcome of a failed request: whether trying again could help, and when
+struct RequestError {
+    message: String,
+    retryable: bool,
+    retry_after: Option<Duration>,
+}
+
+impl RequestError {
+    fn fatal(message: String) -> Self {
+        Self { message, retryable: false, retry_after: None }
+    }
+}
+
+/// What a backend recognized in one request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranscriptionResult {
+    pub text: String,
+    // Timed words (or whole API segments when the response has no word timings), relative
+    // to the start of the uploaded audio until shifted. Empty unless the response was verbose JSON.
+    pub segments: Vec<WordTiming>,
+    pub language: Option<String>,
+}
+
+impl TranscriptionResult {
+    /// Parse a response body: JSON with at least `text`, verbose JSON with `words` and/or
+    /// `segments`, or anything else taken as the plain transcript
+    pub fn parse(body: &str) -> Self {
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+            return Self { text: body.trim().to_string(), ..Default::default() };
+        };
+
+        let timings = |key: &str, text_key: &str| -> Vec<WordTiming> {
+            json.get(key)
+                .and_then(|v| v.as_array())
+                .map(|items| items.iter()
+                    .filter_map(|item| Some(WordTiming {
+                        word: item.get(text_key)?.as_str()?.trim().to_string(),
+                        start_time_seconds: item.get("start")?.as_f64()?,
+                        end_time_seconds: item.get("end")?.as_f64()?,
+                    }))
+                    .collect())
+                .unwrap_or_default()
+        };
+        let mut segments = timings("words", "word");
+        if segments.is_empty() {
+            segments = timings("segments", "text");
+        }
+
+        Self {
+            text: json.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            segments,
+            language: json.get("language").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        }
+    }
+
+    /// Move every timing by `seconds`, e.g. from the uploaded clip onto the source file's timeline
+    pub fn shift(&mut self, seconds: f64) {
+        for timing in &mut self.segments {
+            timing.start_time_seconds += seconds;
+            timing.end_time_seconds += seconds;
+        }
+    }
+}
+
+/// Transcribed text together with the backend that produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcription {
+    #[serde(flatten)]
+    pub result: TranscriptionResult,
+    pub base_url: String,
+    pub model_name: String,
+    pub used_fallback: bool,
+    pub hallucination: Option<String>, // Why the text was flagged when a hallucination filter was given
+}
+
+impl Backend {
+    /// Transcribe with this backend, retrying transient failures as `retry` allows
+    pub async fn transcribe(&self, client: &HttpClient, audio_bytes: Vec<u8>, file_name: String, options: &RequestOptions, retry: &RetryPolicy) -> Result<TranscriptionResult, String> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self.send(client, audio_bytes.clone(), file_name.clone(), options).await {
+                Ok(result) => return Ok(result),
+                Err(error) => error,
+            };
+
+            if !error.retryable || attempt > retry.max_retries {
+                return Err(if attempt > 1 {
+                    format!("{} (after {} attempts)", error.message, attempt)
+                } else {
+                    error.message
+                });
+            }
+
+            let delay = error.retry_after.unwrap_or_else(|| retry.backoff(attempt - 1)).min(MAX_RETRY_DELAY);
+            debug_log!("Transcription attempt {} failed ({}), retrying in {:.1}s", attempt, error.message, delay.as_secs_f64());
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    // Send one `/audio/transcriptions` request and return what was recognized
+    async fn send(&self, client: &HttpClient, audio_bytes: Vec<u8>, file_name: String, options: &RequestOptions) -> Result<TranscriptionResult, RequestError> {
+        // Create multipart form with the field names and order the backend expects
+        let form = self.form_layout.build_form(audio_bytes, file_name, &self.model_name, options)
+            .map_err(RequestError::fatal)?;
+
+        // Make the API request
+        if let Some(count) = &client.request_count {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+        let response = client.client
+            .post(format!("{}/audio/transcriptions", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header(reqwest::header::ACCEPT_ENCODING, "gzip, identity")
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| RequestError {
+                message: client.describe_error("Failed to send request", &e),
+                retryable: e.is_connect() || e.is_timeout(),
+                retry_after: None,
+            })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            // Only the delay-seconds form of Retry-After; HTTP dates fall back to backoff
+            let retry_after = response.headers().get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let error_text = client.read_body(response).await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(RequestError {
+                message: format!("API error {} for model {}: {}", status, self.model_name, describe_api_error(&error_text)),
+                retryable: matches!(status.as_u16(), 429 | 500 | 502 | 503),
+                retry_after,
+            });
+        }
+
+        // Parse the response; `response_format=text` bodies aren't JSON
+        let body = client.read_body(response).await?;
+        Ok(TranscriptionResult::parse(&body))
+    }
+
+    /// Check that the endpoint answers, accepts the API key and serves the model, without
+    /// transcribing anything. Uses the OpenAI `/models` endpoints; servers that have
+    /// neither only get their reachability and credentials checked.
+    pub async fn check(&self, client: &HttpClient) -> Result<(), String> {
+        let (status, body) = self.get(client, &["models", &self.model_name]).await?;
+        match status.as_u16() {
+            _ if status.is_success() => return Ok(()),
+            // Either the model or the endpoint is missing, the listing tells them apart
+            404 => {}
+            _ => return Err(self.describe_check_failure(status, &body)),
+        }
+
+        let (status, body) = self.get(client, &["models"]).await?;
+        match status.as_u16() {
+            _ if status.is_success() => {
+                // Model ids may contain slashes that the single-model lookup can't take
+                let listed = serde_json::from_str::<serde_json::Value>(&body).ok()
+                    .and_then(|json| json.get("data")?.as_array().cloned())
+                    .is_some_and(|models| models.iter()
+                        .any(|model| model.get("id").and_then(|id| id.as_str()) == Some(self.model_name.as_str())));
+                if listed {
+                    Ok(())
+                } else {
+                    Err(format!("Model '{}' is not available at {}", self.model_name, self.base_url))
+                }
+            }
+            // No model listing to check against
+            404 | 405 => Ok(()),
+            _ => Err(self.describe_check_failure(status, &body)),
+        }
+    }
+
+    // GET `path` below the base URL, returning the status and body
+    async fn get(&self, client: &HttpClient, path: &[&str]) -> Result<(reqwest::StatusCode, String), String> {
+        let mut url = Url::parse(&self.base_url).map_err(|e| format!("Invalid base URL '{}': {}", self.base_url, e))?;
+        url.path_segments_mut()
+            .map_err(|_| format!("Invalid base URL '{}'", self.base_url))?
+            .pop_if_empty()
+            .extend(path);
+
+        let response = client.client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header(reqwest::header::ACCEPT_ENCODING, "gzip, identity")
+            .send()
+            .await
+            .map_err(|e| if e.is_timeout() {
+                format!("{} did not respond within {}s", self.base_url, client.timeout.as_secs())
+            } else {
+                format!("Cannot reach {}: {}", self.base_url, e)
+            })?;
+        let status = response.status();
+        let body = client.read_body(response).await.map_err(|e| e.message)?;
+        Ok((status, body))
+    }
+
+    fn describe_check_failure(&self, status: reqwest::StatusCode, body: &str) -> String {
+        match status.as_u16() {
+            401 | 403 => format!("API key rejected by {} ({}): {}", self.base_url, status, describe_api_error(body)),
+            _ => format!("API error {} from {}: {}", status, self.base_url, describe_api_error(body)),
+        }
+    }
+}
+
+// The message (and type, if any) of an OpenAI-style `{"error": {"message", "type"}}`
+// body, or the body itself when it isn't one
+fn describe_api_error(body: &str) -> String {
+    let error = serde_json::from_str::<serde_json::Value>(body).ok()
+        .and_then(|json| json.get("error").cloned());
+    let message = match &error {
+        // Some compatible servers send the message as a plain string
+        Some(serde_json::Value::String(message)) => Some(message.clone()),
+        Some(error) => error.get("message").and_then(|v| v.as_str()).map(|message| {
+            match error.get("type").and_then(|v| v.as_str()) {
+                Some(kind) if !kind.is_empty() => format!("{} ({})", message, kind),
+                _ => message.to_string(),
+            }
+        }),
+        None => None,
+    };
+    message.unwrap_or_else(|| body.trim().to_string())
+}
+
+/// Transcribe with `primary`, trying `fallback` (if any) when the primary fails
+pub async fn transcribe_with_fallback(
+    client: &HttpClient,
+    primary: &Backend,
+    fallback: Option<&Backend>,
+    audio_bytes: Vec<u8>,
+    file_name: String,
+    options: &RequestOptions,
+    retry: &RetryPolicy,
+) -> Result<Transcription, String> {
+    let Some(fallback) = fallback else {
+        let result = primary.transcribe(client, audio_bytes, file_name, options, retry).await?;
+        return Ok(Transcription { result, base_url: primary.base_url.clone(), model_name: primary.model_name.clone(), used_fallback: false, hallucination: None });
+    };
+
+    let primary_error = match primary.transcribe(client, audio_bytes.clone(), file_name.clone(), options, retry).await {
+        Ok(result) => {
+            return Ok(Transcription { result, base_url: primary.base_url.clone(), model_name: primary.model_name.clone(), used_fallback: false, hallucination: None });
+        }
+        Err(e) => e,
+    };
+
+    debug_log!("Primary backend {} failed ({}), trying fallback {}", primary.base_url, primary_error, fallback.base_url);
+    match fallback.transcribe(client, audio_bytes, file_name, options, retry).await {
+        Ok(result) => Ok(Transcription { result, base_url: fallback.base_url.clone(), model_name: fallback.model_name.clone(), used_fallback: true, hallucination: None }),
+        Err(fallback_error) => Err(format!("{} (fallback also failed: {})", primary_error, fallback_error)),
+    }
+}
+
+/// Local HTTP server for tests that answers the n-th request with the n-th (status, body)
+/// pair, repeating the last one, and keeps every raw request it receives
+#[cfg(test)]
+pub(crate) async fn test_server(responses: Vec<(&'static str, &'static str)>) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+    test_server_with_latency(responses, Duration::ZERO).await
+}
+
+/// `test_server` that waits `latency` before answering each request
+#[cfg(test)]
+pub(crate) async fn test_server_with_latency(responses: Vec<(&'static str, &'static str)>, latency: Duration) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let received = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = vec![0u8; 65536];
+            loop {
+                let n = socket.read(&mut buffer).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..header_end].lines()
+                        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+            }
+            let (status, body) = {
+                let mut received = received.lock().unwrap();
+                received.push(String::from_utf8_lossy(&request).into_owned());
+                responses[(received.len() - 1).min(responses.len() - 1)]
+            };
+            tokio::time::sleep(latency).await;
+            let response = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (base_url, requests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(base_url: String, model_name: &str) -> Backend {
+        Backend { base_url, model_name: model_name.to_string(), api_key: "key".to_string(), form_layout: FormLayout::default() }
+    }
+
+    #[tokio::test]
+    async fn requests_carry_the_configured_user_agent() {
+        let (base_url, requests) = test_server(vec![("200 OK", r#"{"text":"hello"}"#)]).await;
+        let client = ClientConfig::new("acme-notes/2.1 (ops@acme.test)".to_string(), None, None, None).unwrap().build().unwrap();
+        let result = backend(base_url, "whisper-1")
+            .transcribe(&client, vec![1, 2, 3], "x.wav".to_string(), &RequestOptions::default(), &RetryPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(result.text, "hello");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].to_ascii_lowercase().contains("\r\nuser-agent: acme-notes/2.1 (ops@acme.test)\r\n"), "{}", requests[0]);
+    }
+
+    #[test]
+    fn form_layout_requires_exactly_one_file_field() {
+        assert_eq!(FormLayout::default().fields().unwrap(), vec![("file", FormField::File), ("model", FormField::Model)]);
+        let no_file = FormLayout { order: vec![FormField::Model], ..FormLayout::default() };
+        assert!(no_file.fields().is_err());
+        let two_models = FormLayout { order: vec![FormField::Model, FormField::File, FormField::Model], ..FormLayout::default() };
+        assert!(two_models.fields().is_err());
+    }
+
+    #[tokio::test]
+    async fn requests_use_the_configured_form_layout() {
+        let (base_url, requests) = test_server(vec![("200 OK", r#"{"text":"hello"}"#)]).await;
+        let client = ClientConfig::new("transcriber-test".to_string(), None, None, None).unwrap().build().unwrap();
+        let mut gateway = backend(base_url, "whisper-1");
+        gateway.form_layout = serde_json::from_str(r#"{"file_field":"audio","order":["model","file"]}"#).unwrap();
+        gateway.transcribe(&client, vec![1, 2, 3], "x.wav".to_string(), &RequestOptions::default(), &RetryPolicy::default())
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        let model = requests[0].find("name=\"model\"").expect("model field");
+        let audio = requests[0].find("name=\"audio\"; filename=\"x.wav\"").expect("audio field");
+        assert!(model < audio, "model is not sent before the audio");
+        assert!(!requests[0].contains("name=\"file\""));
+    }
+
+    #[tokio::test]
+    async fn fallback_backend_answers_when_the_primary_fails() {
+        let (failing_url, failing_requests) = test_server(vec![("500 Internal Server Error", r#"{"error":{"message":"boom"}}"#)]).await;
+        let (working_url, working_requests) = test_server(vec![("200 OK", r#"{"text":"hello"}"#)]).await;
+        let client = ClientConfig::new("transcriber-test".to_string(), None, None, None).unwrap().build().unwrap();
+        let no_retries = RetryPolicy { max_retries: 0, initial_backoff_ms: 1 };
+        let failing = backend(failing_url, "large");
+        let working = backend(working_url, "small");
+        let options = RequestOptions::default();
+
+        let transcription = transcribe_with_fallback(&client, &failing, Some(&working), vec![1, 2, 3], "x.wav".to_string(), &options, &no_retries).await.unwrap();
+        assert_eq!(transcription.result.text, "hello");
+        assert!(transcription.used_fallback);
+        assert_eq!(transcription.model_name, "small");
+        assert!(working_requests.lock().unwrap()[0].contains("small"));
+
+        // A working primary never touches the fallback
+        let transcription = transcribe_with_fallback(&client, &working, Some(&failing), vec![1, 2, 3], "x.wav".to_string(), &options, &no_retries).await.unwrap();
+        assert!(!transcription.used_fallback);
+        assert_eq!(transcription.model_name, "small");
+        assert_eq!(failing_requests.lock().unwrap().len(), 1);
+
+        let error = transcribe_with_fallback(&client, &failing, None, vec![1, 2, 3], "x.wav".to_string(), &options, &no_retries).await.unwrap_err();
+        assert!(error.contains("500") && error.contains("boom"), "{}", error);
+    }
+
+    #[test]
+    fn base_urls_lose_whitespace_and_trailing_slashes() {
+        assert_eq!(normalize_base_url("https://api.openai.com/v1/").unwrap(), "https://api.openai.com/v1");
+        assert_eq!(normalize_base_url(" http://localhost:8080// ").unwrap(), "http://localhost:8080");
+        assert_eq!(normalize_base_url("https://api.openai.com").unwrap(), "https://api.openai.com");
+    }
+
+    #[test]
+    fn malformed_base_urls_are_rejected() {
+        assert_eq!(normalize_base_url("localhost:8080/v1").unwrap_err(), "Base URL must start with http:// or https://, got 'localhost:8080/v1'");
+        assert_eq!(normalize_base_url("https://x/v1?a=1").unwrap_err(), "Base URL must not contain a query or fragment, got 'https://x/v1?a=1'");
+        for bad in ["api.openai.com/v1", "ftp://x/v1", "", "https://x/v1#top", "http://"] {
+            assert!(normalize_base_url(bad).is_err(), "accepted '{}'", bad);
+        }
+    }
+}
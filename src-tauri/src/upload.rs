@@ -0,0 +1,166 @@
+// Reassembly of files the frontend uploads in chunks. Each chunk is stored as
+// its own `{session}_{index}.part` file, so chunks may arrive in any order,
+// and the parts are joined in index order once every one of them is in.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Session ids end up in part file names, so they must be set and stay inside the directory
+pub fn check_session_id(session_id: &str) -> Result<(), String> {
+    if session_id.trim().is_empty() {
+        return Err("Session id must not be empty".to_string());
+    }
+    if session_id.contains(['/', '\\']) || session_id.contains("..") {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkedUpload {
+    dir: PathBuf,
+    session_id: String,
+    total_chunks: usize,
+}
+
+impl ChunkedUpload {
+    pub fn new(dir: PathBuf, session_id: String, total_chunks: usize) -> Self {
+        Self { dir, session_id, total_chunks }
+    }
+
+    /// The parts of `session_id` stored so far, for discarding an upload without knowing
+    /// how many chunks it was meant to have
+    pub fn in_progress(dir: PathBuf, session_id: String) -> Self {
+        let prefix = format!("{}_", session_id);
+        let total_chunks = fs::read_dir(&dir).into_iter().flatten().flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.strip_prefix(&prefix)?.strip_suffix(".part")?.parse::<usize>().ok())
+            .map(|index| index + 1)
+            .max()
+            .unwrap_or(0);
+        Self::new(dir, session_id, total_chunks)
+    }
+
+    fn part_path(&self, chunk_index: usize) -> PathBuf {
+        self.dir.join(format!("{}_{}.part", self.session_id, chunk_index))
+    }
+
+    /// Store one chunk. Duplicates and indices past the end are rejected.
+    pub fn store(&self, chunk_index: usize, data: &[u8]) -> Result<(), String> {
+        if chunk_index >= self.total_chunks {
+            return Err(format!("Chunk index {} is out of range for {} chunks", chunk_index, self.total_chunks));
+        }
+
+        fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
+        // create_new fails if this index was already received
+        let mut part = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.part_path(chunk_index))
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => format!("Chunk {} was already received", chunk_index),
+                _ => format!("Failed to write chunk {}: {}", chunk_index, e),
+            })?;
+        part.write_all(data).map_err(|e| format!("Failed to write chunk {}: {}", chunk_index, e))?;
+        part.flush().map_err(|e| format!("Failed to flush chunk {}: {}", chunk_index, e))
+    }
+
+    /// Indices that have not been stored yet
+    pub fn missing(&self) -> Vec<usize> {
+        (0..self.total_chunks)
+            .filter(|&index| !self.part_path(index).exists())
+            .collect()
+    }
+
+    /// Join all parts in order into `output` and delete them. Fails, listing the
+    /// missing indices, if any part has not arrived.
+    pub fn assemble(&self, output: &Path) -> Result<(), String> {
+        let missing = self.missing();
+        if !missing.is_empty() {
+            let list: Vec<String> = missing.iter().map(|index| index.to_string()).collect();
+            return Err(format!("Upload incomplete, missing chunks: {}", list.join(", ")));
+        }
+
+        let mut file = File::create(output).map_err(|e| format!("Failed to create upload file: {}", e))?;
+        for index in 0..self.total_chunks {
+            let mut part = File::open(self.part_path(index))
+                .map_err(|e| format!("Failed to read chunk {}: {}", index, e))?;
+            io::copy(&mut part, &mut file).map_err(|e| format!("Failed to assemble chunk {}: {}", index, e))?;
+        }
+        file.flush().map_err(|e| format!("Failed to flush upload file: {}", e))?;
+
+        self.discard();
+        Ok(())
+    }
+
+    /// Delete every stored part of this upload
+    pub fn discard(&self) {
+        for index in 0..self.total_chunks {
+            let _ = fs::remove_file(self.part_path(index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_temp_path;
+
+    #[test]
+    fn out_of_order_chunks_assemble_in_index_order() {
+        let dir = test_temp_path("upload-out-of-order");
+        let upload = ChunkedUpload::new(dir.clone(), "session".to_string(), 3);
+        let output = dir.join("assembled");
+
+        upload.store(2, b"C").unwrap();
+        upload.store(0, b"A").unwrap();
+        assert_eq!(upload.missing(), vec![1]);
+        assert_eq!(upload.assemble(&output).unwrap_err(), "Upload incomplete, missing chunks: 1");
+
+        upload.store(1, b"B").unwrap();
+        upload.assemble(&output).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"ABC");
+        // The parts are gone once assembled
+        assert_eq!(upload.missing(), vec![0, 1, 2]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_and_out_of_range_chunks_are_rejected() {
+        let dir = test_temp_path("upload-rejects");
+        let upload = ChunkedUpload::new(dir.clone(), "session".to_string(), 2);
+
+        upload.store(0, b"A").unwrap();
+        assert_eq!(upload.store(0, b"A").unwrap_err(), "Chunk 0 was already received");
+        assert_eq!(upload.store(2, b"X").unwrap_err(), "Chunk index 2 is out of range for 2 chunks");
+
+        upload.discard();
+        assert_eq!(upload.missing(), vec![0, 1]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn in_progress_uploads_discard_only_their_own_parts() {
+        let dir = test_temp_path("upload-in-progress");
+        ChunkedUpload::new(dir.clone(), "7".to_string(), 4).store(2, b"C").unwrap();
+        ChunkedUpload::new(dir.clone(), "7".to_string(), 4).store(0, b"A").unwrap();
+        ChunkedUpload::new(dir.clone(), "77".to_string(), 2).store(1, b"B").unwrap();
+        fs::write(dir.join("7_notes.txt"), b"kept").unwrap();
+
+        ChunkedUpload::in_progress(dir.clone(), "7".to_string()).discard();
+        let mut left: Vec<String> = fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["77_1.part", "7_notes.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_and_escaping_session_ids_are_rejected() {
+        assert!(check_session_id("1697040000abc").is_ok());
+        assert_eq!(check_session_id("").unwrap_err(), "Session id must not be empty");
+        assert_eq!(check_session_id("  ").unwrap_err(), "Session id must not be empty");
+        assert!(check_session_id("../other").is_err());
+    }
+}
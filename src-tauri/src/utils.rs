@@ -1,3 +1,8 @@
+use base64::alphabet;
+use base64::engine::general_purpose::{self, GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+
 #[derive(Debug, Clone, Copy)]
 pub enum SampleRate {
     EightkHz,
@@ -17,6 +22,46 @@ impl From<SampleRate> for i64 {
     }
 }
 
+impl From<SampleRate> for u32 {
+    fn from(value: SampleRate) -> Self {
+        match value {
+            SampleRate::EightkHz => 8000,
+            SampleRate::SixteenkHz => 16000,
+            SampleRate::FourtyFourKHz => 44100,
+            SampleRate::FourtyEightKHz => 48000,
+        }
+    }
+}
+
+impl SampleRate {
+    /// Rates Silero VAD accepts, for a rate in Hz
+    pub fn for_vad(hz: u32) -> Option<Self> {
+        match hz {
+            8000 => Some(SampleRate::EightkHz),
+            16000 => Some(SampleRate::SixteenkHz),
+            _ => None,
+        }
+    }
+
+    /// Samples per VAD chunk; Silero only supports these two rates
+    pub fn vad_chunk_size(self) -> Option<usize> {
+        match self {
+            SampleRate::EightkHz => Some(256),
+            SampleRate::SixteenkHz => Some(512),
+            _ => None,
+        }
+    }
+}
+
+/// Samples per Silero VAD chunk at `hz`: 512 at 16kHz, 256 at 8kHz. The model builder
+/// rejects any other pairing, so every VAD rate goes through here and other rates get an
+/// error naming the supported ones.
+pub fn vad_chunk_size_for(hz: u32) -> Result<usize, String> {
+    SampleRate::for_vad(hz)
+        .and_then(SampleRate::vad_chunk_size)
+        .ok_or_else(|| format!("VAD only supports 8000 or 16000 Hz, got {} Hz", hz))
+}
+
 impl From<SampleRate> for usize {
     fn from(value: SampleRate) -> Self {
         match value {
@@ -63,4 +108,108 @@ impl std::fmt::Display for TimeStamp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "[start:{:08}, end:{:08}]", self.start, self.end)
     }
-}
\ No newline at end of file
+}
+
+/// Base64 alphabet and padding used when encoding audio for the frontend or an API
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Base64Variant {
+    #[default]
+    Standard,
+    StandardNoPad,
+    UrlSafe,
+    UrlSafeNoPad,
+}
+
+impl Base64Variant {
+    pub fn engine(self) -> &'static GeneralPurpose {
+        match self {
+            Base64Variant::Standard => &general_purpose::STANDARD,
+            Base64Variant::StandardNoPad => &general_purpose::STANDARD_NO_PAD,
+            Base64Variant::UrlSafe => &general_purpose::URL_SAFE,
+            Base64Variant::UrlSafeNoPad => &general_purpose::URL_SAFE_NO_PAD,
+        }
+    }
+
+    pub fn encode(self, data: &[u8]) -> String {
+        self.engine().encode(data)
+    }
+}
+
+// Lenient decoders that accept input with or without padding
+const STANDARD_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+const URL_SAFE_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// Alphabet of all audio this app hands out as base64, to the frontend and to transcription
+// APIs alike. Changing it here switches every encoder at once.
+pub const AUDIO_BASE64: Base64Variant = Base64Variant::Standard;
+
+/// Base64 of a WAV file in the app's audio alphabet (`AUDIO_BASE64`)
+pub fn encode_wav_b64(wav: &[u8]) -> String {
+    AUDIO_BASE64.encode(wav)
+}
+
+/// Decode base64 in any of the supported variants, padded or not
+pub fn decode_b64(input: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    if input.contains(['-', '_']) {
+        URL_SAFE_LENIENT.decode(input)
+    } else {
+        STANDARD_LENIENT.decode(input)
+    }
+}
+
+/// Unique temp path for a test file or directory, removed by the caller
+#[cfg(test)]
+pub(crate) fn test_temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("transcriber-test-{}-{}", std::process::id(), name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vad_chunk_size_follows_rate() {
+        assert_eq!(vad_chunk_size_for(16000), Ok(512));
+        assert_eq!(vad_chunk_size_for(8000), Ok(256));
+        assert_eq!(vad_chunk_size_for(44100), Err("VAD only supports 8000 or 16000 Hz, got 44100 Hz".to_string()));
+        assert_eq!(SampleRate::for_vad(8000).and_then(SampleRate::vad_chunk_size), Some(256));
+        assert!(SampleRate::for_vad(48000).is_none());
+    }
+
+    #[test]
+    fn wav_base64_round_trip() {
+        let processor = crate::audio_processing::AudioProcessor::new();
+        let samples: Vec<i16> = (0..1000).map(|i| ((i * 97) % 65536 - 32768) as i16).collect();
+        let wav = processor.samples_to_wav_bytes(&samples, 16000).unwrap();
+
+        let decoded = decode_b64(&encode_wav_b64(&wav)).unwrap();
+        assert_eq!(decoded, wav);
+        let round_tripped: Vec<i16> = hound::WavReader::new(std::io::Cursor::new(decoded)).unwrap()
+            .into_samples::<i16>()
+            .map(|sample| sample.unwrap())
+            .collect();
+        assert_eq!(round_tripped, samples);
+    }
+
+    #[test]
+    fn every_base64_variant_round_trips() {
+        // Bytes that encode to '+' and '/' in the standard alphabet, and a length that needs padding
+        let data: Vec<u8> = (0..=255u8).chain([0xfb, 0xff, 0xfe, 0x3e]).collect();
+        for variant in [Base64Variant::Standard, Base64Variant::StandardNoPad, Base64Variant::UrlSafe, Base64Variant::UrlSafeNoPad] {
+            let encoded = variant.encode(&data);
+            let url_safe = matches!(variant, Base64Variant::UrlSafe | Base64Variant::UrlSafeNoPad);
+            let padded = matches!(variant, Base64Variant::Standard | Base64Variant::UrlSafe);
+            assert_eq!(encoded.contains(['-', '_']), url_safe, "{:?}: {}", variant, encoded);
+            assert_eq!(encoded.contains(['+', '/']), !url_safe, "{:?}: {}", variant, encoded);
+            assert_eq!(encoded.ends_with('='), padded, "{:?}: {}", variant, encoded);
+            assert_eq!(decode_b64(&encoded).unwrap(), data, "{:?}", variant);
+        }
+    }
+}
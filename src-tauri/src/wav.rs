@@ -0,0 +1,303 @@
+// Hand-rolled RIFF/WAVE handling for the cases Symphonia doesn't cover.
+// Everything this app writes is little-endian RIFF; big-endian input is
+// converted to that layout before decoding.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Format tags of the `fmt ` chunk
+pub const FORMAT_PCM: u16 = 1;
+pub const FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Write the 44-byte header of a mono WAV file whose data chunk holds `num_samples`
+/// samples of `bits_per_sample` bits each
+pub fn write_header<W: Write>(out: &mut W, sample_rate: u32, format_tag: u16, bits_per_sample: u16, num_samples: usize) -> io::Result<()> {
+    let bytes_per_sample = u32::from(bits_per_sample / 8);
+    let data_size = u32::try_from(num_samples as u64 * bytes_per_sample as u64)
+        .ok()
+        .filter(|&size| size <= u32::MAX - 36)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Audio is too long for a WAV file"))?;
+
+    // RIFF header
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_size).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+
+    // fmt chunk
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?; // chunk size
+    out.write_all(&format_tag.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?; // mono
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * bytes_per_sample).to_le_bytes())?; // byte rate
+    out.write_all(&(bytes_per_sample as u16).to_le_bytes())?; // block align
+    out.write_all(&bits_per_sample.to_le_bytes())?;
+
+    // data chunk
+    out.write_all(b"data")?;
+    out.write_all(&data_size.to_le_bytes())
+}
+
+/// True if the data starts with a big-endian RIFX WAVE header
+pub fn is_rifx(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[0..4] == b"RIFX" && &data[8..12] == b"WAVE"
+}
+
+/// Convert a big-endian RIFX WAVE file into the equivalent little-endian RIFF file.
+/// Chunk sizes, `fmt ` fields and PCM sample data are byte-swapped; other chunks are copied as-is.
+pub fn rifx_to_riff(data: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_rifx(data) {
+        return Err("Not a RIFX WAVE file".to_string());
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&read_u32_be(data, 4)?.to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+
+    let mut bytes_per_sample = 0usize;
+    let mut pos = 12;
+
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = read_u32_be(data, pos + 4)? as usize;
+        let body_start = pos + 8;
+        // Tolerate a truncated final chunk rather than rejecting the whole file
+        let body_end = (body_start + size).min(data.len());
+        let body = &data[body_start..body_end];
+
+        out.extend_from_slice(id);
+        out.extend_from_slice(&(size as u32).to_le_bytes());
+
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err("RIFX fmt chunk is too short".to_string());
+                }
+                // format tag, channels, sample rate, byte rate, block align, bits per sample
+                let mut fmt = body.to_vec();
+                for (offset, width) in [(0, 2), (2, 2), (4, 4), (8, 4), (12, 2), (14, 2)] {
+                    fmt[offset..offset + width].reverse();
+                }
+                if fmt.len() >= 18 {
+                    // cbSize of the extension block
+                    fmt[16..18].reverse();
+                }
+                // Swap whole sample containers, which may be wider than bits_per_sample
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]) as usize;
+                let block_align = u16::from_le_bytes([fmt[12], fmt[13]]) as usize;
+                bytes_per_sample = block_align.checked_div(channels).unwrap_or(0);
+                out.extend_from_slice(&fmt);
+            }
+            b"data" => {
+                if bytes_per_sample > 1 {
+                    for sample in body.chunks(bytes_per_sample) {
+                        out.extend(sample.iter().rev());
+                    }
+                } else {
+                    out.extend_from_slice(body);
+                }
+            }
+            _ => out.extend_from_slice(body),
+        }
+
+        // Chunks are padded to an even number of bytes
+        pos = body_start + size + (size & 1);
+        if size & 1 == 1 && pos <= data.len() {
+            out.push(0);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Size the data chunk of a WAV file declares, where it disagrees with the bytes the
+/// file actually holds for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLengthMismatch {
+    pub declared: u32,
+    pub actual: u64,
+    size_offset: u64, // Where the declared size is stored
+}
+
+impl DataLengthMismatch {
+    /// Recorders that stream WAV write 0 or 0xFFFFFFFF and never go back to fill in the size
+    pub fn is_placeholder(&self) -> bool {
+        self.declared == 0 || self.declared == u32::MAX
+    }
+}
+
+/// Compare the size declared by the data chunk of a RIFF WAVE file with what follows
+/// it. Chunks after the data (LIST tags and the like) are fine as long as they end with
+/// the file. None for consistent files and anything that isn't RIFF WAVE.
+pub fn check_data_length<R: Read + Seek>(reader: &mut R) -> io::Result<Option<DataLengthMismatch>> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut header = [0u8; 12];
+    if reader.read_exact(&mut header).is_err() || &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+
+    // Walk the chunks up to the end of the file or the first thing after the data that
+    // isn't a chunk, which means the data runs on past its declared size
+    let mut data = None;
+    let mut overrun = false;
+    let mut pos = 12u64;
+    while pos + 8 <= file_len {
+        reader.seek(SeekFrom::Start(pos))?;
+        let mut chunk = [0u8; 8];
+        reader.read_exact(&mut chunk)?;
+        let id = &chunk[0..4];
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        if data.is_some() && !id.iter().all(|&b| b.is_ascii_graphic() || b == b' ') {
+            overrun = true;
+            break;
+        }
+        if id == b"data" && data.is_none() {
+            data = Some((pos + 4, size));
+        }
+        // Chunks are padded to an even number of bytes
+        pos += 8 + u64::from(size) + u64::from(size & 1);
+    }
+
+    // Without a data chunk there is nothing to compare; decoding reports that itself
+    let Some((size_offset, declared)) = data else {
+        return Ok(None);
+    };
+    let actual = file_len - (size_offset + 4);
+    // A missing pad byte after the last chunk is common and harmless
+    if u64::from(declared) > actual || overrun || pos > file_len + 1 {
+        Ok(Some(DataLengthMismatch { declared, actual, size_offset }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Make the data chunk of a whole WAV file in memory run to its end, fixing the RIFF
+/// size with it, for files whose data size `check_data_length` found to be wrong
+pub fn patch_data_length(data: &mut [u8], mismatch: &DataLengthMismatch) {
+    let offset = mismatch.size_offset as usize;
+    if data.len() < offset + 4 {
+        return;
+    }
+    let data_size = u32::try_from(mismatch.actual).unwrap_or(u32::MAX);
+    data[offset..offset + 4].copy_from_slice(&data_size.to_le_bytes());
+    let riff_size = u32::try_from(data.len() as u64 - 8).unwrap_or(u32::MAX);
+    data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "Unexpected end of WAV header".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcm16_wav(samples: &[i16]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_header(&mut out, 16000, FORMAT_PCM, 16, samples.len()).unwrap();
+        out.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+        out
+    }
+
+    // The big-endian twin of a 44-byte-header RIFF file written by write_header
+    fn to_rifx(riff: &[u8], bytes_per_sample: usize) -> Vec<u8> {
+        let mut rifx = riff.to_vec();
+        rifx[0..4].copy_from_slice(b"RIFX");
+        for (offset, width) in [(4, 4), (16, 4), (20, 2), (22, 2), (24, 4), (28, 4), (32, 2), (34, 2), (40, 4)] {
+            rifx[offset..offset + width].reverse();
+        }
+        for sample in rifx[44..].chunks_mut(bytes_per_sample) {
+            sample.reverse();
+        }
+        rifx
+    }
+
+    #[test]
+    fn rifx_converts_to_the_equivalent_riff() {
+        let samples: Vec<i16> = (0..1000).map(|i| (i as i16).wrapping_mul(37)).collect();
+        let riff = pcm16_wav(&samples);
+        let rifx = to_rifx(&riff, 2);
+        assert!(is_rifx(&rifx) && !is_rifx(&riff));
+        assert_eq!(rifx_to_riff(&rifx).unwrap(), riff);
+        assert!(rifx_to_riff(&riff).is_err());
+    }
+
+    #[test]
+    fn rifx_decodes_to_the_same_samples_as_riff() {
+        let processor = crate::audio_processing::AudioProcessor::new();
+        let samples: Vec<i16> = (0..16000).map(|i| ((i as f64 * 0.05).sin() * 20000.0) as i16).collect();
+        let rifx = to_rifx(&pcm16_wav(&samples), 2);
+
+        let (from_bytes, sample_rate) = processor.decode_audio_bytes(&rifx, Some("wav")).unwrap();
+        assert_eq!((from_bytes.as_slice(), sample_rate), (samples.as_slice(), 16000));
+
+        let path = crate::utils::test_temp_path("big-endian.wav");
+        std::fs::write(&path, &rifx).unwrap();
+        let decoded = processor.decode_audio_symphonia(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        assert_eq!(decoded.unwrap().0, samples);
+    }
+
+    fn counting_wav(len: usize) -> Vec<u8> {
+        pcm16_wav(&(0..len).map(|i| (i as i16).wrapping_mul(37)).collect::<Vec<_>>())
+    }
+
+    fn check(data: &[u8]) -> Option<DataLengthMismatch> {
+        check_data_length(&mut std::io::Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn data_length_matching_the_contents_passes() {
+        let good = counting_wav(1000);
+        assert_eq!(check(&good), None);
+
+        // A trailing LIST chunk, even without its pad byte
+        let mut tagged = good.clone();
+        tagged.extend_from_slice(b"LIST");
+        tagged.extend_from_slice(&5u32.to_le_bytes());
+        tagged.extend_from_slice(b"INFOx");
+        assert_eq!(check(&tagged), None);
+
+        // An odd-sized chunk before the data, padded to an even length
+        let mut extra_chunk = good[..36].to_vec();
+        extra_chunk.extend_from_slice(b"junk");
+        extra_chunk.extend_from_slice(&3u32.to_le_bytes());
+        extra_chunk.extend_from_slice(&[1, 2, 3, 0]);
+        extra_chunk.extend_from_slice(&good[36..]);
+        let riff_size = ((extra_chunk.len() - 8) as u32).to_le_bytes();
+        extra_chunk[4..8].copy_from_slice(&riff_size);
+        assert_eq!(check(&extra_chunk), None);
+
+        assert_eq!(check(b"ID3 not a wav file at all"), None);
+    }
+
+    #[test]
+    fn mismatched_data_length_is_reported() {
+        let good = counting_wav(1000);
+
+        let truncated = check(&good[..1044]).unwrap();
+        assert_eq!((truncated.declared, truncated.actual), (2000, 1000));
+        assert!(!truncated.is_placeholder());
+
+        let mut understated = good.clone();
+        understated[40..44].copy_from_slice(&1000u32.to_le_bytes());
+        let understated = check(&understated).unwrap();
+        assert_eq!((understated.declared, understated.actual), (1000, 2000));
+    }
+
+    #[test]
+    fn placeholder_data_lengths_are_patched() {
+        let good = counting_wav(1000);
+        for placeholder in [0u32, u32::MAX] {
+            let mut streamed = good.clone();
+            streamed[40..44].copy_from_slice(&placeholder.to_le_bytes());
+            let mismatch = check(&streamed).unwrap();
+            assert!(mismatch.is_placeholder());
+            patch_data_length(&mut streamed, &mismatch);
+            assert_eq!(streamed, good);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// Fixture audio for the integration tests. The WAVs are synthesized on the fly into
+// the temp directory instead of being checked in, so each fixture is described by the
+// bursts it contains rather than a binary blob.
+
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Well above the loudness gate of VadBackend::Rms (-40 dBFS)
+const BURST_AMPLITUDE: f64 = 0.5;
+const BURST_FREQUENCY_HZ: f64 = 440.0;
+
+// Tests run in parallel and each removes its fixture when done, so every fixture gets a file of its own
+static FIXTURE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Write a mono 16-bit WAV of `duration_seconds` at `sample_rate` that is silent except
+/// for a sine tone during each `(start, end)` burst, in seconds. Returns its path.
+pub fn write_fixture(name: &str, sample_rate: u32, duration_seconds: f64, bursts: &[(f64, f64)]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("transcriber-fixture-{}-{}-{}.wav", std::process::id(), FIXTURE_COUNT.fetch_add(1, Ordering::Relaxed), name));
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).expect("create fixture");
+
+    let total_samples = (duration_seconds * sample_rate as f64).round() as usize;
+    for i in 0..total_samples {
+        let t = i as f64 / sample_rate as f64;
+        let sample = if bursts.iter().any(|&(start, end)| (start..end).contains(&t)) {
+            BURST_AMPLITUDE * (2.0 * PI * BURST_FREQUENCY_HZ * t).sin()
+        } else {
+            0.0
+        };
+        writer.write_sample((sample * i16::MAX as f64).round() as i16).expect("write fixture sample");
+    }
+    writer.finalize().expect("finalize fixture");
+    path
+}
+
+/// A few seconds of digital silence
+pub fn silence(sample_rate: u32) -> PathBuf {
+    write_fixture(&format!("silence-{}", sample_rate), sample_rate, 4.0, &[])
+}
+
+/// One tone burst from 1.0s to 3.0s
+pub fn single_burst(sample_rate: u32) -> PathBuf {
+    write_fixture(&format!("single-burst-{}", sample_rate), sample_rate, 5.0, &[(1.0, 3.0)])
+}
+
+/// Tone bursts at 1.0s-2.5s and 5.0s-6.5s, two and a half seconds apart
+pub fn two_bursts(sample_rate: u32) -> PathBuf {
+    write_fixture(&format!("two-bursts-{}", sample_rate), sample_rate, 8.0, &[(1.0, 2.5), (5.0, 6.5)])
+}
+
+/// Half a minute of bursts with gaps both shorter and longer than the merge gap, the
+/// last running to the end of the file
+pub fn many_bursts(sample_rate: u32) -> PathBuf {
+    let bursts = [(1.0, 2.5), (5.0, 6.5), (7.2, 8.0), (15.0, 20.0), (20.5, 21.0), (26.0, 30.0)];
+    write_fixture(&format!("many-bursts-{}", sample_rate), sample_rate, 30.0, &bursts)
+}
@@ -0,0 +1,234 @@
+// End-to-end runs of the VAD pipeline over synthesized fixtures, without Tauri. The
+// loudness backend stands in for Silero: it needs no model, and the model rightly
+// hears no speech in a sine tone.
+
+mod common;
+
+use std::cell::RefCell;
+use transcriber_lib::audio_processing::{AudioProcessor, AudioSegment, SegmentUpdate, VadBackend};
+
+// Segment edges may land this far outside a burst: padding and the loudness gate's
+// hang time extend speech past where the tone stops
+const EDGE_TOLERANCE_SECONDS: f64 = 0.5;
+
+fn processor() -> AudioProcessor {
+    let mut processor = AudioProcessor::new();
+    processor.set_vad_backend(VadBackend::Rms);
+    processor
+}
+
+fn process(processor: &mut AudioProcessor, path: &std::path::Path) -> Vec<AudioSegment> {
+    let segments = processor.process_audio_file(path.to_str().unwrap(), "").expect("process fixture");
+    std::fs::remove_file(path).ok();
+    segments
+}
+
+// Segments of the fixture from the whole-file pipeline and from the streaming one
+fn process_both_ways(path: &std::path::Path) -> (Vec<AudioSegment>, Vec<AudioSegment>) {
+    let mut whole = processor();
+    whole.set_streaming_threshold(None).unwrap();
+    let whole_segments = whole.process_audio_file(path.to_str().unwrap(), "").expect("process fixture whole");
+
+    let mut streaming = processor();
+    streaming.set_streaming_threshold(Some(0.0)).unwrap();
+    (whole_segments, process(&mut streaming, path))
+}
+
+fn assert_covers(segment: &AudioSegment, start: f64, end: f64) {
+    let (found_start, found_end) = (segment.start_time_seconds, segment.end_time_seconds);
+    assert!(
+        found_start <= start + 0.05 && found_start >= start - EDGE_TOLERANCE_SECONDS,
+        "segment starts at {:.3}s, expected about {:.3}s", found_start, start,
+    );
+    assert!(
+        found_end >= end - 0.05 && found_end <= end + EDGE_TOLERANCE_SECONDS,
+        "segment ends at {:.3}s, expected about {:.3}s", found_end, end,
+    );
+}
+
+#[test]
+fn silence_has_no_segments() {
+    let segments = process(&mut processor(), &common::silence(16000));
+    assert!(segments.is_empty(), "found {} segments in silence", segments.len());
+}
+
+#[test]
+fn single_burst_is_one_segment() {
+    let segments = process(&mut processor(), &common::single_burst(16000));
+    assert_eq!(segments.len(), 1);
+    assert_covers(&segments[0], 1.0, 3.0);
+}
+
+#[test]
+fn two_bursts_are_two_segments() {
+    let segments = process(&mut processor(), &common::two_bursts(16000));
+    assert_eq!(segments.len(), 2);
+    assert_covers(&segments[0], 1.0, 2.5);
+    assert_covers(&segments[1], 5.0, 6.5);
+}
+
+#[test]
+fn segments_carry_their_audio() {
+    let segments = process(&mut processor(), &common::single_burst(16000));
+    let segment = &segments[0];
+    let expected_samples = (segment.end_sample - segment.start_sample) as usize;
+    assert_eq!(segment.audio_data.len(), expected_samples);
+    assert!(!segment.audio_base64.is_empty());
+}
+
+#[test]
+fn resampled_input_keeps_timestamps() {
+    let segments = process(&mut processor(), &common::two_bursts(44100));
+    assert_eq!(segments.len(), 2);
+    assert_covers(&segments[0], 1.0, 2.5);
+    assert_covers(&segments[1], 5.0, 6.5);
+}
+
+#[test]
+fn eight_khz_vad_keeps_timestamps() {
+    let mut processor = processor();
+    processor.set_sample_rate(8000).unwrap();
+    let segments = process(&mut processor, &common::two_bursts(8000));
+    assert_eq!(segments.len(), 2);
+    assert_covers(&segments[0], 1.0, 2.5);
+    assert_covers(&segments[1], 5.0, 6.5);
+
+    // Sample positions count at the 8 kHz VAD rate, not 16 kHz
+    for segment in &segments {
+        assert_eq!(segment.start_sample, (segment.start_time_seconds * 8000.0).round() as i64);
+        assert_eq!(segment.audio_data.len() as i64, segment.end_sample - segment.start_sample);
+    }
+}
+
+#[test]
+fn gap_within_merge_gap_is_merged() {
+    let mut processor = processor();
+    processor.set_merge_gap(3.0);
+    let segments = process(&mut processor, &common::two_bursts(16000));
+    assert_eq!(segments.len(), 1);
+    assert_covers(&segments[0], 1.0, 6.5);
+}
+
+#[test]
+fn streaming_matches_whole_file() {
+    let (whole, streamed) = process_both_ways(&common::many_bursts(16000));
+    assert_eq!(whole.len(), 4);
+    assert_eq!(streamed.len(), whole.len());
+    for (expected, found) in whole.iter().zip(&streamed) {
+        assert_eq!((found.start_sample, found.end_sample), (expected.start_sample, expected.end_sample));
+        assert_eq!(found.start_time_seconds, expected.start_time_seconds);
+        assert_eq!(found.audio_data, expected.audio_data);
+        assert_eq!(found.audio_base64, expected.audio_base64);
+        assert!(found.snr_db.zip(expected.snr_db).is_some_and(|(found, expected)| (found - expected).abs() < 1e-6));
+    }
+    assert_covers(&streamed[3], 26.0, 30.0);
+}
+
+#[test]
+fn streaming_resampled_input_matches_whole_file() {
+    let (whole, streamed) = process_both_ways(&common::many_bursts(44100));
+    assert_eq!(streamed.len(), whole.len());
+    for (expected, found) in whole.iter().zip(&streamed) {
+        assert_eq!((found.start_sample, found.end_sample), (expected.start_sample, expected.end_sample));
+        assert_eq!(found.audio_data.len(), expected.audio_data.len());
+    }
+}
+
+#[test]
+fn streaming_reports_speech_probabilities() {
+    let mut processor = processor();
+    processor.set_streaming_threshold(Some(0.0)).unwrap();
+    processor.set_probability_resolution(Some(100)).unwrap();
+    let segments = process(&mut processor, &common::single_burst(16000));
+    assert_eq!(segments.len(), 1);
+
+    let probabilities = processor.take_speech_probabilities().expect("probabilities");
+    assert_eq!(probabilities.len(), 50);
+    assert_eq!(probabilities[5], 0.0);
+    assert_eq!(probabilities[20], 1.0);
+}
+
+#[test]
+fn benchmark_times_every_stage() {
+    let path = common::many_bursts(44100);
+    let timings = processor().benchmark_pipeline(path.to_str().unwrap()).expect("benchmark fixture");
+    std::fs::remove_file(&path).ok();
+
+    let stages = [timings.decode_ms, timings.resample_ms, timings.vad_ms, timings.merge_ms, timings.encode_ms];
+    assert!(stages.iter().all(|&ms| ms > 0.0), "a stage went untimed: {:?}", timings);
+    // Stages run one after another, with only bookkeeping between them
+    let stage_sum: f64 = stages.iter().sum();
+    assert!(stage_sum <= timings.total_ms && stage_sum >= 0.5 * timings.total_ms, "stages sum to {:.2}ms of {:.2}ms", stage_sum, timings.total_ms);
+}
+
+#[test]
+fn found_segments_are_corrected_by_the_merged_set() {
+    for streaming_threshold in [None, Some(0.0)] {
+        let path = common::many_bursts(16000);
+        let mut processor = processor();
+        processor.set_streaming_threshold(streaming_threshold).unwrap();
+        let found = RefCell::new(Vec::new());
+        let merged = RefCell::new(Vec::new());
+        let segments = processor
+            .process_audio_file_streaming(path.to_str().unwrap(), |_, _, _| {}, |update| match update {
+                SegmentUpdate::Found(index, segment) => found.borrow_mut().push((index, segment.start_sample, segment.end_sample)),
+                SegmentUpdate::Merged(segments) => *merged.borrow_mut() = segments.iter().map(|segment| (segment.start_sample, segment.end_sample)).collect(),
+            })
+            .expect("process fixture");
+        std::fs::remove_file(&path).ok();
+
+        // Every burst is reported as it is found, and merging the close ones leaves four
+        let found = found.into_inner();
+        assert_eq!(segments.len(), 4);
+        assert!(found.len() > segments.len(), "only {} segments found before merging", found.len());
+        for (expected_index, &(index, start, end)) in found.iter().enumerate() {
+            assert_eq!(index, expected_index);
+            assert!(
+                segments.iter().any(|segment| segment.start_sample <= start && end <= segment.end_sample),
+                "found segment {}-{} is not inside a final segment", start, end,
+            );
+        }
+
+        // The last merged update is exactly what the run returns
+        let merged = merged.into_inner();
+        assert_eq!(merged.len(), segments.len());
+        for (&boundaries, segment) in merged.iter().zip(&segments) {
+            assert_eq!(boundaries, (segment.start_sample, segment.end_sample));
+        }
+    }
+}
+
+#[test]
+fn skipped_silence_is_not_run_through_vad() {
+    let path = common::write_fixture("padded", 16000, 64.0, &[(31.0, 33.0)]);
+    // Segments of a run and the number of chunks VAD looked at
+    let run = |skip_silence: bool| {
+        let mut processor = processor();
+        processor.set_streaming_threshold(None).unwrap();
+        processor.set_skip_silence(skip_silence);
+        let chunks = RefCell::new(None);
+        let segments = processor
+            .process_audio_file_with_progress(path.to_str().unwrap(), "", |step, _, details| {
+                if step == "Speech detection complete" {
+                    let count = details.and_then(|details| details.strip_prefix("Processed ")?.strip_suffix(" audio chunks")?.parse::<usize>().ok());
+                    *chunks.borrow_mut() = count;
+                }
+            })
+            .expect("process fixture");
+        (segments, chunks.into_inner().expect("chunk count"))
+    };
+    let (skipped, skipped_chunks) = run(true);
+    let (full, full_chunks) = run(false);
+    std::fs::remove_file(&path).ok();
+
+    // Two seconds of tone and half a second of margin either side, of 64 seconds
+    assert!(skipped_chunks * 16 < full_chunks, "VAD ran on {} of {} chunks", skipped_chunks, full_chunks);
+    assert_eq!(skipped.len(), 1);
+    assert_covers(&skipped[0], 31.0, 33.0);
+    assert_eq!(full.len(), 1);
+    // VAD chunks start where the audible span does, so edges may shift by less than a
+    // chunk: 512 samples at 16 kHz
+    let chunk_size = 512;
+    assert!((skipped[0].start_sample - full[0].start_sample).abs() < chunk_size, "starts at {} instead of {}", skipped[0].start_sample, full[0].start_sample);
+    assert!((skipped[0].end_sample - full[0].end_sample).abs() < chunk_size, "ends at {} instead of {}", skipped[0].end_sample, full[0].end_sample);
+}